@@ -12,9 +12,8 @@ use mcp2518fd::{
     message::tx::TxMessage,
     settings::{
         BitTimeConfiguration, DataBitTimeConfiguration, FifoConfiguration, FifoMode,
-        FilterConfiguration, FilterMatchMode, IoConfiguration, NominalBitTimeConfiguration,
-        OscillatorConfiguration, RxFifoConfiguration, Settings, TxEventFifoConfiguration,
-        TxQueueConfiguration,
+        FilterConfiguration, FilterMatchMode, NominalBitTimeConfiguration, RxFifoConfiguration,
+        Settings, TxEventFifoConfiguration, TxQueueConfiguration,
     },
     spi::MCP2518FD,
 };
@@ -93,37 +92,27 @@ fn main() -> ! {
 
         // Configure the chip with some reasonable settings
         can.configure(
-            Settings {
-                // Standard for 40MHz XTAL
-                oscillator: OscillatorConfiguration::default(),
-                // Use default values for IOCON register
-                io_configuration: IoConfiguration::new(),
-                // Configure the bit timings (assumes a 40MHz input clock)
-                bit_time_configuration: BitTimeConfiguration::new(
-                    NominalBitTimeConfiguration::RATE_500_KBIT,
-                    DataBitTimeConfiguration::RATE_2_MBIT,
-                ),
-                // Store the last 12 transmitted messages in the TEF with timestamps
-                tx_event_fifo: Some(TxEventFifoConfiguration::new(12).with_timestamps(false)),
-                // Configure TXQ to have priority over all other FIFOs, and to
-                // hold up to 8 messages with a max payload size of 32 bytes
-                tx_queue: Some(TxQueueConfiguration::new(
-                    HIGHEST_FIFO_PRIORITY,
-                    8,
-                    PayloadSize::Bytes32,
-                )),
-                // Enable the Time Based Counter (required for timestamps to be
-                // recorded as non-zero)
-                enable_time_based_counter: true,
-                // Do not filter by any data bits
-                data_bits_to_match: None,
-                // Do not interrupt on CAN bus errors
-                enable_can_error_interrupts: false,
-                // Do not interrupt on SPI comms errors
-                enable_spi_error_interrupt: false,
-                // Do not interrupt on RAM ECC errors
-                enable_ecc_error_interrupt: false,
-            },
+            // Configure the bit timings (assumes a 40MHz input clock), everything
+            // not set below is disabled
+            Settings::new(BitTimeConfiguration::new(
+                NominalBitTimeConfiguration::RATE_500_KBIT,
+                DataBitTimeConfiguration::RATE_2_MBIT,
+            ))
+            // Store the last 12 transmitted messages in the TEF
+            .with_tx_event_fifo(TxEventFifoConfiguration::new(12).with_timestamps(false))
+            // Configure TXQ to have priority over all other FIFOs, and to
+            // hold up to 8 messages with a max payload size of 32 bytes
+            .with_tx_queue(TxQueueConfiguration::new(
+                HIGHEST_FIFO_PRIORITY,
+                8,
+                PayloadSize::Bytes32,
+            ))
+            // Enable the Time Based Counter (required for timestamps to be
+            // recorded as non-zero)
+            .with_time_based_counter(true)
+            // Interrupt when an RX FIFO, TX FIFO, or TXQ interrupt is pending
+            .with_rx_interrupt(true)
+            .with_tx_interrupt(true),
             &mut timer,
         )
         .expect("Failed to configure MCP2518FD");
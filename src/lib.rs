@@ -8,6 +8,9 @@ pub mod message;
 pub mod settings;
 pub mod spi;
 
+#[cfg(test)]
+mod mock;
+
 pub use spi::ConfigError;
 pub use spi::Error;
 pub use spi::MCP2518FD;
@@ -22,6 +22,15 @@ macro_rules! impl_register {
             }
         }
     };
+    ($ident:ident, $variant:ident, $volatile_bits:expr) => {
+        impl $crate::memory::Register for $ident {
+            const VOLATILE_BITS: u32 = $volatile_bits;
+
+            fn get_address() -> $crate::memory::SFRAddress {
+                $crate::memory::SFRAddress::$variant
+            }
+        }
+    };
 }
 
 macro_rules! software_clearable {
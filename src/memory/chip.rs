@@ -16,7 +16,11 @@ bitfield! {
 }
 
 impl_to_from_u32!(OscillatorControlRegister);
-impl_register!(OscillatorControlRegister, OSC);
+impl_register!(
+    OscillatorControlRegister,
+    OSC,
+    (1 << 8) | (1 << 10) | (1 << 12)
+);
 
 bitfield! {
     pub struct IoControlRegister(u32);
@@ -37,13 +41,84 @@ bitfield! {
 }
 
 impl_to_from_u32!(IoControlRegister);
-impl_register!(IoControlRegister, IOCON);
+impl_register!(IoControlRegister, IOCON, (1 << 16) | (1 << 17));
+
+/// One of the two pins that can be used as GPIO instead of an interrupt pin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GpioPin {
+    /// INT0/GPIO0/XSTBY, the TX interrupt pin by default. Can't be used as
+    /// GPIO while `IOCON.XSTBYEN` is set.
+    Gpio0,
+    /// INT1/GPIO1, the RX interrupt pin by default
+    Gpio1,
+}
+
+/// What a [`GpioPin`] is used for (`IOCON.PMx` and `IOCON.TRISx`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PinMode {
+    /// The pin signals the TX (GPIO0) or RX (GPIO1) interrupt
+    Interrupt,
+    Input,
+    Output,
+}
+
+impl IoControlRegister {
+    pub fn pin_mode(&self, pin: GpioPin) -> PinMode {
+        let (pm, tris) = match pin {
+            GpioPin::Gpio0 => (self.pm0(), self.tris0()),
+            GpioPin::Gpio1 => (self.pm1(), self.tris1()),
+        };
+
+        match (pm, tris) {
+            (false, _) => PinMode::Interrupt,
+            (true, true) => PinMode::Input,
+            (true, false) => PinMode::Output,
+        }
+    }
+
+    pub fn set_pin_mode(&mut self, pin: GpioPin, mode: PinMode) {
+        let pm = mode != PinMode::Interrupt;
+        let tris = mode != PinMode::Output;
+
+        match pin {
+            GpioPin::Gpio0 => {
+                self.set_pm0(pm);
+                self.set_tris0(tris);
+            }
+            GpioPin::Gpio1 => {
+                self.set_pm1(pm);
+                self.set_tris1(tris);
+            }
+        }
+    }
+
+    /// Sets the output latch of the given pin, which only drives the pin
+    /// while it is configured as [`PinMode::Output`]
+    pub fn set_latch(&mut self, pin: GpioPin, high: bool) {
+        match pin {
+            GpioPin::Gpio0 => self.set_lat0(high),
+            GpioPin::Gpio1 => self.set_lat1(high),
+        }
+    }
+
+    /// Reads the level of the given pin
+    pub fn input(&self, pin: GpioPin) -> bool {
+        match pin {
+            GpioPin::Gpio0 => self.gpio0(),
+            GpioPin::Gpio1 => self.gpio1(),
+        }
+    }
+}
 
 bitfield! {
     pub struct CrcRegister(u32);
     impl Debug;
     u8;
-    pub u16, crc, _: 0, 15;
+    /// CRC computed by the chip for the last SPI transaction that failed its
+    /// CRC check
+    pub u16, crc, _: 15, 0;
     _crcerrif, _set_crcerrif: 16;
     _ferrif, _set_ferrif: 17;
     pub crcerrie, set_crcerrie: 24;
@@ -69,7 +144,7 @@ impl CrcRegister {
 }
 
 impl_to_from_u32!(CrcRegister);
-impl_register!(CrcRegister, CRC);
+impl_register!(CrcRegister, CRC, 0x0003_FFFF);
 
 bitfield! {
     pub struct EccControlRegister(u32);
@@ -112,7 +187,7 @@ impl EccStatusRegister {
 }
 
 impl_to_from_u32!(EccStatusRegister);
-impl_register!(EccStatusRegister, ECCSTAT);
+impl_register!(EccStatusRegister, ECCSTAT, u32::MAX);
 
 bitfield! {
     pub struct DeviceIdRegister(u32);
@@ -123,4 +198,9 @@ bitfield! {
 }
 
 impl_to_from_u32!(DeviceIdRegister);
-impl_register!(DeviceIdRegister, DEVID);
+impl_register!(DeviceIdRegister, DEVID, u32::MAX);
+
+impl DeviceIdRegister {
+    /// Value of the `ID` field on an MCP2518FD (the MCP2517FD reads 0)
+    pub const MCP2518FD_ID: u8 = 0x1;
+}
@@ -29,7 +29,8 @@ pub enum DataBits {
     Bits18 = 18,
 }
 
-#[derive(Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum WakeupFilterTime {
     T00Filter = 0,
@@ -54,7 +55,19 @@ pub enum OperationMode {
 }
 
 /// All times are in arbitration bit times
-#[derive(Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+///
+/// The delay applies to the node as a whole, not per FIFO. Whenever a frame
+/// is ready to be sent, the controller picks the pending message with the
+/// highest priority (`TXPRI`) across the TXQ and all TX FIFOs. After that
+/// frame has been transmitted, the controller waits for the configured number
+/// of bit times before arbitrating for the next one, no matter which FIFO it
+/// comes from. FIFO priorities therefore only decide the order in which
+/// messages leave the node, while the delay caps how often they can.
+///
+/// For example, with `Delay16` configured and a high priority FIFO holding 3
+/// messages, those messages are sent first with at least 16 idle bit times in
+/// between each one, and any lower priority FIFOs wait until it is empty.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum InterTransmissionDelay {
@@ -70,9 +83,34 @@ pub enum InterTransmissionDelay {
     Delay512 = 9,
     Delay1024 = 10,
     Delay2048 = 11,
+    #[num_enum(alternatives = [13, 14, 15])]
     Delay4096 = 12,
 }
 
+impl InterTransmissionDelay {
+    /// Number of arbitration bit times inserted between two transmissions
+    pub fn bit_times(&self) -> u16 {
+        match self {
+            Self::NoDelay => 0,
+            delay => 1 << (*delay as u8),
+        }
+    }
+}
+
+/// Summary of how the controller schedules transmissions, see
+/// [`CanControlRegister::transmit_schedule_hint`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TransmitScheduleHint {
+    /// Idle arbitration bit times inserted after every transmitted frame
+    pub inter_transmission_delay_bit_times: u16,
+    /// Whether the per FIFO retransmission attempts (`TXAT`) are honored. If
+    /// not, failed frames are retried an unlimited number of times.
+    pub restricted_retransmission: bool,
+    /// Whether the TXQ takes part in transmit scheduling
+    pub tx_queue_enabled: bool,
+}
+
 bitfield! {
     pub struct CanControlRegister(u32);
     impl Debug;
@@ -90,13 +128,17 @@ bitfield! {
     pub stef, set_stef: 19;
     pub txqen, set_txqen: 20;
     _opmod, _: 23, 21;
-    _, _set_reqop: 26, 24;
+    _reqop, _set_reqop: 26, 24;
     pub abat, set_abat: 27;
     _txbws, _set_txbws: 31, 28;
 }
 
 impl_to_from_u32!(CanControlRegister);
-impl_register!(CanControlRegister, C1CON);
+impl_register!(
+    CanControlRegister,
+    C1CON,
+    (1 << 11) | (0b111 << 21) | (1 << 27)
+);
 
 impl CanControlRegister {
     pub fn dncnt(&self) -> Result<DataBits, TryFromPrimitiveError<DataBits>> {
@@ -107,6 +149,16 @@ impl CanControlRegister {
         self._set_dncnt(bits.into())
     }
 
+    /// The number of data bits the filters compare for standard frames, or
+    /// `None` if data byte filtering is disabled. Like on the chip, the
+    /// reserved values above 18 compare 18 bits.
+    pub fn data_bits_to_match(&self) -> Option<DataBits> {
+        match self._dncnt() {
+            0 => None,
+            bits => DataBits::try_from(bits.min(18)).ok(),
+        }
+    }
+
     pub fn wft(&self) -> Result<WakeupFilterTime, TryFromPrimitiveError<WakeupFilterTime>> {
         WakeupFilterTime::try_from(self._wft())
     }
@@ -126,6 +178,15 @@ impl CanControlRegister {
         self._set_reqop(mode.into());
     }
 
+    /// The operation mode that was last requested, which may differ from
+    /// [`CanControlRegister::opmode`] while a mode change is pending
+    pub fn requested_opmode(&self) -> OperationMode {
+        match OperationMode::try_from(self._reqop()) {
+            Ok(val) => val,
+            Err(_) => OperationMode::Unknown,
+        }
+    }
+
     pub fn txbws(
         &self,
     ) -> Result<InterTransmissionDelay, TryFromPrimitiveError<InterTransmissionDelay>> {
@@ -135,6 +196,41 @@ impl CanControlRegister {
     pub fn set_txbws(&mut self, delay: InterTransmissionDelay) {
         self._set_txbws(delay.into());
     }
+
+    /// Explains how the controller will schedule transmissions with this
+    /// configuration. See [`InterTransmissionDelay`] for how the delay and
+    /// the FIFO priorities interact.
+    ///
+    /// For example, at 500 kbit/s with `Delay16` a FIFO at priority 31 holding
+    /// 3 messages sends them back to back with 32 µs of idle bus in between,
+    /// and a FIFO at priority 0 only gets its turn 32 µs after the last of
+    /// them:
+    ///
+    /// ```
+    /// use mcp2518fd::memory::controller::configuration::{
+    ///     CanControlRegister, InterTransmissionDelay,
+    /// };
+    ///
+    /// let mut c1con = CanControlRegister::from(0);
+    /// c1con.set_txbws(InterTransmissionDelay::Delay16);
+    /// c1con.set_rtxat(true);
+    ///
+    /// let hint = c1con.transmit_schedule_hint();
+    /// assert_eq!(hint.inter_transmission_delay_bit_times, 16);
+    /// assert!(hint.restricted_retransmission);
+    /// assert!(!hint.tx_queue_enabled);
+    ///
+    /// // One bit time at 500 kbit/s is 2 µs
+    /// let gap_ns = hint.inter_transmission_delay_bit_times as u32 * 2_000;
+    /// assert_eq!(gap_ns, 32_000);
+    /// ```
+    pub fn transmit_schedule_hint(&self) -> TransmitScheduleHint {
+        TransmitScheduleHint {
+            inter_transmission_delay_bit_times: self.txbws().map_or(0, |delay| delay.bit_times()),
+            restricted_retransmission: self.rtxat(),
+            tx_queue_enabled: self.txqen(),
+        }
+    }
 }
 
 bitfield! {
@@ -178,7 +274,7 @@ bitfield! {
     impl Debug;
     u8;
     pub tdcv, set_tdcv: 5, 0;
-    pub tdco, set_tdco: 13, 8;
+    _tdco, _set_tdco: 14, 8;
     _tdcmod, _set_tdcmod: 17, 16;
     pub sid11en, set_sid11en: 24;
     pub edgflten, set_edgflten: 25;
@@ -197,10 +293,20 @@ impl TransmitterDelayCompensationRegister {
     pub fn set_tdcmod(&mut self, filter: TransmitterDelayCompensationMode) {
         self._set_tdcmod(filter.into())
     }
+
+    /// The 7-bit two's complement offset, in SYSCLK periods (-64 to 63)
+    pub fn tdco(&self) -> i8 {
+        ((self._tdco() << 1) as i8) >> 1
+    }
+
+    /// Sets the offset, only the lower 7 bits of `offset` are kept
+    pub fn set_tdco(&mut self, offset: i8) {
+        self._set_tdco(offset as u8 & 0x7F)
+    }
 }
 
 impl_to_from_u32!(TransmitterDelayCompensationRegister);
-impl_register!(TransmitterDelayCompensationRegister, C1TDC);
+impl_register!(TransmitterDelayCompensationRegister, C1TDC, 0x3F);
 
 bitfield! {
     pub struct TimeBasedCounterRegister(u32);
@@ -210,7 +316,7 @@ bitfield! {
 }
 
 impl_to_from_u32!(TimeBasedCounterRegister);
-impl_register!(TimeBasedCounterRegister, C1TBC);
+impl_register!(TimeBasedCounterRegister, C1TBC, u32::MAX);
 
 bitfield! {
     pub struct TimeStampControlRegister(u32);
@@ -17,7 +17,38 @@ bitfield! {
 }
 
 impl_to_from_u32!(TransmitReceiveErrorCountRegister);
-impl_register!(TransmitReceiveErrorCountRegister, C1TREC);
+impl_register!(TransmitReceiveErrorCountRegister, C1TREC, u32::MAX);
+
+/// Fault confinement state of the CAN controller, from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ErrorState {
+    /// Both error counters are below 96
+    ErrorActive,
+    /// At least one error counter reached the warning threshold of 96
+    ErrorWarning,
+    /// At least one error counter exceeded 127. The node only sends passive
+    /// error frames.
+    ErrorPassive,
+    /// The transmit error counter exceeded 255 and the node no longer takes
+    /// part in bus activity
+    BusOff,
+}
+
+impl TransmitReceiveErrorCountRegister {
+    /// Derives the fault confinement state from the status flags
+    pub fn error_state(&self) -> ErrorState {
+        if self.txbo() {
+            ErrorState::BusOff
+        } else if self.txbp() || self.rxbp() {
+            ErrorState::ErrorPassive
+        } else if self.ewarn() {
+            ErrorState::ErrorWarning
+        } else {
+            ErrorState::ErrorActive
+        }
+    }
+}
 
 bitfield! {
     pub struct BusDiagnosticRegister0(u32);
@@ -30,7 +61,7 @@ bitfield! {
 }
 
 impl_to_from_u32!(BusDiagnosticRegister0);
-impl_register!(BusDiagnosticRegister0, C1BDIAG0);
+impl_register!(BusDiagnosticRegister0, C1BDIAG0, u32::MAX);
 
 bitfield! {
     pub struct BusDiagnosticRegister1(u32);
@@ -54,4 +85,4 @@ bitfield! {
 }
 
 impl_to_from_u32!(BusDiagnosticRegister1);
-impl_register!(BusDiagnosticRegister1, C1BDIAG1);
+impl_register!(BusDiagnosticRegister1, C1BDIAG1, u32::MAX);
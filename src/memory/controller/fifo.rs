@@ -2,11 +2,18 @@ use bitfield::bitfield;
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
 use crate::memory::{RepeatedRegister, SFRAddress, RAM_BASE_ADDRESS};
+use crate::message::HEADER_SIZE_DWORDS;
 use crate::{impl_register, impl_to_from_u32, software_clearable, software_settable};
 
 pub const HIGHEST_FIFO_PRIORITY: u8 = 0b0001_1111;
 pub const LOWEST_FIFO_PRIORITY: u8 = 0;
 
+/// Number of FIFOs besides the TEF and TXQ (FIFO 1 to FIFO 31)
+pub const MAX_FIFO_COUNT: usize = 31;
+
+/// Max number of message objects a single FIFO, the TEF, or the TXQ can hold
+pub const MAX_FIFO_DEPTH: u8 = 32;
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
@@ -44,6 +51,71 @@ pub enum FifoNumber {
     Fifo31 = 31,
 }
 
+/// A set of FIFOs backed by a bitmask, where bit N represents FIFO N. This
+/// matches the layout of the various interrupt status registers (bit 0, which
+/// refers to the TXQ in some of them, is never set).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FifoSet(u32);
+
+impl FifoSet {
+    /// Creates an empty set
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    /// Creates a set from a bitmask, ignoring bit 0
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits & !1)
+    }
+
+    /// Gets the underlying bitmask
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub fn contains(&self, fifo_number: FifoNumber) -> bool {
+        self.0 & (1 << fifo_number as u8) != 0
+    }
+
+    pub fn insert(&mut self, fifo_number: FifoNumber) {
+        self.0 |= 1 << fifo_number as u8;
+    }
+
+    pub fn remove(&mut self, fifo_number: FifoNumber) {
+        self.0 &= !(1 << fifo_number as u8);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Iterates over the FIFOs in the set in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = FifoNumber> {
+        let bits = self.0;
+
+        (1..=MAX_FIFO_COUNT as u8)
+            .filter(move |fifo| bits & (1 << fifo) != 0)
+            .filter_map(|fifo| FifoNumber::try_from(fifo).ok())
+    }
+}
+
+impl FromIterator<FifoNumber> for FifoSet {
+    fn from_iter<I: IntoIterator<Item = FifoNumber>>(iter: I) -> Self {
+        let mut set = Self::new();
+
+        for fifo_number in iter {
+            set.insert(fifo_number);
+        }
+
+        set
+    }
+}
+
 bitfield! {
     pub struct UserAddressRegister(u32);
     u32;
@@ -68,6 +140,8 @@ pub enum UserAddressKind {
 impl RepeatedRegister for UserAddressRegister {
     type Index = UserAddressKind;
 
+    const VOLATILE_BITS: u32 = u32::MAX;
+
     fn get_address_for(index: Self::Index) -> SFRAddress {
         match index {
             UserAddressKind::TxEventFifo => SFRAddress::C1TEFUA,
@@ -134,16 +208,20 @@ impl TxEventFifoControlRegister {
         self._fsize() + 1
     }
 
-    /// Max size is 32.
+    /// Size must be between 1 and 32, other values are clamped into that range.
     pub fn set_fifo_size(&mut self, size: u8) {
-        self._set_fsize(match size.cmp(&32u8) {
-            core::cmp::Ordering::Greater => 31,
-            _ => size - 1,
-        });
+        self._set_fsize(size.clamp(1, MAX_FIFO_DEPTH) - 1);
+    }
+
+    /// Number of bytes of RAM allocated to the TEF with this configuration
+    pub fn ram_bytes(&self) -> u32 {
+        let timestamp_bytes = if self.teftsen() { 4 } else { 0 };
+
+        self.fifo_size() as u32 * (HEADER_SIZE_DWORDS as u32 * 4 + timestamp_bytes)
     }
 }
 
-impl_register!(TxEventFifoControlRegister, C1TEFCON);
+impl_register!(TxEventFifoControlRegister, C1TEFCON, (1 << 8) | (1 << 10));
 impl_to_from_u32!(TxEventFifoControlRegister);
 
 bitfield! {
@@ -156,23 +234,41 @@ bitfield! {
     _tefovif, _set_tefovif: 3;
 }
 
-impl_register!(TxEventFifoStatusRegister, C1TEFSTA);
+impl_register!(TxEventFifoStatusRegister, C1TEFSTA, u32::MAX);
 impl_to_from_u32!(TxEventFifoStatusRegister);
 
 impl TxEventFifoStatusRegister {
     software_clearable!(tefovif, clear_tefovif);
 }
 
+/// Number of times a message is retransmitted after losing arbitration or
+/// hitting an error (the `TXAT` field of the TXQ and each TX FIFO).
+///
+/// This only takes effect while `C1CON.RTXAT` is set, which `configure` always
+/// does. With `RTXAT` clear the controller ignores `TXAT` and retransmits
+/// every message an unlimited number of times.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Default, IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
 pub enum RetransmissionAttempts {
+    /// The message is transmitted once and never retried
     Disabled = 0,
+    /// The message is retried up to 3 times, so transmitted up to 4 times in
+    /// total
     ThreeRetries = 1,
+    /// The message is retried until it is sent successfully (or aborted)
     #[default]
     #[num_enum(alternatives = [3])]
     UnlimitedRetries = 2,
 }
 
+/// Size of the data field of every object in a FIFO.
+///
+/// When an RX FIFO receives a frame with more data than its payload size, the
+/// data is truncated to fit and the rest is lost. The DLC in the header still
+/// reflects the original length. Classic CAN frames never carry more than 8
+/// bytes, but RX FIFOs that may receive CAN FD frames should use `Bytes64`
+/// unless the application knows the sizes in advance. See
+/// `MCP2518FD::filter_may_truncate`.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
 pub enum PayloadSize {
@@ -196,9 +292,25 @@ impl PayloadSize {
             Self::Bytes24 => 24,
             Self::Bytes32 => 32,
             Self::Bytes48 => 48,
-            Self::Bytes64 => 65,
+            Self::Bytes64 => 64,
         }
     }
+
+    /// Returns the smallest payload size that can hold `len` bytes of data, or
+    /// `None` if `len` is larger than 64
+    pub fn for_len(len: usize) -> Option<Self> {
+        Some(match len {
+            0..=8 => Self::Bytes8,
+            9..=12 => Self::Bytes12,
+            13..=16 => Self::Bytes16,
+            17..=20 => Self::Bytes20,
+            21..=24 => Self::Bytes24,
+            25..=32 => Self::Bytes32,
+            33..=48 => Self::Bytes48,
+            49..=64 => Self::Bytes64,
+            _ => return None,
+        })
+    }
 }
 
 bitfield! {
@@ -238,12 +350,9 @@ impl TxQueueControlRegister {
         self._fsize() + 1
     }
 
-    /// Max size is 32.
+    /// Size must be between 1 and 32, other values are clamped into that range.
     pub fn set_fifo_size(&mut self, size: u8) {
-        self._set_fsize(match size.cmp(&32u8) {
-            core::cmp::Ordering::Greater => 31,
-            _ => size - 1,
-        });
+        self._set_fsize(size.clamp(1, MAX_FIFO_DEPTH) - 1);
     }
 
     pub fn payload_size(&self) -> PayloadSize {
@@ -256,9 +365,20 @@ impl TxQueueControlRegister {
     pub fn set_payload_size(&mut self, size: PayloadSize) {
         self._set_plsize(size.into());
     }
+
+    /// Number of bytes of RAM allocated to the TXQ with this configuration
+    pub fn ram_bytes(&self) -> u32 {
+        let object_bytes = HEADER_SIZE_DWORDS * 4 + self.payload_size().num_bytes();
+
+        self.fifo_size() as u32 * object_bytes as u32
+    }
 }
 
-impl_register!(TxQueueControlRegister, C1TXQCON);
+impl_register!(
+    TxQueueControlRegister,
+    C1TXQCON,
+    (1 << 8) | (1 << 9) | (1 << 10)
+);
 impl_to_from_u32!(TxQueueControlRegister);
 
 bitfield! {
@@ -271,10 +391,12 @@ bitfield! {
     _txerr, _set_txerr: 5;
     _txlarb, _set_txlarb: 6;
     _txabt, _set_txabt: 7;
+    /// Index of the message slot the TXQ will transmit next. This is a
+    /// position within the queue, not the number of queued messages.
     pub txqci, _: 12, 8;
 }
 
-impl_register!(TxQueueStatusRegister, C1TXQSTA);
+impl_register!(TxQueueStatusRegister, C1TXQSTA, u32::MAX);
 impl_to_from_u32!(TxQueueStatusRegister);
 
 impl TxQueueStatusRegister {
@@ -282,17 +404,69 @@ impl TxQueueStatusRegister {
     software_clearable!(txerr, clear_txerr);
     software_clearable!(txlarb, clear_txlarb);
     software_clearable!(txabt, clear_txabt);
+
+    /// Decodes why the last transmission failed, see [`TxError::decode`]
+    pub fn tx_error(&self) -> TxError {
+        TxError::decode(self.txerr(), self.txlarb(), self.txabt())
+    }
+
+    /// Clears the `TXERR`, `TXLARB`, and `TXABT` flags
+    pub fn clear_tx_errors(&mut self) {
+        self.clear_txerr();
+        self.clear_txlarb();
+        self.clear_txabt();
+    }
+}
+
+/// Why the last transmission of the TXQ or a TX FIFO failed, as reported by
+/// its status register
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TxError {
+    None,
+    /// Another node won arbitration (`TXLARB`). The frame is retried
+    /// according to the retransmission attempts.
+    ArbitrationLost,
+    /// The transmission was aborted (`TXABT`)
+    Aborted,
+    /// A bus error occurred while the frame was transmitted (`TXERR`)
+    BusError,
+}
+
+impl TxError {
+    /// Decodes the `TXERR`, `TXLARB`, and `TXABT` flags. If several are set,
+    /// the most severe one wins: an abort over a bus error over lost
+    /// arbitration.
+    pub fn decode(txerr: bool, txlarb: bool, txabt: bool) -> Self {
+        if txabt {
+            Self::Aborted
+        } else if txerr {
+            Self::BusError
+        } else if txlarb {
+            Self::ArbitrationLost
+        } else {
+            Self::None
+        }
+    }
 }
 
 bitfield! {
     pub struct FifoControlRegister(u32);
     u8;
+    /// TX: FIFO not full interrupt enable, RX: FIFO not empty interrupt enable
     pub tfnrfnie, set_tfnrfnie: 0;
+    /// TX: FIFO half empty interrupt enable, RX: FIFO half full interrupt enable.
+    /// See [`crate::settings::TxWatermark`] and [`crate::settings::RxWatermark`].
     pub tfhrfhie, set_tfhrfhie: 1;
+    /// TX: FIFO empty interrupt enable, RX: FIFO full interrupt enable
     pub tferffie, set_tferffie: 2;
+    /// RX only
     pub rxovie, set_rxovie: 3;
+    /// TX only
     pub txatie, set_txatie: 4;
+    /// RX only
     pub rxtsen, set_rxtsen: 5;
+    /// TX only
     pub rtren, set_rtren: 6;
     pub txen, set_txen: 7;
     _uinc, _set_uinc: 8;
@@ -326,12 +500,9 @@ impl FifoControlRegister {
         self._fsize() + 1
     }
 
-    /// Max size is 32.
+    /// Size must be between 1 and 32, other values are clamped into that range.
     pub fn set_fifo_size(&mut self, size: u8) {
-        self._set_fsize(match size.cmp(&32u8) {
-            core::cmp::Ordering::Greater => 31,
-            _ => size - 1,
-        });
+        self._set_fsize(size.clamp(1, MAX_FIFO_DEPTH) - 1);
     }
 
     pub fn payload_size(&self) -> PayloadSize {
@@ -344,11 +515,23 @@ impl FifoControlRegister {
     pub fn set_payload_size(&mut self, size: PayloadSize) {
         self._set_plsize(size.into());
     }
+
+    /// Number of bytes of RAM allocated to this FIFO with this configuration.
+    /// Only RX FIFOs can store timestamps.
+    pub fn ram_bytes(&self) -> u32 {
+        let timestamp_bytes = if !self.txen() && self.rxtsen() { 4 } else { 0 };
+        let object_bytes =
+            HEADER_SIZE_DWORDS * 4 + timestamp_bytes + self.payload_size().num_bytes();
+
+        self.fifo_size() as u32 * object_bytes as u32
+    }
 }
 
 impl RepeatedRegister for FifoControlRegister {
     type Index = FifoNumber;
 
+    const VOLATILE_BITS: u32 = (1 << 8) | (1 << 9) | (1 << 10);
+
     fn get_address_for(fifo_number: Self::Index) -> SFRAddress {
         match fifo_number {
             FifoNumber::Fifo1 => SFRAddress::C1FIFOCON1,
@@ -397,6 +580,9 @@ bitfield! {
     _txerr, _set_txerr: 5;
     _txlarb, _set_txlarb: 6;
     _txabt, _set_txabt: 7;
+    /// TX: index of the message slot the FIFO will transmit next. RX: index
+    /// of the message slot the next received message will be stored in. This
+    /// is a position within the FIFO, not the number of messages in it.
     pub fifoci, _: 12, 8;
 }
 
@@ -408,11 +594,25 @@ impl FifoStatusRegister {
     software_clearable!(txerr, clear_txerr);
     software_clearable!(txlarb, clear_txlarb);
     software_clearable!(txabt, clear_txabt);
+
+    /// Decodes why the last transmission failed, see [`TxError::decode`]
+    pub fn tx_error(&self) -> TxError {
+        TxError::decode(self.txerr(), self.txlarb(), self.txabt())
+    }
+
+    /// Clears the `TXERR`, `TXLARB`, and `TXABT` flags
+    pub fn clear_tx_errors(&mut self) {
+        self.clear_txerr();
+        self.clear_txlarb();
+        self.clear_txabt();
+    }
 }
 
 impl RepeatedRegister for FifoStatusRegister {
     type Index = FifoNumber;
 
+    const VOLATILE_BITS: u32 = u32::MAX;
+
     fn get_address_for(fifo_number: Self::Index) -> SFRAddress {
         match fifo_number {
             FifoNumber::Fifo1 => SFRAddress::C1FIFOSTA1,
@@ -449,3 +649,86 @@ impl RepeatedRegister for FifoStatusRegister {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_fifo_size_is_clamped_to_one() {
+        let mut tef = TxEventFifoControlRegister(0);
+        tef.set_fifo_size(0);
+        assert_eq!(tef.fifo_size(), 1);
+
+        let mut txq = TxQueueControlRegister(0);
+        txq.set_fifo_size(0);
+        assert_eq!(txq.fifo_size(), 1);
+
+        let mut fifo = FifoControlRegister(0);
+        fifo.set_fifo_size(0);
+        assert_eq!(fifo.fifo_size(), 1);
+    }
+
+    #[test]
+    fn fifo_size_is_clamped_to_32() {
+        let mut fifo = FifoControlRegister(0);
+
+        fifo.set_fifo_size(32);
+        assert_eq!(fifo.fifo_size(), 32);
+
+        fifo.set_fifo_size(u8::MAX);
+        assert_eq!(fifo.fifo_size(), 32);
+
+        // Other fields are left alone
+        assert_eq!(fifo.0, 31 << 24);
+    }
+
+    #[test]
+    fn payload_size_for_len() {
+        assert_eq!(PayloadSize::for_len(0), Some(PayloadSize::Bytes8));
+        assert_eq!(PayloadSize::for_len(8), Some(PayloadSize::Bytes8));
+        assert_eq!(PayloadSize::for_len(13), Some(PayloadSize::Bytes16));
+        assert_eq!(PayloadSize::for_len(33), Some(PayloadSize::Bytes48));
+        assert_eq!(PayloadSize::for_len(64), Some(PayloadSize::Bytes64));
+        assert_eq!(PayloadSize::for_len(65), None);
+    }
+
+    #[test]
+    fn payload_size_for_len_fits_every_len() {
+        for len in 0..=64 {
+            assert!(PayloadSize::for_len(len).unwrap().num_bytes() >= len);
+        }
+    }
+
+    /// `TXAT` encodings from DS20006027B: 0b00 disabled, 0b01 three retries,
+    /// 0b10 and 0b11 unlimited
+    #[test]
+    fn retransmission_attempts_encoding() {
+        let matrix = [
+            (RetransmissionAttempts::Disabled, 0b00),
+            (RetransmissionAttempts::ThreeRetries, 0b01),
+            (RetransmissionAttempts::UnlimitedRetries, 0b10),
+        ];
+
+        for (attempts, txat) in matrix {
+            let mut fifo = FifoControlRegister(0);
+            fifo.set_retransmission_attempts(attempts);
+            assert_eq!(fifo.0, txat << 21);
+            assert_eq!(fifo.retransmission_attempts(), attempts);
+
+            let mut txq = TxQueueControlRegister(0);
+            txq.set_retransmission_attempts(attempts);
+            assert_eq!(txq.0, txat << 21);
+            assert_eq!(txq.retransmission_attempts(), attempts);
+        }
+
+        assert_eq!(
+            FifoControlRegister(0b11 << 21).retransmission_attempts(),
+            RetransmissionAttempts::UnlimitedRetries
+        );
+        assert_eq!(
+            TxQueueControlRegister(0b11 << 21).retransmission_attempts(),
+            RetransmissionAttempts::UnlimitedRetries
+        );
+    }
+}
@@ -1,11 +1,16 @@
 use bitfield::bitfield;
+use embedded_can::Id;
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
 use crate::impl_to_from_u32;
 use crate::memory::{RepeatedRegister, SFRAddress};
 
+use super::configuration::DataBits;
 use super::fifo::FifoNumber;
 
+/// Number of acceptance filters (filter 0 to filter 31)
+pub const MAX_FILTER_COUNT: usize = 32;
+
 #[derive(Clone, Copy, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
 pub enum FilterControlNumber {
@@ -161,6 +166,61 @@ bitfield! {
 
 impl_to_from_u32!(FilterObjectRegister);
 
+impl FilterObjectRegister {
+    /// Replicates the chip's acceptance logic for this filter object and the
+    /// given mask against a message ID.
+    ///
+    /// For standard frames, the first `data_bits` bits of `data` (see
+    /// `CiCON.DNCNT`) are additionally compared against the EID bits of the
+    /// filter and mask, the same way [`data_bits_to_eid`] packs them. Pass
+    /// `None` when data byte filtering is disabled. Bits past the end of
+    /// `data` aren't compared.
+    pub fn matches(
+        &self,
+        mask: &MaskRegister,
+        id: Id,
+        data: &[u8],
+        data_bits: Option<DataBits>,
+    ) -> bool {
+        let (sid, eid, ide) = match id {
+            Id::Standard(id) => (id.as_raw(), 0, false),
+            Id::Extended(id) => (id.standard_id().as_raw(), id.as_raw() & 0x3FFFF, true),
+        };
+
+        // When MIDE is set, only frames with the IDE bit matching EXIDE pass
+        if mask.mide() && self.exide() != ide {
+            return false;
+        }
+
+        if (sid ^ self.sid()) & mask.msid() != 0 {
+            return false;
+        }
+
+        if ide {
+            return (eid ^ self.eid()) & mask.meid() == 0;
+        }
+
+        let data_bits = data_bits.map_or(0, |bits| u8::from(bits) as usize);
+        let data_bits = data_bits.min(data.len() * 8);
+
+        let data_mask = (1 << data_bits) - 1;
+
+        (data_bits_to_eid(data, data_bits) ^ self.eid()) & mask.meid() & data_mask == 0
+    }
+}
+
+/// Packs the first `data_bits` bits of `data` into EID bits the way the chip
+/// compares them against a standard frame's data: the MSB of the first data
+/// byte lands in EID bit 0, its LSB in EID bit 7, and so on up to bit 6 of
+/// the third byte in EID bit 17. `data` must hold at least `data_bits` bits.
+pub fn data_bits_to_eid(data: &[u8], data_bits: usize) -> u32 {
+    (0..data_bits.min(18)).fold(0, |eid, bit| {
+        let value = (data[bit / 8] >> (7 - bit % 8)) & 1;
+
+        eid | ((value as u32) << bit)
+    })
+}
+
 impl RepeatedRegister for FilterObjectRegister {
     type Index = FilterNumber;
 
@@ -254,3 +314,115 @@ impl RepeatedRegister for MaskRegister {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_can::{ExtendedId, StandardId};
+
+    fn standard(id: u16) -> Id {
+        Id::Standard(StandardId::new(id).unwrap())
+    }
+
+    fn extended(id: u32) -> Id {
+        Id::Extended(ExtendedId::new(id).unwrap())
+    }
+
+    fn filter(sid: u16, eid: u32, exide: bool) -> FilterObjectRegister {
+        let mut object = FilterObjectRegister(0);
+        object.set_sid(sid);
+        object.set_eid(eid);
+        object.set_exide(exide);
+        object
+    }
+
+    fn mask(msid: u16, meid: u32, mide: bool) -> MaskRegister {
+        let mut mask = MaskRegister(0);
+        mask.set_msid(msid);
+        mask.set_meid(meid);
+        mask.set_mide(mide);
+        mask
+    }
+
+    #[test]
+    fn standard_id_only() {
+        let object = filter(0x123, 0, false);
+        let mask = mask(0x7FF, 0, true);
+
+        assert!(object.matches(&mask, standard(0x123), &[], None));
+        assert!(!object.matches(&mask, standard(0x124), &[], None));
+        // MIDE rejects extended frames with the same SID
+        assert!(!object.matches(&mask, extended(0x123 << 18), &[], None));
+    }
+
+    #[test]
+    fn extended_id() {
+        let object = filter(0x123, 0x2ABCD, true);
+        let mask = mask(0x7FF, 0x3FFFF, true);
+
+        assert!(object.matches(&mask, extended((0x123 << 18) | 0x2ABCD), &[], None));
+        assert!(!object.matches(&mask, extended((0x123 << 18) | 0x2ABCC), &[], None));
+        assert!(!object.matches(&mask, standard(0x123), &[], None));
+    }
+
+    #[test]
+    fn masked_out_bit_is_ignored() {
+        let object = filter(0x120, 0, false);
+        let mask = mask(0x7FF & !0x001, 0, false);
+
+        assert!(object.matches(&mask, standard(0x120), &[], None));
+        assert!(object.matches(&mask, standard(0x121), &[], None));
+        assert!(!object.matches(&mask, standard(0x122), &[], None));
+        // Without MIDE, extended frames with a matching SID pass too
+        assert!(object.matches(&mask, extended(0x121 << 18), &[], None));
+    }
+
+    #[test]
+    fn data_ignored_without_dncnt() {
+        let object = filter(0x123, 0x3FFFF, false);
+        let mask = mask(0x7FF, 0x3FFFF, true);
+
+        assert!(object.matches(&mask, standard(0x123), &[0x00, 0x00, 0x00], None));
+    }
+
+    #[test]
+    fn dncnt_1_compares_msb_of_first_byte_against_eid0() {
+        let object = filter(0x123, 0b1, false);
+        let mask = mask(0x7FF, 0x3FFFF, true);
+        let bits = Some(DataBits::Bits1);
+
+        assert!(object.matches(&mask, standard(0x123), &[0x80], bits));
+        assert!(object.matches(&mask, standard(0x123), &[0xFF], bits));
+        assert!(!object.matches(&mask, standard(0x123), &[0x7F], bits));
+    }
+
+    #[test]
+    fn dncnt_18_compares_up_to_bit_6_of_third_byte() {
+        let data = [0xA5, 0x3C, 0x40];
+        // DB0[7] -> EID0 ... DB0[0] -> EID7, DB1[7] -> EID8 ... DB2[6] -> EID17
+        let eid = 0xA5u32.reverse_bits() >> 24 | (0x3Cu32.reverse_bits() >> 24) << 8 | 1 << 17;
+        assert_eq!(data_bits_to_eid(&data, 18), eid);
+
+        let object = filter(0x123, eid, false);
+        let mask = mask(0x7FF, 0x3FFFF, true);
+        let bits = Some(DataBits::Bits18);
+
+        assert!(object.matches(&mask, standard(0x123), &data, bits));
+        // DB2[5] and below aren't compared
+        assert!(object.matches(&mask, standard(0x123), &[0xA5, 0x3C, 0x5F], bits));
+        assert!(!object.matches(&mask, standard(0x123), &[0xA5, 0x3C, 0x00], bits));
+        assert!(!object.matches(&mask, standard(0x123), &[0xA4, 0x3C, 0x40], bits));
+    }
+
+    #[test]
+    fn data_bits_masked_out() {
+        let object = filter(0x123, 0b01, false);
+        // Only EID1 (DB0[6]) is compared
+        let mask = mask(0x7FF, 0b10, true);
+        let bits = Some(DataBits::Bits2);
+
+        assert!(object.matches(&mask, standard(0x123), &[0x00], bits));
+        assert!(object.matches(&mask, standard(0x123), &[0x80], bits));
+        assert!(!object.matches(&mask, standard(0x123), &[0x40], bits));
+    }
+}
@@ -1,4 +1,4 @@
-use bitfield::{bitfield, Bit};
+use bitfield::{bitfield, Bit, BitMut};
 
 use crate::{impl_register, impl_to_from_u32, software_clearable};
 
@@ -15,10 +15,11 @@ pub enum RxInterruptFlagCode {
 impl From<u8> for RxInterruptFlagCode {
     fn from(value: u8) -> Self {
         match value {
-            0b0000_0001..=0b0001_1111 => Self::FifoInterrupt(value.try_into().unwrap()),
+            0b0000_0001..=0b0001_1111 => {
+                value.try_into().map_or(Self::Reserved, Self::FifoInterrupt)
+            }
             0b0100_0000 => Self::NoInterrupt,
-            0b0100_0001..=0b0111_1111 | 0b0010_0001..=0b0011_1111 | 0b0000_0000 => Self::Reserved,
-            _ => unreachable!(),
+            _ => Self::Reserved,
         }
     }
 }
@@ -37,9 +38,10 @@ impl From<u8> for TxInterruptFlagCode {
         match value {
             0b0100_0000 => Self::NoInterrupt,
             0b0000_0000 => Self::TxqInterrupt,
-            0b0000_0001..=0b0001_1111 => Self::FifoInterrupt(value.try_into().unwrap()),
-            0b0100_0001..=0b0111_1111 | 0b0010_0001..=0b0011_1111 => Self::Reserved,
-            _ => unreachable!(),
+            0b0000_0001..=0b0001_1111 => {
+                value.try_into().map_or(Self::Reserved, Self::FifoInterrupt)
+            }
+            _ => Self::Reserved,
         }
     }
 }
@@ -68,8 +70,9 @@ impl From<u8> for InterruptFlagCode {
         match value {
             0b0100_0000 => Self::NoInterrupt,
             0b0000_0000 => Self::TxqInterrupt,
-            0b0000_0001..=0b0001_1111 => Self::FifoInterrupt(value.try_into().unwrap()),
-            0b0010_0001..=0b0011_1111 | 0b0100_1011..=0b0111_1111 => Self::Reserved,
+            0b0000_0001..=0b0001_1111 => {
+                value.try_into().map_or(Self::Reserved, Self::FifoInterrupt)
+            }
             0b0100_0001 => Self::ErrorInterrupt,
             0b0100_0010 => Self::WakeUpInterrupt,
             0b0100_0011 => Self::ReceiveFifoOverflowInterrupt,
@@ -80,7 +83,7 @@ impl From<u8> for InterruptFlagCode {
             0b0100_1000 => Self::InvalidMessageOccurred,
             0b0100_1001 => Self::TransmitEventFifoInterrupt,
             0b0100_1010 => Self::TransmitAttemptInterrupt,
-            _ => unreachable!(),
+            _ => Self::Reserved,
         }
     }
 }
@@ -102,11 +105,33 @@ impl InterruptCodeRegister {
         self._icode().into()
     }
 
-    /// Gets the number of the filter that matched the recieved message. It's
-    /// not clear what the value of this field is when a non-RX interrupt is
-    /// raised.
-    pub fn filter_hit(&self) -> FilterNumber {
-        self._filhit().try_into().unwrap()
+    /// Gets the raw `FILHIT` field without checking whether it is meaningful.
+    ///
+    /// The field holds the number of the filter that matched the message at
+    /// the head of the RX FIFO reported by [`InterruptCodeRegister::rx_code`].
+    /// It is only valid while `RXCODE` reports a FIFO interrupt; otherwise the
+    /// chip leaves whatever value was there before, which should not be
+    /// relied upon. Prefer [`InterruptCodeRegister::rx_filter_hit`].
+    pub fn filter_hit_raw(&self) -> u8 {
+        self._filhit()
+    }
+
+    /// Gets the raw `FILHIT` field as a filter number. See
+    /// [`InterruptCodeRegister::filter_hit_raw`] for when this is valid.
+    pub fn filter_hit(&self) -> Option<FilterNumber> {
+        self._filhit().try_into().ok()
+    }
+
+    /// Gets the RX FIFO that raised the highest priority RX interrupt together
+    /// with the filter that matched the message at its head. Returns `None`
+    /// when no RX interrupt is pending, since `FILHIT` isn't valid then.
+    pub fn rx_filter_hit(&self) -> Option<(FifoNumber, FilterNumber)> {
+        match self.rx_code() {
+            RxInterruptFlagCode::FifoInterrupt(fifo_number) => {
+                Some((fifo_number, self.filter_hit()?))
+            }
+            _ => None,
+        }
     }
 
     pub fn tx_code(&self) -> TxInterruptFlagCode {
@@ -116,10 +141,75 @@ impl InterruptCodeRegister {
     pub fn rx_code(&self) -> RxInterruptFlagCode {
         self._rxcode().into()
     }
+
+    /// Decodes the highest priority pending interrupt into a [`CanEvent`].
+    /// Returns `None` if no interrupt is pending.
+    pub fn event(&self) -> Option<CanEvent> {
+        Some(match self.generic_code() {
+            InterruptFlagCode::NoInterrupt => return None,
+            InterruptFlagCode::FifoInterrupt(fifo_number) => {
+                // The generic code doesn't say which direction the FIFO is
+                // configured for, but the RX code does
+                if self.rx_code() == RxInterruptFlagCode::FifoInterrupt(fifo_number) {
+                    CanEvent::RxAvailable(fifo_number)
+                } else {
+                    CanEvent::TxFifo(fifo_number)
+                }
+            }
+            InterruptFlagCode::TxqInterrupt => CanEvent::TxQueue,
+            InterruptFlagCode::ErrorInterrupt => CanEvent::CanError,
+            InterruptFlagCode::WakeUpInterrupt => CanEvent::BusWakeUp,
+            InterruptFlagCode::ReceiveFifoOverflowInterrupt => CanEvent::RxOverflow,
+            InterruptFlagCode::AddressErrorInterrupt
+            | InterruptFlagCode::RxTxMabOverOrUnderflow => CanEvent::SystemError,
+            InterruptFlagCode::TbcOverflow => CanEvent::TbcOverflow,
+            InterruptFlagCode::OperationModeChangeOccurred => CanEvent::ModeChanged,
+            InterruptFlagCode::InvalidMessageOccurred => CanEvent::InvalidMessage,
+            InterruptFlagCode::TransmitEventFifoInterrupt => CanEvent::TxEvent,
+            InterruptFlagCode::TransmitAttemptInterrupt => CanEvent::TxAttemptsExhausted,
+            InterruptFlagCode::Reserved => CanEvent::Unknown,
+        })
+    }
+}
+
+/// The highest priority pending interrupt, as decoded by
+/// [`InterruptCodeRegister::event`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CanEvent {
+    /// An RX FIFO raised one of its enabled interrupts (not empty, half full,
+    /// or full)
+    RxAvailable(FifoNumber),
+    /// A TX FIFO raised one of its enabled interrupts (not full, half empty,
+    /// or empty)
+    TxFifo(FifoNumber),
+    /// The TXQ raised one of its enabled interrupts (not full or empty)
+    TxQueue,
+    /// An RX FIFO overflowed, see
+    /// [`crate::spi::MCP2518FD::overflowed_rx_fifos`] for which one
+    RxOverflow,
+    /// A TX FIFO ran out of retransmission attempts
+    TxAttemptsExhausted,
+    /// The TEF raised one of its enabled interrupts
+    TxEvent,
+    /// The CAN controller's error state changed
+    CanError,
+    /// An invalid message was received
+    InvalidMessage,
+    /// An internal address or message assembly buffer error occurred
+    SystemError,
+    /// The operation mode changed
+    ModeChanged,
+    /// The time base counter wrapped around
+    TbcOverflow,
+    /// Bus activity was detected while in Sleep mode
+    BusWakeUp,
+    /// The chip reported a reserved interrupt code
+    Unknown,
 }
 
 impl_to_from_u32!(InterruptCodeRegister);
-impl_register!(InterruptCodeRegister, C1VEC);
+impl_register!(InterruptCodeRegister, C1VEC, u32::MAX);
 
 bitfield! {
     pub struct InterruptRegister(u32);
@@ -154,7 +244,41 @@ bitfield! {
     pub ivmie, set_ivmie: 31;
 }
 
+/// The top level interrupt enables in `C1INT`. Each variant's value is the
+/// position of its enable bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum InterruptKind {
+    Tx = 16,
+    Rx = 17,
+    TbcOverflow = 18,
+    ModeChange = 19,
+    TxEvent = 20,
+    EccError = 24,
+    SpiCrcError = 25,
+    TxAttempt = 26,
+    RxOverflow = 27,
+    SystemError = 28,
+    CanError = 29,
+    WakeUp = 30,
+    InvalidMessage = 31,
+}
+
 impl InterruptRegister {
+    pub fn is_enabled(&self, kind: InterruptKind) -> bool {
+        self.bit(kind as usize)
+    }
+
+    pub fn set_enabled(&mut self, kind: InterruptKind, enabled: bool) {
+        self.set_bit(kind as usize, enabled)
+    }
+
+    /// The software clearable system interrupt flags that are currently set
+    pub fn pending_system_interrupts(&self) -> SystemInterrupts {
+        SystemInterrupts(self.0 as u16 & SystemInterrupts::ALL.0)
+    }
+
     software_clearable!(tbcif, clear_tbcif);
     software_clearable!(modif, clear_modif);
     software_clearable!(serrif, clear_serrif);
@@ -164,7 +288,69 @@ impl InterruptRegister {
 }
 
 impl_to_from_u32!(InterruptRegister);
-impl_register!(InterruptRegister, C1INT);
+impl_register!(InterruptRegister, C1INT, 0xFFFF);
+
+/// A set of the software clearable system interrupt flags in `C1INT`, see
+/// [`crate::spi::MCP2518FD::clear_system_interrupts`]. Flags can be combined
+/// with `|`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SystemInterrupts(u16);
+
+impl SystemInterrupts {
+    /// Time base counter overflow (`TBCIF`)
+    pub const TBC_OVERFLOW: Self = Self(1 << 2);
+    /// Operation mode change (`MODIF`)
+    pub const MODE_CHANGE: Self = Self(1 << 3);
+    /// System error (`SERRIF`)
+    pub const SYSTEM_ERROR: Self = Self(1 << 12);
+    /// CAN bus error (`CERRIF`)
+    pub const CAN_ERROR: Self = Self(1 << 13);
+    /// Bus wake up (`WAKIF`)
+    pub const WAKE_UP: Self = Self(1 << 14);
+    /// Invalid message (`IVMIF`)
+    pub const INVALID_MESSAGE: Self = Self(1 << 15);
+    /// All of the above
+    pub const ALL: Self = Self(
+        Self::TBC_OVERFLOW.0
+            | Self::MODE_CHANGE.0
+            | Self::SYSTEM_ERROR.0
+            | Self::CAN_ERROR.0
+            | Self::WAKE_UP.0
+            | Self::INVALID_MESSAGE.0,
+    );
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// The flags as they are laid out in `C1INT`
+    pub const fn bits(&self) -> u16 {
+        self.0
+    }
+
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl core::ops::BitOr for SystemInterrupts {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl core::ops::BitOrAssign for SystemInterrupts {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
 
 bitfield! {
     pub struct RxInterruptStatusRegister(u32);
@@ -210,7 +396,7 @@ impl RxInterruptStatusRegister {
 }
 
 impl_to_from_u32!(RxInterruptStatusRegister);
-impl_register!(RxInterruptStatusRegister, C1RXIF);
+impl_register!(RxInterruptStatusRegister, C1RXIF, u32::MAX);
 
 bitfield! {
     pub struct RxOverflowInterruptStatusRegister(u32);
@@ -256,7 +442,7 @@ impl RxOverflowInterruptStatusRegister {
 }
 
 impl_to_from_u32!(RxOverflowInterruptStatusRegister);
-impl_register!(RxOverflowInterruptStatusRegister, C1RXOVIF);
+impl_register!(RxOverflowInterruptStatusRegister, C1RXOVIF, u32::MAX);
 
 bitfield! {
     pub struct TxInterruptStatusRegister(u32);
@@ -307,7 +493,7 @@ impl TxInterruptStatusRegister {
 }
 
 impl_to_from_u32!(TxInterruptStatusRegister);
-impl_register!(TxInterruptStatusRegister, C1TXIF);
+impl_register!(TxInterruptStatusRegister, C1TXIF, u32::MAX);
 
 bitfield! {
     pub struct TxAttemptInterruptStatusRegister(u32);
@@ -358,4 +544,115 @@ impl TxAttemptInterruptStatusRegister {
 }
 
 impl_to_from_u32!(TxAttemptInterruptStatusRegister);
-impl_register!(TxAttemptInterruptStatusRegister, C1TXATIF);
+impl_register!(TxAttemptInterruptStatusRegister, C1TXATIF, u32::MAX);
+
+/// The five adjacent interrupt status registers, `C1INT` through `C1TXATIF`,
+/// as read by [`crate::spi::MCP2518FD::read_all_interrupt_statuses`]
+#[derive(Debug)]
+pub struct InterruptStatuses {
+    pub interrupts: InterruptRegister,
+    pub rx: RxInterruptStatusRegister,
+    pub tx: TxInterruptStatusRegister,
+    pub rx_overflow: RxOverflowInterruptStatusRegister,
+    pub tx_attempt: TxAttemptInterruptStatusRegister,
+}
+
+impl InterruptStatuses {
+    /// Decodes the five registers starting at `C1INT`
+    pub(crate) fn from_words([interrupts, rx, tx, rx_overflow, tx_attempt]: [u32; 5]) -> Self {
+        Self {
+            interrupts: interrupts.into(),
+            rx: rx.into(),
+            tx: tx.into(),
+            rx_overflow: rx_overflow.into(),
+            tx_attempt: tx_attempt.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rx_code_fifo_range() {
+        assert_eq!(
+            RxInterruptFlagCode::from(1),
+            RxInterruptFlagCode::FifoInterrupt(FifoNumber::Fifo1)
+        );
+        assert_eq!(
+            RxInterruptFlagCode::from(31),
+            RxInterruptFlagCode::FifoInterrupt(FifoNumber::Fifo31)
+        );
+        assert_eq!(
+            RxInterruptFlagCode::from(0x40),
+            RxInterruptFlagCode::NoInterrupt
+        );
+    }
+
+    #[test]
+    fn rx_code_out_of_range_is_reserved() {
+        // FIFO 0 is the TXQ, which never raises an RX interrupt
+        assert_eq!(RxInterruptFlagCode::from(0), RxInterruptFlagCode::Reserved);
+        assert_eq!(RxInterruptFlagCode::from(32), RxInterruptFlagCode::Reserved);
+        assert_eq!(
+            RxInterruptFlagCode::from(0x41),
+            RxInterruptFlagCode::Reserved
+        );
+        assert_eq!(
+            RxInterruptFlagCode::from(0x7F),
+            RxInterruptFlagCode::Reserved
+        );
+    }
+
+    #[test]
+    fn tx_code_range() {
+        assert_eq!(
+            TxInterruptFlagCode::from(0),
+            TxInterruptFlagCode::TxqInterrupt
+        );
+        assert_eq!(
+            TxInterruptFlagCode::from(31),
+            TxInterruptFlagCode::FifoInterrupt(FifoNumber::Fifo31)
+        );
+        assert_eq!(TxInterruptFlagCode::from(32), TxInterruptFlagCode::Reserved);
+        assert_eq!(
+            TxInterruptFlagCode::from(0x7F),
+            TxInterruptFlagCode::Reserved
+        );
+    }
+
+    #[test]
+    fn generic_code_out_of_range_is_reserved() {
+        assert_eq!(
+            InterruptFlagCode::from(0x4A),
+            InterruptFlagCode::TransmitAttemptInterrupt
+        );
+        assert_eq!(InterruptFlagCode::from(0x20), InterruptFlagCode::Reserved);
+        assert_eq!(InterruptFlagCode::from(0x4B), InterruptFlagCode::Reserved);
+
+        // ICODE is 7 bits wide, so the register can't hold anything higher
+        assert_eq!(InterruptCodeRegister(0x7F).event(), Some(CanEvent::Unknown));
+    }
+
+    #[test]
+    fn filter_hit_boundaries() {
+        // RXCODE = FIFO 1, FILHIT = 0
+        let icode = InterruptCodeRegister(1 << 24);
+        assert!(matches!(
+            icode.rx_filter_hit(),
+            Some((FifoNumber::Fifo1, FilterNumber::Filter0))
+        ));
+
+        let icode = InterruptCodeRegister((1 << 24) | (31 << 8));
+        assert!(matches!(
+            icode.rx_filter_hit(),
+            Some((FifoNumber::Fifo1, FilterNumber::Filter31))
+        ));
+
+        // FILHIT isn't valid without an RX interrupt
+        let icode = InterruptCodeRegister((0x40 << 24) | (5 << 8));
+        assert!(icode.rx_filter_hit().is_none());
+        assert_eq!(icode.filter_hit_raw(), 5);
+    }
+}
@@ -1,5 +1,6 @@
 use bitfield::bitfield;
 
+use crate::memory::controller::fifo::FifoSet;
 use crate::{impl_register, impl_to_from_u32, software_settable};
 
 bitfield! {
@@ -75,5 +76,13 @@ impl TransmitRequestRegister {
     software_settable!(txreq31, set_txreq31);
 }
 
+impl TransmitRequestRegister {
+    /// The TX FIFOs with a pending transmit request. Whether the TXQ has one
+    /// is given by [`TransmitRequestRegister::txreq_txq`].
+    pub fn fifos(&self) -> FifoSet {
+        FifoSet::from_bits(self.0)
+    }
+}
+
 impl_to_from_u32!(TransmitRequestRegister);
-impl_register!(TransmitRequestRegister, C1TXREQ);
+impl_register!(TransmitRequestRegister, C1TXREQ, u32::MAX);
@@ -1,6 +1,8 @@
 pub mod chip;
 pub mod controller;
 
+use num_enum::TryFromPrimitive;
+
 /// Base address of the chip's RAM segment. Used for verifying memory accesses
 /// and calculating FIFO pointer addresses.
 pub const RAM_BASE_ADDRESS: u32 = 0x400;
@@ -8,13 +10,22 @@ pub const RAM_BASE_ADDRESS: u32 = 0x400;
 /// End address of the chip's RAM segment. Used for verifying memory accesses
 pub const RAM_END_ADDRESS: u32 = 0xBFF;
 
-/// Calculates whether a RAM address range is valid without making any IO calls
+/// Total size of the chip's RAM segment in bytes (2 KB)
+pub const RAM_SIZE_BYTES: u32 = RAM_END_ADDRESS - RAM_BASE_ADDRESS + 1;
+
+/// Calculates whether a RAM address range is valid without making any IO calls.
+/// The range covers `address..address + data_size`, so it may end right after
+/// [`RAM_END_ADDRESS`].
 pub fn is_valid_ram_address(address: u32, data_size: usize) -> bool {
-    address >= RAM_BASE_ADDRESS && (address + data_size as u32) <= RAM_END_ADDRESS
+    address >= RAM_BASE_ADDRESS && (address + data_size as u32) <= RAM_END_ADDRESS + 1
 }
 
 /// Represents an SFR register that has a single unique memory location
 pub trait Register {
+    /// Bits that are read-only or updated by the hardware, and so may read
+    /// back differently to what was last written
+    const VOLATILE_BITS: u32 = 0;
+
     fn get_address() -> SFRAddress;
 }
 
@@ -22,10 +33,15 @@ pub trait Register {
 pub trait RepeatedRegister {
     type Index: Copy;
 
+    /// See [`Register::VOLATILE_BITS`]
+    const VOLATILE_BITS: u32 = 0;
+
     fn get_address_for(index: Self::Index) -> SFRAddress;
 }
 
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, TryFromPrimitive)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u16)]
 pub enum SFRAddress {
     /* Chip Specific Registers */
     OSC = 0xE00,
@@ -288,3 +304,59 @@ pub enum SFRAddress {
     C1FLTOBJ31 = 0x2E8,
     C1MASK31 = 0x2EC,
 }
+
+/// Number of controller SFRs, `C1CON` through `C1MASK31` (including the
+/// reserved word at 0x4C)
+pub const CONTROLLER_SFR_COUNT: usize = (SFRAddress::C1MASK31 as usize + 4) / 4;
+
+/// Number of chip specific SFRs, `OSC` through `DEVID`
+pub const CHIP_SFR_COUNT: usize = (SFRAddress::DEVID as usize + 4 - SFRAddress::OSC as usize) / 4;
+
+/// The raw value of every SFR at one point in time, as read by
+/// [`crate::spi::MCP2518FD::dump_registers`]. Mostly useful for logging the
+/// complete chip state when debugging.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RegisterSnapshot {
+    /// Controller SFRs, indexed by address / 4
+    pub controller: [u32; CONTROLLER_SFR_COUNT],
+    /// Chip specific SFRs, indexed by (address - `OSC`) / 4
+    pub chip: [u32; CHIP_SFR_COUNT],
+}
+
+impl RegisterSnapshot {
+    /// Gets the value the given SFR had when the snapshot was taken
+    pub fn get(&self, address: SFRAddress) -> u32 {
+        let address = address as usize;
+
+        if address >= SFRAddress::OSC as usize {
+            self.chip[(address - SFRAddress::OSC as usize) / 4]
+        } else {
+            self.controller[address / 4]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ram_access_ending_at_last_byte() {
+        assert!(is_valid_ram_address(RAM_END_ADDRESS - 3, 4));
+        assert!(is_valid_ram_address(
+            RAM_BASE_ADDRESS,
+            RAM_SIZE_BYTES as usize
+        ));
+    }
+
+    #[test]
+    fn ram_access_past_last_byte() {
+        assert!(!is_valid_ram_address(RAM_END_ADDRESS - 2, 4));
+        assert!(!is_valid_ram_address(
+            RAM_BASE_ADDRESS,
+            RAM_SIZE_BYTES as usize + 1
+        ));
+        assert!(!is_valid_ram_address(RAM_BASE_ADDRESS - 4, 4));
+    }
+}
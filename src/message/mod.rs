@@ -2,11 +2,37 @@ pub mod rx;
 pub mod tx;
 
 /// The length in DWORDs of the TX and RX header objects
-const HEADER_SIZE_DWORDS: usize = 2;
+pub(crate) const HEADER_SIZE_DWORDS: usize = 2;
 
 /// The maximum data buffer (paylod) size in bytes
 pub const MAX_FD_BUFFER_SIZE: usize = 64;
 
+/// Whether a frame is a classic CAN 2.0 frame or a CAN FD frame, as given by
+/// its FDF bit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FrameFormat {
+    /// CAN 2.0 frame with at most 8 bytes of data. DLCs above 8 still mean 8
+    /// bytes.
+    Classic,
+    /// CAN FD frame with up to 64 bytes of data
+    Fd,
+}
+
+impl FrameFormat {
+    pub(crate) fn from_fdf(fdf: bool) -> Self {
+        if fdf {
+            Self::Fd
+        } else {
+            Self::Classic
+        }
+    }
+
+    pub fn is_fd(&self) -> bool {
+        *self == Self::Fd
+    }
+}
+
 pub fn dlc_for_len(len: usize, is_fd: bool) -> Option<u8> {
     if is_fd {
         Some(match len {
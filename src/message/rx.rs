@@ -3,7 +3,8 @@ use embedded_can::{ExtendedId, Id, StandardId};
 
 use crate::memory::controller::filter::FilterNumber;
 
-use super::{len_for_dlc, HEADER_SIZE_DWORDS, MAX_FD_BUFFER_SIZE};
+use super::tx::TxMessage;
+use super::{len_for_dlc, FrameFormat, HEADER_SIZE_DWORDS, MAX_FD_BUFFER_SIZE};
 
 bitfield! {
     pub struct RxHeader([u32]);
@@ -43,27 +44,46 @@ impl<T: AsRef<[u32]>> RxHeader<T> {
     }
 }
 
+/// The header of a single received message object
+pub type RxMessageHeader = RxHeader<[u32; HEADER_SIZE_DWORDS]>;
+
+/// A received message. `N` is the size of the payload buffer, so applications
+/// that only ever receive classic frames can use `RxMessage<8>` to save
+/// memory.
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub struct RxMessage {
+pub struct RxMessage<const N: usize = MAX_FD_BUFFER_SIZE> {
     #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
     header: RxHeader<[u32; HEADER_SIZE_DWORDS]>,
     timestamp: Option<u32>,
-    data: [u8; MAX_FD_BUFFER_SIZE],
+    data: [u8; N],
+}
+
+/// An empty message with a zeroed header, useful for initializing buffers
+/// passed to [`crate::spi::MCP2518FD::rx_fifo_drain`]
+impl<const N: usize> Default for RxMessage<N> {
+    fn default() -> Self {
+        Self {
+            header: RxHeader([0u32; HEADER_SIZE_DWORDS]),
+            timestamp: None,
+            data: [0u8; N],
+        }
+    }
 }
 
-impl RxMessage {
-    /// Constructs a new RxMessage from the data found in the chip's RAM
+impl<const N: usize> RxMessage<N> {
+    /// Constructs a new RxMessage from the data found in the chip's RAM.
+    /// Returns `None` if the data doesn't fit in `N` bytes.
     pub fn new(
         header: RxHeader<[u32; HEADER_SIZE_DWORDS]>,
         timestamp: Option<u32>,
         data: &[u8],
-    ) -> Option<RxMessage> {
-        if data.len() > MAX_FD_BUFFER_SIZE {
+    ) -> Option<RxMessage<N>> {
+        if data.len() > N {
             return None;
         }
 
-        let mut buffer = [0u8; MAX_FD_BUFFER_SIZE];
+        let mut buffer = [0u8; N];
         buffer[..data.len()].copy_from_slice(data);
 
         Some(Self {
@@ -94,14 +114,129 @@ impl RxMessage {
         }
     }
 
+    /// Returns which filter was matched when receiving this message
+    pub fn filter_hit(&self) -> FilterNumber {
+        self.header.filter_hit()
+    }
+
+    /// Determines from the header whether or not this message is a remote
+    /// frame
+    pub fn is_remote_frame(&self) -> bool {
+        self.header.rtr()
+    }
+
+    /// Gets the raw Data Length Code from the header. Use
+    /// [`RxMessage::data`] for the payload length in bytes.
+    pub fn dlc(&self) -> u8 {
+        self.header.dlc()
+    }
+
     /// Creates a slice over the data associated with this message with the
-    /// correct length calculated from the DLC
+    /// correct length calculated from the DLC. Classic frames are always
+    /// decoded as such, even when received while in CAN FD mode, so a DLC
+    /// above 8 yields 8 bytes.
     pub fn data(&self) -> &[u8] {
-        &self.data[..len_for_dlc(self.header.dlc(), self.header.fdf()).unwrap()]
+        &self.data[..len_for_dlc(self.header.dlc(), self.header.fdf())
+            .unwrap()
+            .min(N)]
     }
 
     /// Determines from the header whether or not this message is a CAN FD frame
     pub fn is_fd(&self) -> bool {
         self.header.fdf()
     }
+
+    /// Determines from the header whether this message is a classic CAN 2.0
+    /// frame or a CAN FD frame
+    pub fn format(&self) -> FrameFormat {
+        FrameFormat::from_fdf(self.header.fdf())
+    }
+
+    /// Converts this message into a CAN 2.0 message that can be forwarded onto
+    /// a classic CAN segment. Returns `None` if the payload is longer than 8
+    /// bytes. The FD-only BRS and ESI bits are dropped.
+    pub fn to_classic_tx(&self) -> Option<TxMessage<N>> {
+        if self.header.rtr() {
+            return TxMessage::new_remote(self.id(), self.header.dlc());
+        }
+
+        TxMessage::new_2_0(self.id(), self.data())
+    }
+
+    /// Converts this message into an identical message that can be transmitted
+    /// again, keeping the FD, BRS, ESI and RTR bits. Classic remote frames with
+    /// a DLC above 8 are clamped to 8.
+    pub fn to_tx(&self) -> TxMessage<N> {
+        if self.header.rtr() {
+            return TxMessage::new_remote(self.id(), self.header.dlc().min(8)).unwrap();
+        }
+
+        if self.header.fdf() {
+            TxMessage::new_fd(self.id(), self.data())
+                .unwrap()
+                .with_bit_rate_switched(self.header.brs())
+                .with_error_status_indicator(self.header.esi())
+        } else {
+            TxMessage::new_2_0(self.id(), self.data()).unwrap()
+        }
+    }
+
+    /// Converts this message into a CAN FD message that can be forwarded onto
+    /// a CAN FD segment. Returns `None` for remote frames since CAN FD has no
+    /// equivalent.
+    pub fn to_fd_tx(&self) -> Option<TxMessage<N>> {
+        if self.header.rtr() {
+            return None;
+        }
+
+        TxMessage::new_fd(self.id(), self.data())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accessors_decode_the_header() {
+        // EID = 0x05678 and SID = 0x48D, DLC 3, IDE, FILHIT 7
+        let header = RxHeader([0x02B3_C48D, 3 | (1 << 4) | (7 << 11)]);
+        let message = RxMessage::<8>::new(header, None, &[1, 2, 3]).unwrap();
+
+        assert_eq!(
+            message.id(),
+            Id::Extended(ExtendedId::new(0x1234_5678).unwrap())
+        );
+        assert!(message.filter_hit() == FilterNumber::Filter7);
+        assert!(!message.is_remote_frame());
+        assert_eq!(message.dlc(), 3);
+        assert_eq!(message.data(), &[1, 2, 3]);
+
+        // SID = 0x123, DLC 2, RTR, FILHIT 31
+        let header = RxHeader([0x123, 2 | (1 << 5) | (31 << 11)]);
+        let message = RxMessage::<8>::new(header, None, &[]).unwrap();
+
+        assert_eq!(message.id(), Id::Standard(StandardId::new(0x123).unwrap()));
+        assert!(message.filter_hit() == FilterNumber::Filter31);
+        assert!(message.is_remote_frame());
+        assert_eq!(message.dlc(), 2);
+    }
+
+    #[test]
+    fn classic_frame_with_dlc_above_8_has_8_bytes() {
+        // SID = 0x123, DLC 15 without FDF
+        let header = RxHeader([0x123, 15]);
+        let message = RxMessage::<64>::new(header, None, &[0xAA; 64]).unwrap();
+
+        assert_eq!(message.format(), FrameFormat::Classic);
+        assert!(!message.is_fd());
+        assert_eq!(message.data(), &[0xAA; 8]);
+
+        // The same DLC with FDF set
+        let header = RxHeader([0x123, 15 | (1 << 7)]);
+        let message = RxMessage::<64>::new(header, None, &[0xAA; 64]).unwrap();
+
+        assert_eq!(message.format(), FrameFormat::Fd);
+        assert_eq!(message.data().len(), 64);
+    }
 }
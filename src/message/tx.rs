@@ -1,7 +1,7 @@
 use bitfield::bitfield;
 use embedded_can::{ExtendedId, Id, StandardId};
 
-use super::{dlc_for_len, len_for_dlc, HEADER_SIZE_DWORDS, MAX_FD_BUFFER_SIZE};
+use super::{dlc_for_len, len_for_dlc, FrameFormat, HEADER_SIZE_DWORDS, MAX_FD_BUFFER_SIZE};
 
 bitfield! {
     pub struct TxHeader([u32]);
@@ -49,25 +49,62 @@ impl PartialOrd for TxHeader<[u32; HEADER_SIZE_DWORDS]> {
     }
 }
 
+/// A message to be transmitted. `N` is the size of the payload buffer, so
+/// applications that only ever send classic frames can use `TxMessage<8>` to
+/// save memory. Payloads longer than `N` bytes are rejected when constructing
+/// the message.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub struct TxMessage {
+pub struct TxMessage<const N: usize = MAX_FD_BUFFER_SIZE> {
     #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
     header: TxHeader<[u32; HEADER_SIZE_DWORDS]>,
-    data: [u8; MAX_FD_BUFFER_SIZE],
+    data: [u8; N],
     data_len: usize,
 }
 
-impl TxMessage {
+impl<const N: usize> TxMessage<N> {
     pub fn new_fd(identifier: impl Into<Id>, data: &[u8]) -> Option<Self> {
         Self::new_with_data(identifier.into(), data, true)
     }
 
+    /// Creates a classic CAN 2.0 message, which never has the FDF bit set.
+    /// These are the only data frames that can be transmitted while the
+    /// controller is in [`OperationMode::NormalCan2`].
+    ///
+    /// [`OperationMode::NormalCan2`]: crate::memory::controller::configuration::OperationMode::NormalCan2
     pub fn new_2_0(identifier: impl Into<Id>, data: &[u8]) -> Option<Self> {
         Self::new_with_data(identifier.into(), data, false)
     }
 
+    /// Creates a CAN FD message with a standard ID. Returns `None` if the ID
+    /// doesn't fit in 11 bits or the data is too long.
+    pub fn new_fd_std(sid: u16, data: &[u8]) -> Option<Self> {
+        Self::new_fd(StandardId::new(sid)?, data)
+    }
+
+    /// Creates a CAN FD message with an extended ID. Returns `None` if the ID
+    /// doesn't fit in 29 bits or the data is too long.
+    pub fn new_fd_ext(eid: u32, data: &[u8]) -> Option<Self> {
+        Self::new_fd(ExtendedId::new(eid)?, data)
+    }
+
+    /// Creates a CAN 2.0 message with a standard ID. Returns `None` if the ID
+    /// doesn't fit in 11 bits or the data is too long.
+    pub fn new_2_0_std(sid: u16, data: &[u8]) -> Option<Self> {
+        Self::new_2_0(StandardId::new(sid)?, data)
+    }
+
+    /// Creates a CAN 2.0 message with an extended ID. Returns `None` if the ID
+    /// doesn't fit in 29 bits or the data is too long.
+    pub fn new_2_0_ext(eid: u32, data: &[u8]) -> Option<Self> {
+        Self::new_2_0(ExtendedId::new(eid)?, data)
+    }
+
     fn new_with_data(identifier: Id, data: &[u8], is_fd: bool) -> Option<Self> {
+        if data.len() > N {
+            return None;
+        }
+
         let mut header = TxHeader([0u32; HEADER_SIZE_DWORDS]);
 
         let dlc = dlc_for_len(data.len(), is_fd)?;
@@ -86,7 +123,7 @@ impl TxMessage {
             }
         }
 
-        let mut data_buf = [0u8; MAX_FD_BUFFER_SIZE];
+        let mut data_buf = [0u8; N];
         data_buf[..data.len()].copy_from_slice(data);
 
         Some(Self {
@@ -117,10 +154,12 @@ impl TxMessage {
             }
         }
 
+        // Remote frames carry no data, so only report as much of the DLC as
+        // fits in the buffer
         Some(Self {
             header,
-            data: [0u8; MAX_FD_BUFFER_SIZE],
-            data_len: dlc as usize,
+            data: [0u8; N],
+            data_len: (dlc as usize).min(N),
         })
     }
 
@@ -134,6 +173,10 @@ impl TxMessage {
         self
     }
 
+    /// Tags the message with a 23 bit sequence number, which the chip copies
+    /// into the TEF object once the message was transmitted. Giving every
+    /// message a unique number (e.g. a wrapping counter) allows matching TEF
+    /// objects back to it with [`TxEventObject::sequence_number`].
     pub fn with_sequence_number(mut self, seq: u32) -> Self {
         self.header.set_seq(seq);
         self
@@ -158,22 +201,116 @@ impl TxMessage {
         &self.data[..self.data_len]
     }
 
-    #[allow(clippy::identity_op)]
+    /// Whether this message is sent as a classic CAN 2.0 frame or a CAN FD
+    /// frame
+    pub fn format(&self) -> FrameFormat {
+        FrameFormat::from_fdf(self.header.fdf())
+    }
+
+    /// Converts this message into a CAN 2.0 message. Returns `None` if the
+    /// payload is longer than 8 bytes. The FD-only BRS and ESI bits are
+    /// dropped, the sequence number is kept.
+    pub fn to_classic(&self) -> Option<TxMessage<N>> {
+        if self.header.rtr() {
+            return Self::new_remote(self.id(), self.header.dlc())
+                .map(|msg| msg.with_sequence_number(self.header.seq()));
+        }
+
+        Self::new_2_0(self.id(), self.data()).map(|msg| msg.with_sequence_number(self.header.seq()))
+    }
+
+    /// Converts this message into a CAN FD message. Returns `None` for remote
+    /// frames since CAN FD has no equivalent. The sequence number is kept.
+    pub fn to_fd(&self) -> Option<TxMessage<N>> {
+        if self.header.rtr() {
+            return None;
+        }
+
+        Self::new_fd(self.id(), self.data()).map(|msg| msg.with_sequence_number(self.header.seq()))
+    }
+
+    /// Encodes the message object as it is laid out in RAM: the header
+    /// followed by the payload. Returns the object length in bytes, which is
+    /// the header plus the payload length given by the DLC, together with a
+    /// buffer holding the object. Only the first `data().len()` payload bytes
+    /// are copied, everything after them up to the next DWORD boundary is
+    /// zero so the object can be written to RAM in whole DWORDs.
     pub fn as_bytes(&self) -> (usize, [u8; HEADER_SIZE_DWORDS * 4 + MAX_FD_BUFFER_SIZE]) {
         let mut buffer = [0u8; HEADER_SIZE_DWORDS * 4 + MAX_FD_BUFFER_SIZE];
 
+        let header_len = HEADER_SIZE_DWORDS * 4;
+        let length = header_len + len_for_dlc(self.header.dlc(), self.header.fdf()).unwrap();
+
         buffer[0..4].copy_from_slice(&self.header.0[0].to_le_bytes());
         buffer[4..8].copy_from_slice(&self.header.0[1].to_le_bytes());
 
-        buffer[8..self.data.len() + 8].copy_from_slice(&self.data);
+        let data_end = header_len + self.data_len;
+
+        buffer[header_len..data_end].copy_from_slice(self.data());
+        buffer[data_end..length.next_multiple_of(4)].fill(0);
+
+        (length, buffer)
+    }
+}
+
+impl<const N: usize> embedded_can::Frame for TxMessage<N> {
+    /// Creates a CAN 2.0 frame if the data fits in 8 bytes, and a CAN FD frame
+    /// otherwise
+    fn new(id: impl Into<Id>, data: &[u8]) -> Option<Self> {
+        if data.len() <= 8 {
+            Self::new_2_0(id, data)
+        } else {
+            Self::new_fd(id, data)
+        }
+    }
+
+    fn new_remote(id: impl Into<Id>, dlc: usize) -> Option<Self> {
+        Self::new_remote(id, u8::try_from(dlc).ok()?)
+    }
+
+    fn is_extended(&self) -> bool {
+        self.header.ide()
+    }
+
+    fn is_remote_frame(&self) -> bool {
+        self.header.rtr()
+    }
+
+    fn id(&self) -> Id {
+        self.id()
+    }
 
-        (
-            len_for_dlc(self.header.dlc(), self.header.fdf()).unwrap() + 8,
-            buffer,
-        )
+    fn dlc(&self) -> usize {
+        if self.header.rtr() {
+            self.header.dlc() as usize
+        } else {
+            self.data_len
+        }
+    }
+
+    fn data(&self) -> &[u8] {
+        if self.header.rtr() {
+            &[]
+        } else {
+            self.data()
+        }
     }
 }
 
+/// An object read from the TEF. In RAM it is laid out as (DS20006027B,
+/// section 4.3):
+///
+/// | Offset | Content                                   |
+/// |--------|-------------------------------------------|
+/// | 0      | TE0: SID, EID, SID11                      |
+/// | 4      | TE1: DLC, IDE, RTR, BRS, FDF, ESI, SEQ    |
+/// | 8      | TE2: timestamp (only if C1TEFCON.TEFTSEN) |
+///
+/// The first two DWORDs are identical to the header of the transmitted
+/// message, which is why they are decoded as a [`TxHeader`]. This includes
+/// the sequence number given with [`TxMessage::with_sequence_number`], so
+/// when every frame is tagged with a unique one, each TEF object confirms
+/// exactly which frame was transmitted.
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct TxEventObject {
@@ -181,3 +318,84 @@ pub struct TxEventObject {
     pub header: TxHeader<[u32; HEADER_SIZE_DWORDS]>,
     pub timestamp: Option<u32>,
 }
+
+/// An empty object with a zeroed header, useful for initializing buffers
+/// passed to [`crate::spi::MCP2518FD::tx_event_fifo_drain`]
+impl Default for TxEventObject {
+    fn default() -> Self {
+        Self {
+            header: TxHeader([0u32; HEADER_SIZE_DWORDS]),
+            timestamp: None,
+        }
+    }
+}
+
+impl TxEventObject {
+    /// The sequence number of the transmitted message, see
+    /// [`TxMessage::with_sequence_number`]
+    pub fn sequence_number(&self) -> u32 {
+        self.header.seq()
+    }
+
+    /// Size in bytes of a TEF object in RAM
+    pub(crate) const fn ram_size(timestamped: bool) -> usize {
+        if timestamped {
+            (HEADER_SIZE_DWORDS + 1) * 4
+        } else {
+            HEADER_SIZE_DWORDS * 4
+        }
+    }
+
+    /// Decodes a TEF object from the raw bytes read from RAM. `bytes` must be
+    /// exactly [`TxEventObject::ram_size`] bytes long, the timestamp is only
+    /// decoded if it is present.
+    pub(crate) fn from_ram_bytes(bytes: &[u8]) -> Self {
+        let dword = |i: usize| u32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap());
+
+        Self {
+            header: TxHeader([dword(0), dword(1)]),
+            timestamp: (bytes.len() > HEADER_SIZE_DWORDS * 4).then(|| dword(HEADER_SIZE_DWORDS)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn as_bytes_3_byte_frame() {
+        let message = TxMessage::<8>::new_2_0_std(0x123, &[1, 2, 3]).unwrap();
+        let (len, bytes) = message.as_bytes();
+
+        assert_eq!(len, 11);
+        assert_eq!(&bytes[8..11], &[1, 2, 3]);
+        assert!(bytes[11..].iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn as_bytes_classic_frame() {
+        let message = TxMessage::<64>::new_2_0_std(0x123, &[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        let (len, bytes) = message.as_bytes();
+
+        assert_eq!(message.format(), FrameFormat::Classic);
+        assert_eq!(len, 16);
+        // SID = 0x123, and DLC 8 without FDF or BRS
+        assert_eq!(&bytes[..8], &[0x23, 0x01, 0, 0, 8, 0, 0, 0]);
+        assert_eq!(&bytes[8..16], &[1, 2, 3, 4, 5, 6, 7, 8]);
+
+        // Classic frames can't carry more than 8 bytes
+        assert!(TxMessage::<64>::new_2_0_std(0x123, &[0; 9]).is_none());
+    }
+
+    #[test]
+    fn as_bytes_pads_fd_frame_to_dlc() {
+        // 10 bytes need DLC 9, which sends 12
+        let message = TxMessage::<64>::new_fd_std(0x123, &[0xFF; 10]).unwrap();
+        let (len, bytes) = message.as_bytes();
+
+        assert_eq!(len, 20);
+        assert_eq!(&bytes[8..18], &[0xFF; 10]);
+        assert!(bytes[18..].iter().all(|byte| *byte == 0));
+    }
+}
@@ -0,0 +1,242 @@
+//! A simulated chip for unit tests. SFRs and RAM are backed by a flat byte
+//! array indexed by address, and every transfer is logged so tests can check
+//! exactly what the driver sent over SPI.
+//!
+//! Hardware behavior (mode changes, FIFO pointers, ...) isn't simulated. Tests
+//! that rely on it install a hook with [`MockSpi::on_write`] instead.
+//!
+//! Driver tests are written like the driver itself, as `async` code that
+//! `maybe_async` turns blocking unless the `async` feature is enabled, and
+//! run with [`block_on`] so they cover both builds.
+
+extern crate std;
+
+use std::boxed::Box;
+use std::vec;
+use std::vec::Vec;
+
+use embedded_hal::spi::{ErrorKind, ErrorType, Operation};
+
+/// Everything up to and including the chip SFRs at 0xE00
+const ADDRESS_SPACE: usize = 0x1000;
+
+type ReadHook = Box<dyn FnMut(&mut [u8], u16)>;
+type WriteHook = Box<dyn FnMut(&mut [u8], u16, &[u8])>;
+
+pub(crate) struct MockSpi {
+    pub memory: Vec<u8>,
+    /// Address and data of every write, in order
+    pub writes: Vec<(u16, Vec<u8>)>,
+    /// Address of every read, in order
+    pub reads: Vec<u16>,
+    /// Number of SPI transactions, including RESET
+    pub transactions: usize,
+    /// Transactions fail once this many have gone through
+    pub fail_after: Option<usize>,
+    read_hook: Option<ReadHook>,
+    write_hook: Option<WriteHook>,
+}
+
+impl MockSpi {
+    pub fn new() -> Self {
+        Self {
+            memory: vec![0; ADDRESS_SPACE],
+            writes: Vec::new(),
+            reads: Vec::new(),
+            transactions: 0,
+            fail_after: None,
+            read_hook: None,
+            write_hook: None,
+        }
+    }
+
+    /// Called with the memory and address before every read, so the hook can
+    /// change what the read returns, e.g. to flip a status bit after a number
+    /// of polls
+    pub fn on_read(mut self, hook: impl FnMut(&mut [u8], u16) + 'static) -> Self {
+        self.read_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Called with the memory, address, and data after every write is stored
+    pub fn on_write(mut self, hook: impl FnMut(&mut [u8], u16, &[u8]) + 'static) -> Self {
+        self.write_hook = Some(Box::new(hook));
+        self
+    }
+
+    pub fn set_u32(&mut self, address: u16, value: u32) {
+        set_u32(&mut self.memory, address, value);
+    }
+
+    /// Data of every write that started at `address`, in order
+    pub fn writes_to(&self, address: u16) -> Vec<&[u8]> {
+        self.writes
+            .iter()
+            .filter(|(start, _)| *start == address)
+            .map(|(_, data)| data.as_slice())
+            .collect()
+    }
+
+    /// The first word of every write that started at `address`, in order
+    pub fn words_written_to(&self, address: u16) -> Vec<u32> {
+        self.writes_to(address)
+            .iter()
+            .map(|data| u32::from_le_bytes(data[..4].try_into().unwrap()))
+            .collect()
+    }
+
+    /// Number of reads that started at `address`
+    pub fn reads_of(&self, address: u16) -> usize {
+        self.reads.iter().filter(|start| **start == address).count()
+    }
+}
+
+pub(crate) fn set_u32(memory: &mut [u8], address: u16, value: u32) {
+    let address = address as usize;
+    memory[address..address + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+pub(crate) fn u32_at(memory: &[u8], address: u16) -> u32 {
+    let address = address as usize;
+    u32::from_le_bytes(memory[address..address + 4].try_into().unwrap())
+}
+
+impl ErrorType for MockSpi {
+    type Error = ErrorKind;
+}
+
+impl MockSpi {
+    /// Carries out one transaction, shared by the blocking and the async
+    /// `SpiDevice` implementations
+    pub fn transfer(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), ErrorKind> {
+        if self
+            .fail_after
+            .is_some_and(|limit| self.transactions >= limit)
+        {
+            return Err(ErrorKind::Other);
+        }
+
+        self.transactions += 1;
+
+        let (header, data) = match operations {
+            [Operation::Write(header), data @ ..] => (*header, data),
+            _ => panic!("transaction doesn't start with an instruction"),
+        };
+
+        let instruction = u16::from_be_bytes([header[0], header[1]]);
+        let op_code = instruction >> 12;
+        let address = instruction & 0xFFF;
+
+        // The CRC instructions end with the CRC, which isn't part of the data
+        let data = match op_code {
+            0b1010 | 0b1011 => {
+                let (_, data) = data.split_last_mut().unwrap();
+                data
+            }
+            _ => data,
+        };
+
+        let mut cursor = address as usize;
+
+        for operation in data.iter_mut() {
+            match operation {
+                Operation::Read(buffer) => {
+                    if let Some(hook) = &mut self.read_hook {
+                        hook(&mut self.memory, cursor as u16);
+                    }
+
+                    self.reads.push(cursor as u16);
+                    buffer.copy_from_slice(&self.memory[cursor..cursor + buffer.len()]);
+                    cursor += buffer.len();
+                }
+                Operation::Write(buffer) => {
+                    self.memory[cursor..cursor + buffer.len()].copy_from_slice(buffer);
+                    self.writes.push((cursor as u16, buffer.to_vec()));
+
+                    if let Some(hook) = &mut self.write_hook {
+                        hook(&mut self.memory, cursor as u16, buffer);
+                    }
+
+                    cursor += buffer.len();
+                }
+                _ => panic!("unsupported SPI operation"),
+            }
+        }
+
+        #[cfg(feature = "spi-crc")]
+        if op_code == 0b1011 {
+            let read_len = cursor - address as usize;
+            let crc = crate::spi::crc16(
+                crate::spi::crc16(0xFFFF, header),
+                &self.memory[address as usize..address as usize + read_len],
+            );
+
+            if let Some(Operation::Read(buffer)) = operations.last_mut() {
+                buffer.copy_from_slice(&crc.to_be_bytes());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "async"))]
+impl embedded_hal::spi::SpiDevice<u8> for MockSpi {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        self.transfer(operations)
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::spi::SpiDevice<u8> for MockSpi {
+    async fn transaction(
+        &mut self,
+        operations: &mut [Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        self.transfer(operations)
+    }
+}
+
+/// A delay that returns immediately, keeping track of how long it would have
+/// waited
+#[derive(Default)]
+pub(crate) struct MockDelay {
+    pub elapsed_ns: u64,
+}
+
+#[cfg(not(feature = "async"))]
+impl embedded_hal::delay::DelayNs for MockDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.elapsed_ns += ns as u64;
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::delay::DelayNs for MockDelay {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.elapsed_ns += ns as u64;
+    }
+}
+
+/// Runs a test body written against the async API. In the blocking build
+/// `maybe_async` has already turned `body` into a plain closure.
+#[cfg(not(feature = "async"))]
+pub(crate) fn block_on<T>(body: impl FnOnce() -> T) -> T {
+    body()
+}
+
+/// Runs a test body written against the async API. Nothing the mock does
+/// ever has to wait, so the future is simply polled until it's done.
+#[cfg(feature = "async")]
+pub(crate) fn block_on<F: core::future::Future>(body: impl FnOnce() -> F) -> F::Output {
+    use core::task::{Context, Poll, Waker};
+
+    let mut future = core::pin::pin!(body());
+    let mut context = Context::from_waker(Waker::noop());
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
@@ -1,10 +1,13 @@
-use arbitrary_int::{u4, u5, u7};
-use embedded_can::Id;
+use arbitrary_int::{u10, u4, u5, u6, u7};
+use embedded_can::{ExtendedId, Id, StandardId};
 
 use crate::memory::controller::{
-    configuration::DataBits,
-    fifo::{FifoNumber, PayloadSize, RetransmissionAttempts},
+    configuration::{DataBits, InterTransmissionDelay, WakeupFilterTime},
+    fifo::{FifoNumber, PayloadSize, RetransmissionAttempts, MAX_FIFO_COUNT, MAX_FIFO_DEPTH},
+    filter::{data_bits_to_eid, FilterObjectRegister, MaskRegister},
 };
+use crate::memory::{RAM_BASE_ADDRESS, RAM_SIZE_BYTES};
+use crate::message::HEADER_SIZE_DWORDS;
 
 #[derive(Debug, Default)]
 pub enum Pll {
@@ -20,15 +23,38 @@ pub enum SysClkDivider {
     DivByTwo,
 }
 
+/// Frequency of the clock on the CLKO pin (`OSC.CLKODIV`), as a division of
+/// the system clock. The pin outputs the start of frame signal instead when
+/// [`IoConfiguration::start_of_frame_on_clko`] is set.
+#[derive(Debug, Default)]
+pub enum ClockOutput {
+    SysClk,
+    DivBy2,
+    DivBy4,
+    /// Reset value of the chip
+    #[default]
+    DivBy10,
+}
+
 #[derive(Debug, Default)]
 pub struct OscillatorConfiguration {
     pub pll: Pll,
     pub divider: SysClkDivider,
+    pub clock_output: ClockOutput,
 }
 
 impl OscillatorConfiguration {
     pub fn new(pll: Pll, divider: SysClkDivider) -> Self {
-        Self { pll, divider }
+        Self {
+            pll,
+            divider,
+            clock_output: ClockOutput::default(),
+        }
+    }
+
+    pub fn with_clock_output(mut self, clock_output: ClockOutput) -> Self {
+        self.clock_output = clock_output;
+        self
     }
 }
 
@@ -66,6 +92,147 @@ impl IoConfiguration {
     }
 }
 
+/// Returned by [`TimestampConfiguration::for_tick_period`] when the requested
+/// tick period can't be produced by the 10-bit prescaler
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TickPeriodError {
+    /// The tick period is shorter than a single SYSCLK cycle
+    TooShort,
+    /// The tick period is longer than 1024 SYSCLK cycles
+    TooLong,
+}
+
+/// When during a received frame the timestamp is taken (C1TSCON.TSEOF)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TimestampPoint {
+    /// At the sample point of the start of frame bit
+    #[default]
+    StartOfFrame,
+    /// Once the frame is considered valid, near the end of frame
+    EndOfFrame,
+}
+
+#[derive(Debug, Default)]
+pub struct TimestampConfiguration {
+    /// The time base counter increments once every `prescaler + 1` SYSCLK
+    /// cycles (C1TSCON.TBCPRE)
+    pub prescaler: u10,
+    pub timestamp_point: TimestampPoint,
+}
+
+impl TimestampConfiguration {
+    pub fn new(prescaler: u10) -> Self {
+        Self {
+            prescaler,
+            timestamp_point: TimestampPoint::default(),
+        }
+    }
+
+    pub fn with_timestamp_point(mut self, timestamp_point: TimestampPoint) -> Self {
+        self.timestamp_point = timestamp_point;
+        self
+    }
+
+    /// Solves for the prescaler that gets closest to `desired_tick_ns` for the
+    /// given SYSCLK frequency. The tick period actually achieved can be checked
+    /// with [`TimestampConfiguration::tick_period_ns`]. A tick period or
+    /// SYSCLK frequency of 0 is rejected as [`TickPeriodError::TooShort`].
+    pub fn for_tick_period(sysclk_hz: u32, desired_tick_ns: u32) -> Result<Self, TickPeriodError> {
+        if sysclk_hz == 0 || desired_tick_ns == 0 {
+            return Err(TickPeriodError::TooShort);
+        }
+
+        let cycles = (desired_tick_ns as u64 * sysclk_hz as u64 + 500_000_000) / 1_000_000_000;
+
+        if cycles == 0 {
+            return Err(TickPeriodError::TooShort);
+        }
+
+        if cycles > 1024 {
+            return Err(TickPeriodError::TooLong);
+        }
+
+        Ok(Self::new(u10::new(cycles as u16 - 1)))
+    }
+
+    /// The period of a single timestamp tick in nanoseconds (rounded to the
+    /// nearest nanosecond) for the given SYSCLK frequency, or `None` if the
+    /// frequency is 0 or the period doesn't fit in a `u32`
+    pub fn tick_period_ns(&self, sysclk_hz: u32) -> Option<u32> {
+        if sysclk_hz == 0 {
+            return None;
+        }
+
+        let cycles = self.prescaler.value() as u64 + 1;
+
+        u32::try_from((cycles * 1_000_000_000 + sysclk_hz as u64 / 2) / sysclk_hz as u64).ok()
+    }
+
+    /// Converts a raw timestamp (a time base counter value) into nanoseconds
+    /// for the given SYSCLK frequency, or `None` if the frequency is 0 or the
+    /// result doesn't fit in a `u64`
+    pub fn timestamp_ns(&self, timestamp: u32, sysclk_hz: u32) -> Option<u64> {
+        if sysclk_hz == 0 {
+            return None;
+        }
+
+        // Wide enough that a full 32-bit timestamp at the largest prescaler
+        // can't overflow
+        let cycles = timestamp as u128 * (self.prescaler.value() as u128 + 1);
+
+        u64::try_from((cycles * 1_000_000_000 + sysclk_hz as u128 / 2) / sysclk_hz as u128).ok()
+    }
+}
+
+/// Returned when building a bit time configuration from raw register values
+/// that don't fit their fields. Each variant holds the rejected value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BitTimeError {
+    TimeSegment1(u8),
+    TimeSegment2(u8),
+    SynchronizationJumpWidth(u8),
+}
+
+/// Number of TQ per bit when the TQ clock is SYSCLK divided by `brp + 1`, or
+/// `None` if the bit rate can't be hit exactly
+fn tq_per_bit(sysclk_hz: u32, brp: u8, bitrate: u32) -> Option<u32> {
+    let divisor = (brp as u32 + 1).checked_mul(bitrate)?;
+
+    if divisor == 0 || sysclk_hz % divisor != 0 {
+        return None;
+    }
+
+    Some(sysclk_hz / divisor)
+}
+
+/// Splits a bit of `tq_per_bit` TQ into raw TSEG1, TSEG2 and SJW values with
+/// the sample point as close as possible to `sample_point_percent`. The SJW is
+/// made as large as the segments allow. Returns `None` if the segments don't
+/// fit within `max_tseg1` and `max_tseg2` (raw values).
+fn split_bit_time(
+    tq_per_bit: u32,
+    sample_point_percent: u8,
+    max_tseg1: u32,
+    max_tseg2: u32,
+) -> Option<(u8, u8, u8)> {
+    let sample_tq = (tq_per_bit * sample_point_percent as u32 + 50) / 100;
+
+    // The sync segment always takes up the first TQ
+    let tseg1 = sample_tq.checked_sub(2)?;
+    let tseg2 = tq_per_bit.checked_sub(sample_tq)?.checked_sub(1)?;
+
+    if tseg1 > max_tseg1 || tseg2 > max_tseg2 {
+        return None;
+    }
+
+    let sjw = tseg1.min(tseg2);
+
+    Some((tseg1 as u8, tseg2 as u8, sjw as u8))
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NominalBitTimeConfiguration {
     pub baud_rate_prescaler: u8,
@@ -75,6 +242,31 @@ pub struct NominalBitTimeConfiguration {
 }
 
 impl NominalBitTimeConfiguration {
+    /// Builds a configuration from raw register values (each one less than
+    /// the actual number of TQ), as output by most CAN bit timing
+    /// calculators.
+    pub fn from_raw(brp: u8, tseg1: u8, tseg2: u8, sjw: u8) -> Result<Self, BitTimeError> {
+        Ok(Self {
+            baud_rate_prescaler: brp,
+            time_segment_1: tseg1,
+            time_segment_2: u7::try_new(tseg2).map_err(|_| BitTimeError::TimeSegment2(tseg2))?,
+            synchronization_jump_width: u7::try_new(sjw)
+                .map_err(|_| BitTimeError::SynchronizationJumpWidth(sjw))?,
+        })
+    }
+
+    fn for_prescaler(
+        sysclk_hz: u32,
+        brp: u8,
+        bitrate: u32,
+        sample_point_percent: u8,
+    ) -> Option<Self> {
+        let tq_per_bit = tq_per_bit(sysclk_hz, brp, bitrate)?;
+        let (tseg1, tseg2, sjw) = split_bit_time(tq_per_bit, sample_point_percent, 255, 127)?;
+
+        Self::from_raw(brp, tseg1, tseg2, sjw).ok()
+    }
+
     /// Max bus length of 550m
     pub const RATE_100_KBIT: Self = Self {
         baud_rate_prescaler: 1,
@@ -114,6 +306,27 @@ impl NominalBitTimeConfiguration {
         time_segment_2: u7::new(7),
         synchronization_jump_width: u7::new(7),
     };
+
+    /// Length of a single nominal bit in SYSCLK periods
+    pub fn bit_time_sysclk(&self) -> u32 {
+        let tq_per_bit =
+            1 + (self.time_segment_1 as u32 + 1) + (self.time_segment_2.value() as u32 + 1);
+
+        (self.baud_rate_prescaler as u32 + 1) * tq_per_bit
+    }
+}
+
+/// Transmitter delay compensation mode (`C1TDC.TDCMOD`) together with the
+/// loop delay used in manual mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransmitterDelayCompensation {
+    /// No secondary sample point is used, which only works at data bit rates
+    /// where the loop delay is a small part of the bit time
+    Disabled,
+    /// The given transceiver loop delay in SYSCLK periods is used as-is
+    Manual(u6),
+    /// The chip measures the loop delay itself
+    Automatic,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -123,16 +336,73 @@ pub struct DataBitTimeConfiguration {
     pub time_segment_2: u4,
     pub synchronization_jump_width: u4,
 
-    pub transmitter_delay_compensation_offset: u7,
+    /// Offset of the secondary sample point in SYSCLK periods, between
+    /// [`DataBitTimeConfiguration::MIN_TDC_OFFSET`] and
+    /// [`DataBitTimeConfiguration::MAX_TDC_OFFSET`] (`C1TDC.TDCO` is a 7-bit
+    /// two's complement field)
+    pub transmitter_delay_compensation_offset: i8,
+    /// How the transceiver loop delay (TDCV) is determined
+    pub transmitter_delay_compensation: TransmitterDelayCompensation,
 }
 
 impl DataBitTimeConfiguration {
+    /// Smallest transmitter delay compensation offset the chip supports
+    pub const MIN_TDC_OFFSET: i8 = -64;
+    /// Largest transmitter delay compensation offset the chip supports
+    pub const MAX_TDC_OFFSET: i8 = 63;
+
+    /// Builds a configuration from raw register values (each one less than
+    /// the actual number of TQ), as output by most CAN bit timing
+    /// calculators.
+    ///
+    /// Automatic transmitter delay compensation is used, with the offset set
+    /// to place the secondary sample point at the data phase sample point
+    /// (`(brp + 1) * (tseg1 + 1)` SYSCLK periods), like the `RATE_*`
+    /// constants. If that is beyond
+    /// [`DataBitTimeConfiguration::MAX_TDC_OFFSET`], which only happens at low
+    /// data bit rates where the loop delay is a small part of the bit time,
+    /// transmitter delay compensation is disabled instead.
+    pub fn from_raw(brp: u8, tseg1: u8, tseg2: u8, sjw: u8) -> Result<Self, BitTimeError> {
+        let tdco = (brp as u16 + 1) * (tseg1 as u16 + 1);
+
+        let (transmitter_delay_compensation_offset, transmitter_delay_compensation) =
+            match i8::try_from(tdco) {
+                Ok(tdco) if tdco <= Self::MAX_TDC_OFFSET => {
+                    (tdco, TransmitterDelayCompensation::Automatic)
+                }
+                _ => (0, TransmitterDelayCompensation::Disabled),
+            };
+
+        Ok(Self {
+            baud_rate_prescaler: brp,
+            time_segment_1: u5::try_new(tseg1).map_err(|_| BitTimeError::TimeSegment1(tseg1))?,
+            time_segment_2: u4::try_new(tseg2).map_err(|_| BitTimeError::TimeSegment2(tseg2))?,
+            synchronization_jump_width: u4::try_new(sjw)
+                .map_err(|_| BitTimeError::SynchronizationJumpWidth(sjw))?,
+            transmitter_delay_compensation_offset,
+            transmitter_delay_compensation,
+        })
+    }
+
+    fn for_prescaler(
+        sysclk_hz: u32,
+        brp: u8,
+        bitrate: u32,
+        sample_point_percent: u8,
+    ) -> Option<Self> {
+        let tq_per_bit = tq_per_bit(sysclk_hz, brp, bitrate)?;
+        let (tseg1, tseg2, sjw) = split_bit_time(tq_per_bit, sample_point_percent, 31, 15)?;
+
+        Self::from_raw(brp, tseg1, tseg2, sjw).ok()
+    }
+
     pub const RATE_500_KBIT: Self = Self {
         baud_rate_prescaler: 1,
         time_segment_1: u5::new(30),
         time_segment_2: u4::new(7),
         synchronization_jump_width: u4::new(7),
-        transmitter_delay_compensation_offset: u7::new(62),
+        transmitter_delay_compensation_offset: 62,
+        transmitter_delay_compensation: TransmitterDelayCompensation::Automatic,
     };
 
     pub const RATE_1_MBIT: Self = Self {
@@ -140,7 +410,8 @@ impl DataBitTimeConfiguration {
         time_segment_1: u5::new(30),
         time_segment_2: u4::new(7),
         synchronization_jump_width: u4::new(7),
-        transmitter_delay_compensation_offset: u7::new(31),
+        transmitter_delay_compensation_offset: 31,
+        transmitter_delay_compensation: TransmitterDelayCompensation::Automatic,
     };
 
     pub const RATE_2_MBIT: Self = Self {
@@ -148,7 +419,8 @@ impl DataBitTimeConfiguration {
         time_segment_1: u5::new(14),
         time_segment_2: u4::new(3),
         synchronization_jump_width: u4::new(3),
-        transmitter_delay_compensation_offset: u7::new(15),
+        transmitter_delay_compensation_offset: 15,
+        transmitter_delay_compensation: TransmitterDelayCompensation::Automatic,
     };
 
     pub const RATE_5_MBIT: Self = Self {
@@ -156,26 +428,151 @@ impl DataBitTimeConfiguration {
         time_segment_1: u5::new(4),
         time_segment_2: u4::new(1),
         synchronization_jump_width: u4::new(1),
-        transmitter_delay_compensation_offset: u7::new(5),
+        transmitter_delay_compensation_offset: 5,
+        transmitter_delay_compensation: TransmitterDelayCompensation::Automatic,
     };
+
+    /// Length of a single data phase bit in SYSCLK periods
+    pub fn bit_time_sysclk(&self) -> u32 {
+        let tq_per_bit =
+            1 + (self.time_segment_1.value() as u32 + 1) + (self.time_segment_2.value() as u32 + 1);
+
+        (self.baud_rate_prescaler as u32 + 1) * tq_per_bit
+    }
+
+    /// Calculates where the secondary sample point (SSP) lands, in SYSCLK
+    /// periods after the start of the transmitted bit.
+    ///
+    /// During the data phase the transmitter checks its own bits at the SSP
+    /// instead of the regular sample point, since the bit it sees on RXCAN is
+    /// delayed by the transceiver loop delay (TDCV). The offset (TDCO) places
+    /// the SSP within the delayed bit, usually at the data phase sample point.
+    /// A negative offset places the SSP before the measured loop delay.
+    pub fn secondary_sample_point(tdcv: u6, tdco: i8) -> i16 {
+        tdcv.value() as i16 + tdco as i16
+    }
+
+    /// Switches to manual transmitter delay compensation with a fixed loop
+    /// delay, placing the secondary sample point at `tdcv + tdco`.
+    ///
+    /// This is mostly useful at high data rates (e.g. 5 Mbit) where the
+    /// automatically measured loop delay isn't accurate enough. Returns `None`
+    /// if `tdco` is outside of [`DataBitTimeConfiguration::MIN_TDC_OFFSET`]
+    /// to [`DataBitTimeConfiguration::MAX_TDC_OFFSET`], or if the SSP doesn't
+    /// land within the data bit time.
+    pub fn with_manual_transmitter_delay_compensation(
+        mut self,
+        tdcv: u6,
+        tdco: i8,
+    ) -> Option<Self> {
+        if !(Self::MIN_TDC_OFFSET..=Self::MAX_TDC_OFFSET).contains(&tdco) {
+            return None;
+        }
+
+        let ssp = Self::secondary_sample_point(tdcv, tdco);
+
+        if ssp < 0 || ssp as u32 >= self.bit_time_sysclk() {
+            return None;
+        }
+
+        self.transmitter_delay_compensation_offset = tdco;
+        self.transmitter_delay_compensation = TransmitterDelayCompensation::Manual(tdcv);
+
+        Some(self)
+    }
+
+    /// Switches transmitter delay compensation off, see
+    /// [`TransmitterDelayCompensation::Disabled`]
+    pub fn without_transmitter_delay_compensation(mut self) -> Self {
+        self.transmitter_delay_compensation = TransmitterDelayCompensation::Disabled;
+        self
+    }
 }
 
 /// For best performance, use nominal and data bit rates with the same baud rate
 /// prescaler. Identical TQ in both phases prevent quantization errors during
 /// bit rate switching.
-///
-/// TODO: Add functions for automatically calculating bit time configurations
-/// based on input parameters (bit rate, SYSCLK, bus length, max baud rate
-/// prescaler, etc.)
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BitTimeConfiguration {
     pub nominal: NominalBitTimeConfiguration,
     pub data: DataBitTimeConfiguration,
+    /// Use the RRS bit of CAN FD base frames as a 12th SID bit
+    /// (`C1TDC.SID11EN`). The driver's IDs don't include it, see the README.
+    pub enable_sid11: bool,
+    /// Filter out edges shorter than the SJW during bus integration
+    /// (`C1TDC.EDGFLTEN`)
+    pub enable_edge_filtering: bool,
 }
 
 impl BitTimeConfiguration {
     pub fn new(nominal: NominalBitTimeConfiguration, data: DataBitTimeConfiguration) -> Self {
-        Self { nominal, data }
+        Self {
+            nominal,
+            data,
+            enable_sid11: false,
+            enable_edge_filtering: false,
+        }
+    }
+
+    pub fn with_sid11(mut self, enable_sid11: bool) -> Self {
+        self.enable_sid11 = enable_sid11;
+        self
+    }
+
+    pub fn with_edge_filtering(mut self, enable_edge_filtering: bool) -> Self {
+        self.enable_edge_filtering = enable_edge_filtering;
+        self
+    }
+
+    /// Calculates a configuration for the given SYSCLK frequency and bit rates
+    /// with both sample points as close as possible to `sample_point_percent`
+    /// (80 is a common choice). The SJW is made as large as possible and
+    /// automatic transmitter delay compensation is used, unless the data
+    /// phase sample point is beyond the largest offset `C1TDC.TDCO` can hold
+    /// ([`DataBitTimeConfiguration::MAX_TDC_OFFSET`]). That only happens at
+    /// low data bit rates, so TDC is disabled instead.
+    ///
+    /// The smallest baud rate prescaler that works for both phases is used,
+    /// which gives the finest TQ resolution. If no prescaler works for both,
+    /// each phase gets its own. Returns `None` if either bit rate can't be
+    /// produced exactly from SYSCLK within the register field widths.
+    ///
+    /// For example, 500 kbit/2 Mbit at 40 MHz with an 80% sample point gives
+    /// [`NominalBitTimeConfiguration::RATE_500_KBIT`] and
+    /// [`DataBitTimeConfiguration::RATE_2_MBIT`].
+    pub fn calculate(
+        sysclk_hz: u32,
+        nominal_bitrate: u32,
+        data_bitrate: u32,
+        sample_point_percent: u8,
+    ) -> Option<Self> {
+        let nominal = |brp| {
+            NominalBitTimeConfiguration::for_prescaler(
+                sysclk_hz,
+                brp,
+                nominal_bitrate,
+                sample_point_percent,
+            )
+        };
+        let data = |brp| {
+            DataBitTimeConfiguration::for_prescaler(
+                sysclk_hz,
+                brp,
+                data_bitrate,
+                sample_point_percent,
+            )
+        };
+
+        let shared = (0..=u8::MAX).find_map(|brp| Some(Self::new(nominal(brp)?, data(brp)?)));
+
+        if shared.is_some() {
+            return shared;
+        }
+
+        Some(Self::new(
+            (0..=u8::MAX).find_map(nominal)?,
+            (0..=u8::MAX).find_map(data)?,
+        ))
     }
 }
 
@@ -190,6 +587,11 @@ pub struct TxEventFifoConfiguration {
 }
 
 impl TxEventFifoConfiguration {
+    /// Number of bytes of RAM the TEF takes up with this configuration
+    pub fn ram_bytes(&self) -> u32 {
+        object_ram_bytes(self.fifo_size, 0, self.enable_timestamps)
+    }
+
     pub fn new(fifo_size: u8) -> Self {
         Self {
             fifo_size,
@@ -229,6 +631,7 @@ impl TxEventFifoConfiguration {
 
 #[derive(Debug)]
 pub struct TxQueueConfiguration {
+    /// Must be at most [`HIGHEST_FIFO_PRIORITY`](crate::memory::controller::fifo::HIGHEST_FIFO_PRIORITY)
     pub message_priority: u8,
     pub retransmission_attempts: RetransmissionAttempts,
     pub fifo_size: u8,
@@ -239,6 +642,11 @@ pub struct TxQueueConfiguration {
 }
 
 impl TxQueueConfiguration {
+    /// Number of bytes of RAM the TXQ takes up with this configuration
+    pub fn ram_bytes(&self) -> u32 {
+        object_ram_bytes(self.fifo_size, self.payload_size.num_bytes(), false)
+    }
+
     pub fn new(message_priority: u8, fifo_size: u8, payload_size: PayloadSize) -> Self {
         Self {
             message_priority,
@@ -286,28 +694,402 @@ pub struct Settings {
     pub bit_time_configuration: BitTimeConfiguration,
     pub tx_event_fifo: Option<TxEventFifoConfiguration>,
     pub tx_queue: Option<TxQueueConfiguration>,
+    /// Run the time base counter, which is needed for message timestamps.
+    /// This doesn't enable any interrupt, see `enable_tbc_overflow_interrupt`.
     pub enable_time_based_counter: bool,
+    /// Resolution of the time base counter and when messages are timestamped
+    pub timestamp: TimestampConfiguration,
+    /// Interrupt when the time base counter wraps around. Independent of
+    /// `enable_time_based_counter`, timestamps work with this disabled.
+    pub enable_tbc_overflow_interrupt: bool,
+    /// Number of data bits (`CiCON.DNCNT`) that acceptance filters compare
+    /// against the EID bits for standard frames. Extended frames are not
+    /// affected. See [`FilterConfiguration::match_standard_with_data`].
     pub data_bits_to_match: Option<DataBits>,
+    /// Interrupt when any RX FIFO interrupt is pending (`C1INT.RXIE`). The
+    /// individual FIFOs still need their own interrupts enabled.
+    pub enable_rx_interrupt: bool,
+    /// Interrupt when any TX FIFO or TXQ interrupt is pending (`C1INT.TXIE`)
+    pub enable_tx_interrupt: bool,
+    /// Interrupt when the operation mode changes (`C1INT.MODIE`)
+    pub enable_mode_change_interrupt: bool,
+    /// Interrupt when any RX FIFO overflows (`C1INT.RXOVIE`)
+    pub enable_rx_overflow_interrupt: bool,
+    /// Interrupt when any TX FIFO runs out of retransmission attempts
+    /// (`C1INT.TXATIE`)
+    pub enable_tx_attempt_interrupt: bool,
     pub enable_can_error_interrupts: bool,
     pub enable_spi_error_interrupt: bool,
     pub enable_ecc_error_interrupt: bool,
+    /// Interrupt when bus activity is detected while the chip is in Sleep mode
+    pub enable_wake_up_interrupt: bool,
+    /// Filters out glitches on RXCAN shorter than the given time so they don't
+    /// wake the chip up. `None` disables the filter.
+    pub wake_up_filter: Option<WakeupFilterTime>,
+    /// Include the stuff bit count in the CRC of CAN FD frames as required by
+    /// ISO 11898-1:2015 (`C1CON.ISOCRCEN`). Only disable this to talk to older
+    /// non-ISO CAN FD nodes. Enabled after a reset.
+    pub use_iso_crc: bool,
+    /// Treat a protocol exception (a recessive reserved bit after FDF) as a
+    /// form error instead of waiting for the bus to go idle
+    /// (`C1CON.PXEDIS`). Set after a reset, so protocol exceptions are
+    /// disabled unless this is cleared.
+    pub disable_protocol_exception: bool,
+    /// Never switch to the data bit rate, no matter the BRS bit of a frame
+    /// (`C1CON.BRSDIS`)
+    pub disable_bit_rate_switching: bool,
+    /// Transmit the ESI bit recessive if the error status of the message
+    /// says so, rather than only according to the node's own error state
+    /// (`C1CON.ESIGM`). Useful for gateways forwarding frames.
+    pub esi_gateway_mode: bool,
+    /// Switch to Listen Only mode instead of Restricted Operation mode on a
+    /// system error (`C1CON.SERR2LOM`)
+    pub listen_only_on_system_error: bool,
+    /// Idle time inserted after every transmitted frame so that lower
+    /// priority nodes get a chance to use the bus (`C1CON.TXBWS`). `None`
+    /// leaves it at its reset value of no delay.
+    pub inter_transmission_delay: Option<InterTransmissionDelay>,
+    /// Read back every register written during configuration and fail with
+    /// `ConfigError::ReadbackMismatch` if it doesn't match. Slows down
+    /// configuration, but catches SPI corruption immediately.
+    pub verify_writes: bool,
+    /// Fail with `ConfigError::UnexpectedDeviceId` unless `DEVID` identifies
+    /// the chip as an MCP2518FD, e.g. to reject an MCP2517FD. `DEVID` is read
+    /// once at the start of configuration either way, see
+    /// `MCP2518FD::verify_device_id`.
+    pub require_mcp2518fd: bool,
+    /// Check the SPI connection during configuration with a single burst
+    /// write and read of RAM (`MCP2518FD::verify_spi_communications_long`)
+    /// instead of 32 separate write/read pairs. This saves 62 SPI
+    /// transactions, which keeps CS toggling (and the time other devices
+    /// wait for the bus) down on a shared bus.
+    pub batched_spi_check: bool,
 }
 
-#[derive(Debug)]
+impl Settings {
+    /// Starts from the given bit timing with everything else off: PLL off,
+    /// default IO configuration, no TEF, no TXQ, time base counter stopped, no
+    /// data byte filtering, all interrupts disabled, and no verification.
+    /// The ISO CRC stays enabled like after a reset. Everything can be changed
+    /// with the `with_*` methods.
+    pub fn new(bit_time_configuration: BitTimeConfiguration) -> Self {
+        Self {
+            oscillator: OscillatorConfiguration::default(),
+            io_configuration: IoConfiguration::default(),
+            bit_time_configuration,
+            tx_event_fifo: None,
+            tx_queue: None,
+            enable_time_based_counter: false,
+            timestamp: TimestampConfiguration::default(),
+            enable_tbc_overflow_interrupt: false,
+            data_bits_to_match: None,
+            enable_rx_interrupt: false,
+            enable_tx_interrupt: false,
+            enable_mode_change_interrupt: false,
+            enable_rx_overflow_interrupt: false,
+            enable_tx_attempt_interrupt: false,
+            enable_can_error_interrupts: false,
+            enable_spi_error_interrupt: false,
+            enable_ecc_error_interrupt: false,
+            enable_wake_up_interrupt: false,
+            wake_up_filter: None,
+            use_iso_crc: true,
+            disable_protocol_exception: true,
+            disable_bit_rate_switching: false,
+            esi_gateway_mode: false,
+            listen_only_on_system_error: false,
+            inter_transmission_delay: None,
+            verify_writes: false,
+            require_mcp2518fd: false,
+            batched_spi_check: false,
+        }
+    }
+
+    pub fn with_oscillator(mut self, oscillator: OscillatorConfiguration) -> Self {
+        self.oscillator = oscillator;
+        self
+    }
+
+    pub fn with_io_configuration(mut self, io_configuration: IoConfiguration) -> Self {
+        self.io_configuration = io_configuration;
+        self
+    }
+
+    pub fn with_bit_time_configuration(
+        mut self,
+        bit_time_configuration: BitTimeConfiguration,
+    ) -> Self {
+        self.bit_time_configuration = bit_time_configuration;
+        self
+    }
+
+    pub fn with_tx_event_fifo(mut self, tx_event_fifo: TxEventFifoConfiguration) -> Self {
+        self.tx_event_fifo = Some(tx_event_fifo);
+        self
+    }
+
+    pub fn with_tx_queue(mut self, tx_queue: TxQueueConfiguration) -> Self {
+        self.tx_queue = Some(tx_queue);
+        self
+    }
+
+    pub fn with_time_based_counter(mut self, enable_time_based_counter: bool) -> Self {
+        self.enable_time_based_counter = enable_time_based_counter;
+        self
+    }
+
+    pub fn with_timestamp(mut self, timestamp: TimestampConfiguration) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    pub fn with_tbc_overflow_interrupt(mut self, enable_tbc_overflow_interrupt: bool) -> Self {
+        self.enable_tbc_overflow_interrupt = enable_tbc_overflow_interrupt;
+        self
+    }
+
+    pub fn with_data_bits_to_match(mut self, data_bits_to_match: DataBits) -> Self {
+        self.data_bits_to_match = Some(data_bits_to_match);
+        self
+    }
+
+    pub fn with_rx_interrupt(mut self, enable_rx_interrupt: bool) -> Self {
+        self.enable_rx_interrupt = enable_rx_interrupt;
+        self
+    }
+
+    pub fn with_tx_interrupt(mut self, enable_tx_interrupt: bool) -> Self {
+        self.enable_tx_interrupt = enable_tx_interrupt;
+        self
+    }
+
+    pub fn with_mode_change_interrupt(mut self, enable_mode_change_interrupt: bool) -> Self {
+        self.enable_mode_change_interrupt = enable_mode_change_interrupt;
+        self
+    }
+
+    pub fn with_rx_overflow_interrupt(mut self, enable_rx_overflow_interrupt: bool) -> Self {
+        self.enable_rx_overflow_interrupt = enable_rx_overflow_interrupt;
+        self
+    }
+
+    pub fn with_tx_attempt_interrupt(mut self, enable_tx_attempt_interrupt: bool) -> Self {
+        self.enable_tx_attempt_interrupt = enable_tx_attempt_interrupt;
+        self
+    }
+
+    pub fn with_can_error_interrupts(mut self, enable_can_error_interrupts: bool) -> Self {
+        self.enable_can_error_interrupts = enable_can_error_interrupts;
+        self
+    }
+
+    pub fn with_spi_error_interrupt(mut self, enable_spi_error_interrupt: bool) -> Self {
+        self.enable_spi_error_interrupt = enable_spi_error_interrupt;
+        self
+    }
+
+    pub fn with_ecc_error_interrupt(mut self, enable_ecc_error_interrupt: bool) -> Self {
+        self.enable_ecc_error_interrupt = enable_ecc_error_interrupt;
+        self
+    }
+
+    pub fn with_wake_up_interrupt(mut self, enable_wake_up_interrupt: bool) -> Self {
+        self.enable_wake_up_interrupt = enable_wake_up_interrupt;
+        self
+    }
+
+    pub fn with_wake_up_filter(mut self, wake_up_filter: WakeupFilterTime) -> Self {
+        self.wake_up_filter = Some(wake_up_filter);
+        self
+    }
+
+    pub fn with_iso_crc(mut self, use_iso_crc: bool) -> Self {
+        self.use_iso_crc = use_iso_crc;
+        self
+    }
+
+    pub fn with_protocol_exception_disabled(mut self, disable_protocol_exception: bool) -> Self {
+        self.disable_protocol_exception = disable_protocol_exception;
+        self
+    }
+
+    pub fn with_bit_rate_switching_disabled(mut self, disable_bit_rate_switching: bool) -> Self {
+        self.disable_bit_rate_switching = disable_bit_rate_switching;
+        self
+    }
+
+    pub fn with_esi_gateway_mode(mut self, esi_gateway_mode: bool) -> Self {
+        self.esi_gateway_mode = esi_gateway_mode;
+        self
+    }
+
+    pub fn with_listen_only_on_system_error(mut self, listen_only_on_system_error: bool) -> Self {
+        self.listen_only_on_system_error = listen_only_on_system_error;
+        self
+    }
+
+    pub fn with_inter_transmission_delay(
+        mut self,
+        inter_transmission_delay: InterTransmissionDelay,
+    ) -> Self {
+        self.inter_transmission_delay = Some(inter_transmission_delay);
+        self
+    }
+
+    pub fn with_verify_writes(mut self, verify_writes: bool) -> Self {
+        self.verify_writes = verify_writes;
+        self
+    }
+
+    pub fn with_require_mcp2518fd(mut self, require_mcp2518fd: bool) -> Self {
+        self.require_mcp2518fd = require_mcp2518fd;
+        self
+    }
+
+    pub fn with_batched_spi_check(mut self, batched_spi_check: bool) -> Self {
+        self.batched_spi_check = batched_spi_check;
+        self
+    }
+}
+
+/// Where each TEF, TXQ, and FIFO ends up in RAM for a given configuration,
+/// computed without touching the chip. The chip allocates RAM sequentially in
+/// the order TEF, TXQ, FIFO 1 to FIFO 31, with every object starting right
+/// where the previous one ended. FIFOs that are never configured keep their
+/// reset configuration, a single object with an 8 byte payload (16 bytes).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RamLayout {
+    /// Start address of the TEF, if enabled
+    pub tx_event_fifo: Option<u16>,
+    /// Start address of the TXQ, if enabled
+    pub tx_queue: Option<u16>,
+    /// Start address of every FIFO, indexed by FIFO number minus 1. See
+    /// [`RamLayout::fifo_address`]. A FIFO that would start beyond the 16 bit
+    /// address space is reported at `u16::MAX`, which only happens if the
+    /// layout doesn't fit anyway.
+    pub fifos: [u16; MAX_FIFO_COUNT],
+    /// Number of bytes allocated to the TEF, TXQ, and all 31 FIFOs, like
+    /// [`crate::spi::MCP2518FD::used_ram_bytes`]
+    pub used_bytes: u32,
+}
+
+impl RamLayout {
+    /// Computes the layout for the TEF and TXQ in `settings` plus the given
+    /// FIFO configurations. If a FIFO is listed more than once, the last
+    /// configuration wins.
+    pub fn new(settings: &Settings, fifos: &[(FifoNumber, FifoConfiguration)]) -> Self {
+        let mut sizes =
+            [object_ram_bytes(1, PayloadSize::Bytes8.num_bytes(), false); MAX_FIFO_COUNT];
+
+        for (fifo_number, config) in fifos {
+            sizes[*fifo_number as usize - 1] = config.ram_bytes();
+        }
+
+        let address = |offset: u32| u16::try_from(RAM_BASE_ADDRESS + offset).unwrap_or(u16::MAX);
+
+        let mut used_bytes = 0;
+
+        let tx_event_fifo = settings.tx_event_fifo.as_ref().map(|config| {
+            let start = address(used_bytes);
+            used_bytes += config.ram_bytes();
+            start
+        });
+        let tx_queue = settings.tx_queue.as_ref().map(|config| {
+            let start = address(used_bytes);
+            used_bytes += config.ram_bytes();
+            start
+        });
+
+        let mut addresses = [0u16; MAX_FIFO_COUNT];
+
+        for (start, size) in addresses.iter_mut().zip(sizes) {
+            *start = address(used_bytes);
+            used_bytes += size;
+        }
+
+        Self {
+            tx_event_fifo,
+            tx_queue,
+            fifos: addresses,
+            used_bytes,
+        }
+    }
+
+    /// Start address of the given FIFO
+    pub fn fifo_address(&self, fifo_number: FifoNumber) -> u16 {
+        self.fifos[fifo_number as usize - 1]
+    }
+
+    /// Whether everything fits in the chip's 2 KB of RAM
+    pub fn fits(&self) -> bool {
+        self.used_bytes <= RAM_SIZE_BYTES
+    }
+}
+
+/// Bytes of RAM taken up by a FIFO with the given number of objects, each
+/// holding a header, an optional timestamp, and `payload_bytes` of data. The
+/// size is clamped to 1..=32 objects like the FIFO size registers do.
+fn object_ram_bytes(fifo_size: u8, payload_bytes: usize, timestamped: bool) -> u32 {
+    let timestamp_bytes = if timestamped { 4 } else { 0 };
+    let object_bytes = HEADER_SIZE_DWORDS * 4 + timestamp_bytes + payload_bytes;
+
+    fifo_size.clamp(1, MAX_FIFO_DEPTH) as u32 * object_bytes as u32
+}
+
+/// Fill level watermark of a receive FIFO. The hardware has a single watermark
+/// at half of the FIFO size (the `TFHRFHIE` bit).
+///
+/// For double-buffered reception, use an even FIFO size and drain half of the
+/// FIFO every time the interrupt fires, while the other half keeps filling up.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RxWatermark {
+    #[default]
+    Disabled,
+    /// Interrupt while at least half of the FIFO's message slots are occupied,
+    /// e.g. 4 or more messages in a FIFO of size 8
+    HalfFull,
+}
+
+impl RxWatermark {
+    pub fn is_enabled(&self) -> bool {
+        *self != Self::Disabled
+    }
+}
+
+/// Fill level watermark of a transmit FIFO. The hardware has a single watermark
+/// at half of the FIFO size (the `TFHRFHIE` bit).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TxWatermark {
+    #[default]
+    Disabled,
+    /// Interrupt while at most half of the FIFO's message slots are occupied,
+    /// e.g. 4 or fewer pending messages in a FIFO of size 8
+    HalfEmpty,
+}
+
+impl TxWatermark {
+    pub fn is_enabled(&self) -> bool {
+        *self != Self::Disabled
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
 pub enum FifoMode {
     Transmit(TxFifoConfiguration),
     Receive(RxFifoConfiguration),
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct TxFifoConfiguration {
-    /// See sfr::controller::fifo::HIGHEST_FIFO_PRIORITY
+    /// Must be at most [`HIGHEST_FIFO_PRIORITY`](crate::memory::controller::fifo::HIGHEST_FIFO_PRIORITY)
     pub priority: u8,
     pub retransmission_attempts: RetransmissionAttempts,
     pub enable_auto_rtr: bool,
     pub enable_transmit_attempts_exhausted_interrupt: bool,
     pub enable_fifo_empty_interrupt: bool,
-    pub enable_fifo_half_empty_interrupt: bool,
+    pub watermark: TxWatermark,
     pub enable_fifo_not_full_interrupt: bool,
 }
 
@@ -319,7 +1101,7 @@ impl TxFifoConfiguration {
             enable_auto_rtr: false,
             enable_transmit_attempts_exhausted_interrupt: false,
             enable_fifo_empty_interrupt: false,
-            enable_fifo_half_empty_interrupt: false,
+            watermark: TxWatermark::Disabled,
             enable_fifo_not_full_interrupt: false,
         }
     }
@@ -351,11 +1133,8 @@ impl TxFifoConfiguration {
         self
     }
 
-    pub fn with_fifo_half_empty_interrupt(
-        mut self,
-        enable_fifo_half_empty_interrupt: bool,
-    ) -> Self {
-        self.enable_fifo_half_empty_interrupt = enable_fifo_half_empty_interrupt;
+    pub fn with_watermark(mut self, watermark: TxWatermark) -> Self {
+        self.watermark = watermark;
         self
     }
 
@@ -365,12 +1144,12 @@ impl TxFifoConfiguration {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq, Eq)]
 pub struct RxFifoConfiguration {
     pub enable_message_timestamps: bool,
     pub enable_fifo_overflow_interrupt: bool,
     pub enable_fifo_full_interrupt: bool,
-    pub enable_fifo_half_full_interrupt: bool,
+    pub watermark: RxWatermark,
     pub enable_fifo_not_empty_interrupt: bool,
 }
 
@@ -394,18 +1173,60 @@ impl RxFifoConfiguration {
         self
     }
 
-    pub fn with_fifo_half_full_interrupt(mut self, enable_fifo_half_full_interrupt: bool) -> Self {
-        self.enable_fifo_half_full_interrupt = enable_fifo_half_full_interrupt;
+    pub fn with_watermark(mut self, watermark: RxWatermark) -> Self {
+        self.watermark = watermark;
         self
     }
 
+    /// Note that the not empty interrupt is level-triggered, not
+    /// edge-triggered. It stays asserted for as long as there is at least one
+    /// message left in the FIFO, so an interrupt handler that doesn't fully
+    /// drain the FIFO will be re-entered immediately. See
+    /// [`crate::spi::MCP2518FD::rx_fifo_latch_interrupt`] for a way to get
+    /// edge-like behavior.
     pub fn with_fifo_not_empty_interrupt(mut self, enable_fifo_not_empty_interrupt: bool) -> Self {
         self.enable_fifo_not_empty_interrupt = enable_fifo_not_empty_interrupt;
         self
     }
 }
 
-#[derive(Debug)]
+/// The interrupt enables of an RX FIFO, see
+/// [`crate::spi::MCP2518FD::set_fifo_interrupts_bulk`]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RxFifoInterrupts {
+    pub enable_fifo_overflow_interrupt: bool,
+    pub enable_fifo_full_interrupt: bool,
+    pub watermark: RxWatermark,
+    pub enable_fifo_not_empty_interrupt: bool,
+}
+
+impl RxFifoInterrupts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_fifo_overflow_interrupt(mut self, enable_fifo_overflow_interrupt: bool) -> Self {
+        self.enable_fifo_overflow_interrupt = enable_fifo_overflow_interrupt;
+        self
+    }
+
+    pub fn with_fifo_full_interrupt(mut self, enable_fifo_full_interrupt: bool) -> Self {
+        self.enable_fifo_full_interrupt = enable_fifo_full_interrupt;
+        self
+    }
+
+    pub fn with_watermark(mut self, watermark: RxWatermark) -> Self {
+        self.watermark = watermark;
+        self
+    }
+
+    pub fn with_fifo_not_empty_interrupt(mut self, enable_fifo_not_empty_interrupt: bool) -> Self {
+        self.enable_fifo_not_empty_interrupt = enable_fifo_not_empty_interrupt;
+        self
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
 pub struct FifoConfiguration {
     /// Max number of messages that can be stored in this FIFO (0 to 32)
     pub fifo_size: u8,
@@ -416,6 +1237,17 @@ pub struct FifoConfiguration {
 }
 
 impl FifoConfiguration {
+    /// Number of bytes of RAM the FIFO takes up with this configuration. Only
+    /// RX FIFOs can store timestamps.
+    pub fn ram_bytes(&self) -> u32 {
+        let timestamped = match &self.mode {
+            FifoMode::Transmit(_) => false,
+            FifoMode::Receive(config) => config.enable_message_timestamps,
+        };
+
+        object_ram_bytes(self.fifo_size, self.payload_size.num_bytes(), timestamped)
+    }
+
     pub fn new(fifo_size: u8, payload_size: PayloadSize, mode: FifoMode) -> Self {
         Self {
             fifo_size,
@@ -425,6 +1257,7 @@ impl FifoConfiguration {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct FilterConfiguration {
     pub buffer_pointer: FifoNumber,
     pub mode: FilterMatchMode,
@@ -432,8 +1265,480 @@ pub struct FilterConfiguration {
     pub mask_bits: Id,
 }
 
+impl FilterConfiguration {
+    /// Accepts only the given ID, routing it to `buffer_pointer`. Standard
+    /// IDs only match standard frames and extended IDs only match extended
+    /// frames.
+    pub fn match_exact(buffer_pointer: FifoNumber, id: impl Into<Id>) -> Self {
+        let id = id.into();
+
+        let mask = match id {
+            Id::Standard(_) => Id::Standard(StandardId::MAX),
+            Id::Extended(_) => Id::Extended(ExtendedId::MAX),
+        };
+
+        Self::match_masked(buffer_pointer, id, mask).unwrap()
+    }
+
+    /// Accepts every ID whose bits match `id` wherever `mask` has a 1 bit,
+    /// routing it to `buffer_pointer`. The mask is given as a plain 11 or 29
+    /// bit ID, so e.g. `ExtendedId::new(0x1FFFFF00)` ignores the J1939 source
+    /// address. Returns `None` if `id` and `mask` are not the same kind of ID.
+    pub fn match_masked(
+        buffer_pointer: FifoNumber,
+        id: impl Into<Id>,
+        mask: impl Into<Id>,
+    ) -> Option<Self> {
+        let (filter_bits, mask_bits) = (id.into(), mask.into());
+
+        let mode = match (filter_bits, mask_bits) {
+            (Id::Standard(_), Id::Standard(_)) => FilterMatchMode::StandardOnly,
+            (Id::Extended(_), Id::Extended(_)) => FilterMatchMode::ExtendedOnly,
+            _ => return None,
+        };
+
+        Some(Self {
+            buffer_pointer,
+            mode,
+            filter_bits,
+            mask_bits,
+        })
+    }
+
+    /// Accepts standard frames with the given ID whose data starts with the
+    /// first `data_bits` bits of `data_prefix`, routing them to
+    /// `buffer_pointer`. [`Settings::data_bits_to_match`] must be set to the
+    /// same `data_bits` for the chip to compare the data at all.
+    ///
+    /// This only works for standard frames: the hardware compares the data
+    /// bits against the filter's EID field, with the MSB of the first data
+    /// byte in EID bit 0 (see [`data_bits_to_eid`]). Extended frames are
+    /// rejected by this filter.
+    ///
+    /// `data_prefix` must hold exactly the bytes covering `data_bits`, e.g. 1
+    /// byte for up to 8 bits and 3 bytes for 17 or 18 bits, otherwise `None`
+    /// is returned. Bits of the last byte past `data_bits` aren't compared.
+    pub fn match_standard_with_data(
+        buffer_pointer: FifoNumber,
+        sid: StandardId,
+        data_prefix: &[u8],
+        data_bits: DataBits,
+    ) -> Option<Self> {
+        let data_bits = u8::from(data_bits) as usize;
+
+        if data_prefix.len() != data_bits.div_ceil(8) {
+            return None;
+        }
+
+        let eid = data_bits_to_eid(data_prefix, data_bits);
+        let meid = (1 << data_bits) - 1;
+
+        // The EID bits only make it into the registers through extended IDs,
+        // the mode still restricts the filter to standard frames
+        Some(Self {
+            buffer_pointer,
+            mode: FilterMatchMode::StandardOnly,
+            filter_bits: Id::Extended(ExtendedId::new(((sid.as_raw() as u32) << 18) | eid)?),
+            mask_bits: Id::Extended(ExtendedId::new((0x7FF << 18) | meid)?),
+        })
+    }
+
+    /// Reconstructs a configuration from the filter object and mask register
+    /// values, reversing [`FilterConfiguration::object_register`] and
+    /// [`FilterConfiguration::mask_register`].
+    ///
+    /// The registers don't record whether the IDs were given as standard or
+    /// extended IDs, so `filter_bits` and `mask_bits` are returned as
+    /// extended IDs if the mode is `ExtendedOnly` or any EID bit is set, and
+    /// as standard IDs otherwise.
+    pub fn from_registers(
+        buffer_pointer: FifoNumber,
+        object: &FilterObjectRegister,
+        mask: &MaskRegister,
+    ) -> Self {
+        let mode = match (mask.mide(), object.exide()) {
+            (false, _) => FilterMatchMode::Both,
+            (true, false) => FilterMatchMode::StandardOnly,
+            (true, true) => FilterMatchMode::ExtendedOnly,
+        };
+
+        let extended = matches!(mode, FilterMatchMode::ExtendedOnly);
+
+        Self {
+            buffer_pointer,
+            mode,
+            filter_bits: join_id(object.sid(), object.eid(), extended),
+            mask_bits: join_id(mask.msid(), mask.meid(), extended),
+        }
+    }
+
+    /// Builds the filter object register value that represents this
+    /// configuration. If `filter_bits` is a standard ID, the EID bits are 0.
+    pub fn object_register(&self) -> FilterObjectRegister {
+        let mut object_register = FilterObjectRegister(0);
+
+        let (sid, eid) = split_id(self.filter_bits);
+        object_register.set_sid(sid);
+        object_register.set_eid(eid);
+
+        object_register.set_exide(match self.mode {
+            FilterMatchMode::StandardOnly | FilterMatchMode::Both => false,
+            FilterMatchMode::ExtendedOnly => true,
+        });
+
+        object_register
+    }
+
+    /// Builds the mask register value that represents this configuration. If
+    /// `mask_bits` is a standard ID, the EID bits are 0.
+    pub fn mask_register(&self) -> MaskRegister {
+        let mut mask_register = MaskRegister(0);
+
+        let (sid, eid) = split_id(self.mask_bits);
+        mask_register.set_msid(sid);
+        mask_register.set_meid(eid);
+
+        mask_register.set_mide(match self.mode {
+            FilterMatchMode::Both => false,
+            FilterMatchMode::StandardOnly | FilterMatchMode::ExtendedOnly => true,
+        });
+
+        mask_register
+    }
+
+    /// Determines whether a message with the given ID (and data, for standard
+    /// frames with data byte filtering) would be accepted by this filter. This
+    /// does not touch the chip at all, which makes it useful for diagnosing
+    /// filter misconfigurations.
+    ///
+    /// `data_bits` is the configured [`Settings::data_bits_to_match`], see
+    /// [`FilterObjectRegister::matches`] for how `data` is compared.
+    pub fn matches(&self, id: Id, data: &[u8], data_bits: Option<DataBits>) -> bool {
+        self.object_register()
+            .matches(&self.mask_register(), id, data, data_bits)
+    }
+}
+
+/// Splits an ID into the SID and EID fields used by the filter, mask, and
+/// message object registers
+fn split_id(id: Id) -> (u16, u32) {
+    match id {
+        Id::Standard(id) => (id.as_raw(), 0),
+        Id::Extended(id) => (id.standard_id().as_raw(), id.as_raw() & 0x3FFFF),
+    }
+}
+
+/// Reverse of [`split_id`]. Returns an extended ID if `extended` is set or
+/// any EID bit is set.
+fn join_id(sid: u16, eid: u32, extended: bool) -> Id {
+    if extended || eid != 0 {
+        Id::Extended(ExtendedId::new(((sid as u32) << 18) | eid).unwrap())
+    } else {
+        Id::Standard(StandardId::new(sid).unwrap())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FilterMatchMode {
     StandardOnly,
     ExtendedOnly,
     Both,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `Settings::new` leaves every interrupt disabled, including RXIE and
+    /// TXIE, so the examples have to opt back into the ones they used to
+    /// spell out
+    #[test]
+    fn new_matches_the_minimal_example_configuration() {
+        extern crate std;
+
+        let bit_time_configuration = || {
+            BitTimeConfiguration::new(
+                NominalBitTimeConfiguration::RATE_500_KBIT,
+                DataBitTimeConfiguration::RATE_2_MBIT,
+            )
+        };
+        let tx_queue = || {
+            TxQueueConfiguration::new(
+                crate::memory::controller::fifo::HIGHEST_FIFO_PRIORITY,
+                8,
+                PayloadSize::Bytes32,
+            )
+        };
+
+        let defaults = Settings::new(bit_time_configuration());
+        assert!(!defaults.enable_rx_interrupt);
+        assert!(!defaults.enable_tx_interrupt);
+
+        let minimal = Settings {
+            oscillator: OscillatorConfiguration::default(),
+            io_configuration: IoConfiguration::new(),
+            bit_time_configuration: bit_time_configuration(),
+            tx_event_fifo: None,
+            tx_queue: Some(tx_queue()),
+            enable_time_based_counter: true,
+            timestamp: TimestampConfiguration::default(),
+            enable_tbc_overflow_interrupt: false,
+            data_bits_to_match: None,
+            enable_rx_interrupt: true,
+            enable_tx_interrupt: true,
+            enable_mode_change_interrupt: false,
+            enable_rx_overflow_interrupt: false,
+            enable_tx_attempt_interrupt: false,
+            enable_can_error_interrupts: false,
+            enable_spi_error_interrupt: false,
+            enable_ecc_error_interrupt: false,
+            enable_wake_up_interrupt: false,
+            wake_up_filter: None,
+            use_iso_crc: true,
+            disable_protocol_exception: true,
+            disable_bit_rate_switching: false,
+            esi_gateway_mode: false,
+            listen_only_on_system_error: false,
+            inter_transmission_delay: None,
+            verify_writes: false,
+            require_mcp2518fd: false,
+            batched_spi_check: false,
+        };
+
+        let built = defaults
+            .with_tx_queue(tx_queue())
+            .with_time_based_counter(true)
+            .with_rx_interrupt(true)
+            .with_tx_interrupt(true);
+
+        assert_eq!(std::format!("{built:?}"), std::format!("{minimal:?}"));
+    }
+
+    fn sample_point_percent(tseg1: u32, tseg2: u32) -> u32 {
+        100 * (1 + tseg1 + 1) / (1 + tseg1 + 1 + tseg2 + 1)
+    }
+
+    #[test]
+    fn calculate_500k_2m_at_40mhz() {
+        let config = BitTimeConfiguration::calculate(40_000_000, 500_000, 2_000_000, 80).unwrap();
+
+        assert_eq!(config.nominal, NominalBitTimeConfiguration::RATE_500_KBIT);
+        assert_eq!(config.data, DataBitTimeConfiguration::RATE_2_MBIT);
+    }
+
+    #[test]
+    fn calculate_500k_2m_at_20mhz() {
+        let config = BitTimeConfiguration::calculate(20_000_000, 500_000, 2_000_000, 80).unwrap();
+
+        assert_eq!(config.nominal.baud_rate_prescaler, 0);
+        assert_eq!(config.data.baud_rate_prescaler, 0);
+
+        assert_eq!(config.nominal.bit_time_sysclk(), 40);
+        assert_eq!(config.data.bit_time_sysclk(), 10);
+
+        assert_eq!(
+            sample_point_percent(
+                config.nominal.time_segment_1 as u32,
+                config.nominal.time_segment_2.value() as u32
+            ),
+            80
+        );
+        assert_eq!(
+            sample_point_percent(
+                config.data.time_segment_1.value() as u32,
+                config.data.time_segment_2.value() as u32
+            ),
+            80
+        );
+        assert_eq!(config.data.transmitter_delay_compensation_offset, 7);
+    }
+
+    #[test]
+    fn calculate_keeps_tdco_in_range() {
+        for (sysclk_hz, nominal, data) in [
+            (40_000_000, 125_000, 500_000),
+            (40_000_000, 250_000, 500_000),
+            (40_000_000, 500_000, 1_000_000),
+            (20_000_000, 125_000, 500_000),
+            (20_000_000, 250_000, 1_000_000),
+        ] {
+            let config = BitTimeConfiguration::calculate(sysclk_hz, nominal, data, 80).unwrap();
+            let data = &config.data;
+
+            if data.transmitter_delay_compensation == TransmitterDelayCompensation::Disabled {
+                assert_eq!(data.transmitter_delay_compensation_offset, 0);
+                continue;
+            }
+
+            let tdco =
+                (data.baud_rate_prescaler as i32 + 1) * (data.time_segment_1.value() as i32 + 1);
+
+            assert!(tdco <= DataBitTimeConfiguration::MAX_TDC_OFFSET as i32);
+            assert_eq!(data.transmitter_delay_compensation_offset as i32, tdco);
+        }
+    }
+
+    #[test]
+    fn calculate_disables_tdc_at_low_data_rates() {
+        // Only a prescaler of 3 or more fits 250 kbit into the TSEG fields,
+        // which puts the sample point 124 SYSCLK periods into the bit
+        let config = BitTimeConfiguration::calculate(40_000_000, 125_000, 250_000, 80).unwrap();
+
+        assert_eq!(
+            config.data.transmitter_delay_compensation,
+            TransmitterDelayCompensation::Disabled
+        );
+        assert_eq!(config.data.bit_time_sysclk(), 160);
+    }
+
+    #[test]
+    fn timestamp_conversion_rejects_zero_sysclk() {
+        let config = TimestampConfiguration::new(u10::new(39));
+
+        assert_eq!(config.tick_period_ns(40_000_000), Some(1_000));
+        assert_eq!(config.timestamp_ns(1_500, 40_000_000), Some(1_500_000));
+
+        assert_eq!(config.tick_period_ns(0), None);
+        assert_eq!(config.timestamp_ns(1_500, 0), None);
+    }
+
+    #[test]
+    fn from_raw_disables_tdc_above_63() {
+        // (3 + 1) * (30 + 1) = 124 SYSCLK periods would read back as -4
+        let config = DataBitTimeConfiguration::from_raw(3, 30, 7, 7).unwrap();
+
+        assert_eq!(
+            config.transmitter_delay_compensation,
+            TransmitterDelayCompensation::Disabled
+        );
+        assert_eq!(config.transmitter_delay_compensation_offset, 0);
+
+        // A legal timing whose sample point is 64 SYSCLK periods into the bit
+        let config = DataBitTimeConfiguration::from_raw(1, 31, 7, 7).unwrap();
+
+        assert_eq!(
+            config.transmitter_delay_compensation,
+            TransmitterDelayCompensation::Disabled
+        );
+
+        let config = DataBitTimeConfiguration::from_raw(1, 30, 7, 7).unwrap();
+
+        assert_eq!(
+            config.transmitter_delay_compensation,
+            TransmitterDelayCompensation::Automatic
+        );
+        assert_eq!(config.transmitter_delay_compensation_offset, 62);
+    }
+
+    #[test]
+    fn match_exact_masks_every_id_bit() {
+        let id = ExtendedId::new(0x1234_5678).unwrap();
+        let filter = FilterConfiguration::match_exact(FifoNumber::Fifo1, id);
+
+        // MIDE, and every MEID and MSID bit
+        assert_eq!(u32::from(filter.mask_register()), 0x5FFF_FFFF);
+        // EXIDE, EID = 0x05678 and SID = 0x48D
+        assert_eq!(u32::from(filter.object_register()), 0x42B3_C48D);
+
+        let id = StandardId::new(0x123).unwrap();
+        let filter = FilterConfiguration::match_exact(FifoNumber::Fifo1, id);
+
+        assert_eq!(u32::from(filter.mask_register()), 0x4000_07FF);
+        assert_eq!(u32::from(filter.object_register()), 0x123);
+    }
+
+    #[test]
+    fn match_standard_with_data_filter() {
+        let sid = StandardId::new(0x123).unwrap();
+        let filter = FilterConfiguration::match_standard_with_data(
+            FifoNumber::Fifo1,
+            sid,
+            &[0xC0, 0xFF],
+            DataBits::Bits10,
+        )
+        .unwrap();
+
+        // DB0[7] and DB0[6] in EID0 and EID1, DB1[7] and DB1[6] in EID8 and EID9
+        assert_eq!(filter.object_register().eid(), 0b11_0000_0011);
+        assert_eq!(filter.mask_register().meid(), 0x3FF);
+        assert!(filter.mask_register().mide());
+        assert!(!filter.object_register().exide());
+
+        let bits = Some(DataBits::Bits10);
+
+        assert!(filter.matches(Id::Standard(sid), &[0xC0, 0xC0], bits));
+        assert!(filter.matches(Id::Standard(sid), &[0xC0, 0xDF, 0x00], bits));
+        assert!(!filter.matches(Id::Standard(sid), &[0x80, 0xC0], bits));
+        assert!(!filter.matches(Id::Standard(sid), &[0xC0, 0x80], bits));
+        assert!(!filter.matches(
+            Id::Extended(ExtendedId::new(0x123 << 18).unwrap()),
+            &[],
+            bits
+        ));
+    }
+
+    #[test]
+    fn match_standard_with_data_checks_prefix_length() {
+        let sid = StandardId::new(0x123).unwrap();
+        let filter = |prefix: &[u8], bits| {
+            FilterConfiguration::match_standard_with_data(FifoNumber::Fifo1, sid, prefix, bits)
+        };
+
+        assert!(filter(&[0x80], DataBits::Bits1).is_some());
+        assert!(filter(&[0xFF, 0xFF, 0xC0], DataBits::Bits18).is_some());
+        assert!(filter(&[0xFF, 0xFF], DataBits::Bits18).is_none());
+        assert!(filter(&[0xFF, 0xFF], DataBits::Bits8).is_none());
+        assert!(filter(&[], DataBits::Bits1).is_none());
+    }
+
+    fn layout_settings() -> Settings {
+        Settings::new(BitTimeConfiguration::new(
+            NominalBitTimeConfiguration::RATE_500_KBIT,
+            DataBitTimeConfiguration::RATE_2_MBIT,
+        ))
+    }
+
+    #[test]
+    fn ram_layout_counts_every_fifo() {
+        let settings = layout_settings()
+            .with_tx_event_fifo(TxEventFifoConfiguration::new(4))
+            .with_tx_queue(TxQueueConfiguration::new(0, 2, PayloadSize::Bytes64));
+        let rx = FifoConfiguration::new(
+            8,
+            PayloadSize::Bytes8,
+            FifoMode::Receive(RxFifoConfiguration::new()),
+        );
+
+        let layout = RamLayout::new(&settings, &[(FifoNumber::Fifo2, rx)]);
+
+        // TEF: 4 * 8, TXQ: 2 * 72, FIFO 2: 8 * 16, and 30 unconfigured FIFOs
+        // of 16 bytes each
+        assert_eq!(layout.tx_event_fifo, Some(0x400));
+        assert_eq!(layout.tx_queue, Some(0x420));
+        assert_eq!(layout.fifo_address(FifoNumber::Fifo1), 0x4B0);
+        assert_eq!(layout.fifo_address(FifoNumber::Fifo2), 0x4C0);
+        assert_eq!(layout.fifo_address(FifoNumber::Fifo3), 0x540);
+        assert_eq!(layout.used_bytes, 32 + 144 + 128 + 30 * 16);
+        assert!(layout.fits());
+    }
+
+    #[test]
+    fn ram_layout_overrun() {
+        let big = || {
+            FifoConfiguration::new(
+                32,
+                PayloadSize::Bytes64,
+                FifoMode::Receive(RxFifoConfiguration::new()),
+            )
+        };
+        let fifos: [(FifoNumber, FifoConfiguration); MAX_FIFO_COUNT] =
+            core::array::from_fn(|i| (FifoNumber::try_from(i as u8 + 1).unwrap(), big()));
+
+        let layout = RamLayout::new(&layout_settings(), &fifos);
+
+        assert_eq!(layout.used_bytes, 31 * 32 * 72);
+        assert!(!layout.fits());
+        // FIFO 31 would start at 0x400 + 30 * 2304, past the address space
+        assert_eq!(layout.fifo_address(FifoNumber::Fifo31), u16::MAX);
+    }
+}
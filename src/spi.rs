@@ -1,7 +1,8 @@
 use core::fmt::Debug;
 
+use arbitrary_int::{u4, u5, u6, u7};
 use bitfield::bitfield;
-use embedded_can::{Id, StandardId};
+use embedded_can::Id;
 
 #[cfg(not(feature = "async"))]
 use embedded_hal::delay::DelayNs;
@@ -13,38 +14,49 @@ use embedded_hal::spi::SpiDevice;
 use embedded_hal_async::delay::DelayNs;
 #[cfg(feature = "async")]
 use embedded_hal_async::spi::SpiDevice;
-#[cfg(feature = "async")]
-use futures::future::OptionFuture;
 
-use crate::memory::chip::{IoControlRegister, OscillatorControlRegister};
+use crate::memory::chip::{
+    CrcRegister, DeviceIdRegister, GpioPin, IoControlRegister, OscillatorControlRegister, PinMode,
+};
 use crate::memory::controller::configuration::{
     CanControlRegister, DataBitTimeConfigurationRegister, NominalBitTimeConfigurationRegister,
-    OperationMode, TimeStampControlRegister, TransmitterDelayCompensationMode,
-    TransmitterDelayCompensationRegister,
+    OperationMode, TimeBasedCounterRegister, TimeStampControlRegister,
+    TransmitterDelayCompensationMode, TransmitterDelayCompensationRegister,
+};
+use crate::memory::controller::diagnostic::{
+    BusDiagnosticRegister0, BusDiagnosticRegister1, TransmitReceiveErrorCountRegister,
 };
 use crate::memory::controller::fifo::{
-    FifoControlRegister, FifoNumber, FifoStatusRegister, TxEventFifoControlRegister,
-    TxEventFifoStatusRegister, TxQueueControlRegister, TxQueueStatusRegister, UserAddressKind,
-    UserAddressRegister,
+    FifoControlRegister, FifoNumber, FifoSet, FifoStatusRegister, PayloadSize,
+    RetransmissionAttempts, TxError, TxEventFifoControlRegister, TxEventFifoStatusRegister,
+    TxQueueControlRegister, TxQueueStatusRegister, UserAddressKind, UserAddressRegister,
+    HIGHEST_FIFO_PRIORITY, MAX_FIFO_COUNT,
 };
 use crate::memory::controller::filter::{
-    FilterControlRegister, FilterNumber, FilterObjectRegister, MaskRegister,
+    FilterControlIndex, FilterControlNumber, FilterControlRegister, FilterNumber,
+    FilterObjectRegister, MaskRegister, MAX_FILTER_COUNT,
 };
 use crate::memory::controller::interrupt::{
-    InterruptCodeRegister, InterruptRegister, RxInterruptStatusRegister,
-    RxOverflowInterruptStatusRegister, TxAttemptInterruptStatusRegister, TxInterruptStatusRegister,
+    CanEvent, InterruptCodeRegister, InterruptKind, InterruptRegister, InterruptStatuses,
+    RxInterruptStatusRegister, RxOverflowInterruptStatusRegister, SystemInterrupts,
+    TxAttemptInterruptStatusRegister, TxInterruptStatusRegister,
+};
+use crate::memory::controller::status::TransmitRequestRegister;
+use crate::memory::{
+    is_valid_ram_address, Register, RegisterSnapshot, RepeatedRegister, SFRAddress, RAM_SIZE_BYTES,
 };
-use crate::memory::{is_valid_ram_address, Register, RepeatedRegister, SFRAddress};
-use crate::message::rx::{RxHeader, RxMessage};
-use crate::message::tx::{TxEventObject, TxHeader, TxMessage};
-use crate::message::{len_for_dlc, MAX_FD_BUFFER_SIZE};
+use crate::message::rx::{RxHeader, RxMessage, RxMessageHeader};
+use crate::message::tx::{TxEventObject, TxMessage};
+use crate::message::{len_for_dlc, HEADER_SIZE_DWORDS, MAX_FD_BUFFER_SIZE};
 use crate::settings::{
-    self, BitTimeConfiguration, FilterConfiguration, FilterMatchMode, RxFifoConfiguration,
-    TxFifoConfiguration,
+    self, BitTimeConfiguration, DataBitTimeConfiguration, FilterConfiguration,
+    NominalBitTimeConfiguration, RxFifoConfiguration, RxFifoInterrupts, RxWatermark,
+    TxFifoConfiguration, TxWatermark,
 };
 use crate::settings::{
-    FifoConfiguration, IoConfiguration, OscillatorConfiguration, Pll, SysClkDivider,
-    TxEventFifoConfiguration, TxQueueConfiguration,
+    ClockOutput, FifoConfiguration, IoConfiguration, OscillatorConfiguration, Pll, SysClkDivider,
+    TimestampConfiguration, TimestampPoint, TransmitterDelayCompensation, TxEventFifoConfiguration,
+    TxQueueConfiguration,
 };
 
 #[derive(Debug)]
@@ -70,6 +82,28 @@ pub enum Error {
     FifoFull,
     /// Tried to read a message from a FIFO not configured for reception
     FifoNotRx,
+    /// The buffer passed in is too small for the payload of the next message,
+    /// which is the given number of bytes long
+    BufferTooSmall(usize),
+    /// Tried to transmit a CAN FD frame while the controller is in CAN 2.0
+    /// mode, which would only produce error frames on the bus
+    FdFrameInClassicMode,
+    /// The controller didn't finish aborting the pending transmissions in time
+    AbortTimeout,
+    /// A frame wasn't transmitted within the given timeout
+    TransmitTimeout,
+    /// RAM read back a different value than was written, at the given address
+    RamVerifyMismatch(u16),
+    /// The CRC appended by the chip didn't match the data read starting at the
+    /// given address. Only returned with the `spi-crc` feature.
+    CrcMismatch(u16),
+    /// A register read back a different value than was written while write
+    /// verification was enabled
+    ReadbackMismatch {
+        register: SFRAddress,
+        wrote: u32,
+        read: u32,
+    },
     Other,
 }
 
@@ -80,6 +114,46 @@ pub enum ConfigError {
     ConfigurationModeTimeout,
     SPIFailedRAMEcho,
     PLLNotReady,
+    /// The oscillator didn't become ready after waking the chip up from Sleep
+    OscillatorNotReady,
+    /// The device ID register read back an impossible value. The SPI clock may
+    /// be too fast for the chip's current system clock (it must be below
+    /// roughly half of it), or the chip's oscillator isn't running yet.
+    SpiCommunicationFailed,
+    /// The device ID register doesn't identify the chip as an MCP2518FD (only
+    /// checked when `Settings::require_mcp2518fd` is set)
+    UnexpectedDeviceId {
+        id: u8,
+        rev: u8,
+    },
+    /// The controller did not accept the requested operation mode
+    ModeRequestRejected,
+    /// The controller is taking part in bus activity or still has frames
+    /// pending, so switching to Configuration mode could abort a frame
+    ControllerBusy,
+    /// The controller accepted the request to enter Normal mode, but never saw
+    /// the 11 consecutive recessive bits it needs to integrate onto the bus
+    BusIntegrationTimeout,
+    /// The controller did not leave bus off within the given timeout
+    BusOffRecoveryTimeout,
+    /// The requested TEF, TXQ, or FIFO needs more RAM than is left next to
+    /// everything else that is allocated (all sizes in bytes)
+    RamExhausted {
+        requested: u32,
+        available: u32,
+    },
+    /// A TXQ or TX FIFO priority is above [`HIGHEST_FIFO_PRIORITY`]
+    InvalidPriority(u8),
+    /// The chip is in Configuration mode, where FIFOs are held in reset and a
+    /// message loaded into them would be lost
+    InConfigurationMode,
+    /// A register read back a different value than was written (only checked
+    /// when `Settings::verify_writes` is set)
+    ReadbackMismatch {
+        register: SFRAddress,
+        wrote: u32,
+        read: u32,
+    },
     Other(Error),
 }
 
@@ -87,13 +161,157 @@ impl From<Error> for ConfigError {
     fn from(error: Error) -> Self {
         match error {
             Error::SPIRead | Error::SPIWrite => ConfigError::ConfigurationModeTimeout,
+            Error::ReadbackMismatch {
+                register,
+                wrote,
+                read,
+            } => ConfigError::ReadbackMismatch {
+                register,
+                wrote,
+                read,
+            },
             _ => ConfigError::Other(error),
         }
     }
 }
 
+impl embedded_can::Error for Error {
+    fn kind(&self) -> embedded_can::ErrorKind {
+        embedded_can::ErrorKind::Other
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::SPIRead => write!(f, "failed to read from the chip over SPI"),
+            Error::SPIWrite => write!(f, "failed to write to the chip over SPI"),
+            Error::InvalidRamAddress(address) => write!(f, "invalid RAM address {address:#05x}"),
+            Error::InvalidReadLength(len) => {
+                write!(f, "RAM read length {len} is not a multiple of 4")
+            }
+            Error::InvalidWriteLength(len) => {
+                write!(f, "RAM write length {len} is not a multiple of 4")
+            }
+            Error::TxQueueDisabled => write!(f, "the TXQ is not enabled"),
+            Error::FifoNotTx => write!(f, "the FIFO is not configured for transmission"),
+            Error::FifoTooSmall => write!(f, "the message does not fit in the FIFO"),
+            Error::FifoFull => write!(f, "the FIFO is full"),
+            Error::FifoNotRx => write!(f, "the FIFO is not configured for reception"),
+            Error::BufferTooSmall(len) => {
+                write!(f, "the buffer is too small for a {len} byte payload")
+            }
+            Error::FdFrameInClassicMode => {
+                write!(f, "tried to transmit a CAN FD frame in CAN 2.0 mode")
+            }
+            Error::AbortTimeout => write!(f, "timed out aborting pending transmissions"),
+            Error::TransmitTimeout => write!(f, "timed out waiting for the frame to be sent"),
+            Error::RamVerifyMismatch(address) => {
+                write!(f, "RAM read back a different value at {address:#05x}")
+            }
+            Error::CrcMismatch(address) => {
+                write!(f, "SPI read CRC mismatch at {address:#05x}")
+            }
+            Error::ReadbackMismatch {
+                register,
+                wrote,
+                read,
+            } => write!(
+                f,
+                "{register:?} read back {read:#010x} after writing {wrote:#010x}"
+            ),
+            Error::Other => write!(f, "unknown error"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl core::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ConfigError::ChangeOpModeTimeout => {
+                write!(f, "timed out changing the operation mode")
+            }
+            ConfigError::ConfigurationModeTimeout => {
+                write!(f, "timed out entering Configuration mode")
+            }
+            ConfigError::SPIFailedRAMEcho => write!(f, "RAM did not echo the written test pattern"),
+            ConfigError::PLLNotReady => write!(f, "the PLL did not lock"),
+            ConfigError::OscillatorNotReady => write!(f, "the oscillator did not become ready"),
+            ConfigError::SpiCommunicationFailed => {
+                write!(f, "the device ID register read back an impossible value")
+            }
+            ConfigError::UnexpectedDeviceId { id, rev } => {
+                write!(f, "unexpected device ID {id:#x} (revision {rev})")
+            }
+            ConfigError::ModeRequestRejected => {
+                write!(f, "the controller rejected the requested operation mode")
+            }
+            ConfigError::ControllerBusy => write!(f, "the controller is busy"),
+            ConfigError::BusIntegrationTimeout => write!(f, "timed out integrating onto the bus"),
+            ConfigError::BusOffRecoveryTimeout => write!(f, "timed out recovering from bus off"),
+            ConfigError::RamExhausted {
+                requested,
+                available,
+            } => write!(
+                f,
+                "requested {requested} bytes of RAM but only {available} are available"
+            ),
+            ConfigError::InvalidPriority(priority) => write!(
+                f,
+                "priority {priority} is above the highest priority {HIGHEST_FIFO_PRIORITY}"
+            ),
+            ConfigError::InConfigurationMode => {
+                write!(f, "the chip is in Configuration mode")
+            }
+            ConfigError::ReadbackMismatch {
+                register,
+                wrote,
+                read,
+            } => write!(
+                f,
+                "{register:?} read back {read:#010x} after writing {wrote:#010x}"
+            ),
+            ConfigError::Other(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl core::error::Error for ConfigError {}
+
+/// Counters kept by the driver itself for health monitoring, see
+/// [`MCP2518FD::stats`]. The chip only counts errors, so these only cover what
+/// went through this driver instance. All counters wrap around on overflow.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DriverStats {
+    /// Frames pushed into the TXQ or a TX FIFO. This counts frames handed to
+    /// the controller, which may still fail to send them.
+    pub frames_transmitted: u32,
+    /// Frames read out of an RX FIFO
+    pub frames_received: u32,
+    /// RX FIFO overflows cleared with [`MCP2518FD::clear_rx_overflow`]
+    pub rx_overflows: u32,
+    /// Exhausted transmit attempts cleared with
+    /// [`MCP2518FD::clear_tx_attempts_exhausted`]
+    pub tx_attempt_failures: u32,
+}
+
 pub struct MCP2518FD<SPI> {
     spi: SPI,
+    /// Whether register writes are read back and compared, see
+    /// `Settings::verify_writes`
+    verify_writes: bool,
+    /// Number of invalid message interrupts serviced, see
+    /// [`MCP2518FD::service_invalid_message_interrupt`]
+    invalid_message_count: u32,
+    /// Frame counters, see [`MCP2518FD::stats`]
+    stats: DriverStats,
+    /// TX FIFO used by the `embedded_can` implementation, `None` for the TXQ
+    can_tx_fifo: Option<FifoNumber>,
+    /// RX FIFO used by the `embedded_can` implementation
+    can_rx_fifo: FifoNumber,
 }
 
 #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
@@ -103,8 +321,35 @@ where
     SPIE: Debug,
 {
     /// Constructs a new MCP2518FD controller from an SPI bus and CS GPIO pin
+    ///
+    /// Every register or RAM access is a single [`SpiDevice::transaction`],
+    /// since the chip expects CS to be deasserted after each instruction. The
+    /// driver keeps no state on the chip between transactions, so it works
+    /// just as well on a shared bus, e.g. with `embedded-hal-bus`'s
+    /// `RefCellDevice` or `CriticalSectionDevice`, where other devices may
+    /// use the bus in between. Adjacent registers are accessed in one burst
+    /// wherever the driver can, to keep the number of transactions down.
+    /// [`Settings::batched_spi_check`](settings::Settings::batched_spi_check)
+    /// cuts down the transactions needed for configuration further.
     pub fn new(spi: SPI) -> MCP2518FD<SPI> {
-        Self { spi }
+        Self {
+            spi,
+            verify_writes: false,
+            invalid_message_count: 0,
+            stats: DriverStats::default(),
+            can_tx_fifo: None,
+            can_rx_fifo: FifoNumber::Fifo1,
+        }
+    }
+
+    /// Selects the FIFOs used by the [`embedded_can::nb::Can`] implementation.
+    /// `tx` is the FIFO frames are transmitted through (`None` for the TXQ,
+    /// which is the default), and `rx` is the FIFO frames are received from
+    /// (FIFO 1 by default).
+    pub fn with_can_fifos(mut self, tx: Option<FifoNumber>, rx: FifoNumber) -> Self {
+        self.can_tx_fifo = tx;
+        self.can_rx_fifo = rx;
+        self
     }
 
     /// Releases ownership of the SPI resources
@@ -133,17 +378,86 @@ where
     /// You may want to reset the chip before calling this method. See
     /// [`MCP2518FD::reset`] for more information.
     ///
-    /// The data_bits_to_match field must be within 1..=18 if it is `Some`. A value of Some(0) will be interpreted the same as None, and
+    /// After a reset the chip runs directly from the oscillator (the PLL is
+    /// off), and the SPI clock must not exceed roughly half of the system
+    /// clock. The recommended startup sequence is therefore to start with a
+    /// slow SPI clock (e.g. 1 MHz), call [`MCP2518FD::reset`] and then this
+    /// method, and only raise the SPI clock once configuration succeeded and
+    /// the PLL (if enabled) is locked. The device ID register is read first,
+    /// and if it doesn't look valid [`ConfigError::SpiCommunicationFailed`] is
+    /// returned before anything is written.
+    ///
+    /// If the chip isn't already in Configuration or Sleep mode, it must be
+    /// idle: not busy on the bus and without pending transmit requests.
+    /// Otherwise [`ConfigError::ControllerBusy`] is returned rather than
+    /// aborting a frame mid-transmission. Abort or drain pending frames first.
+    ///
+    /// If `data_bits_to_match` is `Some`, standard frames are additionally
+    /// matched on their first data bits, see
+    /// [`FilterConfiguration::match_standard_with_data`]. The
+    /// [`DataBits`](crate::memory::controller::configuration::DataBits) type
+    /// limits this to 1 to 18 bits. `None` leaves `CiCON.DNCNT` at its reset
+    /// value of 0, which disables data matching.
     pub async fn configure(
         &mut self,
         settings: settings::Settings,
         delay: &mut impl DelayNs,
     ) -> Result<(), ConfigError> {
-        self.set_op_mode(OperationMode::Configuration, delay)
-            .await
-            .map_err(|_| ConfigError::ConfigurationModeTimeout)?;
+        self.verify_writes = settings.verify_writes;
+
+        let result = self.apply_settings(settings, delay).await;
+
+        self.verify_writes = false;
+
+        result
+    }
+
+    async fn apply_settings(
+        &mut self,
+        settings: settings::Settings,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), ConfigError> {
+        let devid = self.verify_device_id().await?;
+
+        if settings.require_mcp2518fd && devid.id() != DeviceIdRegister::MCP2518FD_ID {
+            return Err(ConfigError::UnexpectedDeviceId {
+                id: devid.id(),
+                rev: devid.rev(),
+            });
+        }
+
+        /* Make sure we don't abort a frame on a live bus */
+
+        let c1con = self.read_register::<CanControlRegister>().await?;
 
-        self.verify_spi_communications().await?;
+        if !matches!(
+            c1con.opmode(),
+            OperationMode::Configuration | OperationMode::Sleep
+        ) && (c1con.busy() || !self.all_tx_fifos_empty().await?)
+        {
+            return Err(ConfigError::ControllerBusy);
+        }
+
+        // The current frame is finished first, which takes a while at low bit
+        // rates, so allow much longer than set_op_mode does
+        const CONFIGURATION_MODE_TIMEOUT_US: u32 = 50_000;
+
+        self.set_op_mode_with_timeout(
+            OperationMode::Configuration,
+            delay,
+            CONFIGURATION_MODE_TIMEOUT_US,
+        )
+        .await
+        .map_err(|error| match error {
+            ConfigError::ChangeOpModeTimeout => ConfigError::ConfigurationModeTimeout,
+            error => error,
+        })?;
+
+        if settings.batched_spi_check {
+            self.verify_spi_communications_long().await?;
+        } else {
+            self.verify_spi_communications().await?;
+        }
 
         self.configure_osc(settings.oscillator, delay).await?;
         self.configure_io(settings.io_configuration).await?;
@@ -151,6 +465,7 @@ where
             .await?;
         self.configure_tx_event_fifo(settings.tx_event_fifo).await?;
         self.configure_tx_queue(settings.tx_queue).await?;
+        self.configure_timestamp(settings.timestamp).await?;
 
         if settings.enable_time_based_counter {
             self.modify_register(|mut tscon: TimeStampControlRegister| {
@@ -160,37 +475,52 @@ where
             .await?;
         }
 
-        if let Some(dncnt) = settings.data_bits_to_match {
-            self.modify_register(|mut cicon: CanControlRegister| {
+        // The remaining C1CON settings are applied in a single read-modify-write
+        self.modify_register(|mut cicon: CanControlRegister| {
+            if let Some(dncnt) = settings.data_bits_to_match {
                 cicon.set_dncnt(dncnt);
-                cicon
-            })
-            .await?;
-        }
+            }
 
-        self.modify_register(|mut cicon: CanControlRegister| {
+            if let Some(wft) = settings.wake_up_filter {
+                cicon.set_wakfil(true);
+                cicon.set_wft(wft);
+            } else {
+                cicon.set_wakfil(false);
+            }
+
+            if let Some(delay) = settings.inter_transmission_delay {
+                cicon.set_txbws(delay);
+            }
+
+            cicon.set_isocrcen(settings.use_iso_crc);
+            cicon.set_pxedis(settings.disable_protocol_exception);
+            cicon.set_brsdis(settings.disable_bit_rate_switching);
+            cicon.set_esigm(settings.esi_gateway_mode);
+            cicon.set_serr2lom(settings.listen_only_on_system_error);
+
+            // Restrict retransmissions so that the RetransmissionAttempts of
+            // the TXQ and each TX FIFO are honored
             cicon.set_rtxat(true);
+
             cicon
         })
         .await?;
 
         self.modify_register(|mut ciint: InterruptRegister| {
-            ciint.set_rxie(true);
-            ciint.set_txie(true);
-
-            if settings.enable_can_error_interrupts {
-                ciint.set_ivmie(true);
-                ciint.set_cerrie(true);
-                ciint.set_serrie(true);
-            }
-
-            if settings.enable_spi_error_interrupt {
-                ciint.set_spicrcie(true);
-            }
-
-            if settings.enable_ecc_error_interrupt {
-                ciint.set_eccie(true);
-            }
+            ciint.set_rxie(settings.enable_rx_interrupt);
+            ciint.set_txie(settings.enable_tx_interrupt);
+            ciint.set_modie(settings.enable_mode_change_interrupt);
+            ciint.set_rxovie(settings.enable_rx_overflow_interrupt);
+            ciint.set_txatie(settings.enable_tx_attempt_interrupt);
+            ciint.set_tbcie(settings.enable_tbc_overflow_interrupt);
+            ciint.set_wakie(settings.enable_wake_up_interrupt);
+            ciint.set_ivmie(settings.enable_can_error_interrupts);
+            ciint.set_cerrie(settings.enable_can_error_interrupts);
+            ciint.set_serrie(settings.enable_can_error_interrupts);
+            ciint.set_spicrcie(settings.enable_spi_error_interrupt);
+            ciint.set_eccie(settings.enable_ecc_error_interrupt);
+            // Not exposed through the settings, keep it at its reset value
+            ciint.set_tefie(false);
 
             ciint
         })
@@ -204,35 +534,302 @@ where
         Ok(c1con.opmode())
     }
 
-    /// Changes the operating mode of the chip. Will time out after 5 attempts.
+    /// Changes the operating mode of the chip. Will time out after about 2ms,
+    /// see [`MCP2518FD::set_op_mode_with_timeout`] to wait longer.
     pub async fn set_op_mode(
         &mut self,
         op_mode: OperationMode,
         delay: &mut impl DelayNs,
     ) -> Result<(), ConfigError> {
+        self.set_op_mode_with_timeout(op_mode, delay, 2000).await
+    }
+
+    /// Changes the operating mode of the chip, waiting up to `timeout_us`
+    /// microseconds for the controller to switch before returning
+    /// [`ConfigError::ChangeOpModeTimeout`].
+    ///
+    /// The controller only changes modes between frames, so on a slow or busy
+    /// bus entering Configuration mode can take up to a full frame time, and
+    /// leaving it for a Normal mode waits for 11 consecutive recessive bits.
+    /// At 125 kbit/s a classic extended frame with stuff bits already takes
+    /// about 1ms.
+    pub async fn set_op_mode_with_timeout(
+        &mut self,
+        op_mode: OperationMode,
+        delay: &mut impl DelayNs,
+        timeout_us: u32,
+    ) -> Result<(), ConfigError> {
+        const POLL_INTERVAL_US: u32 = 100;
+
         self.modify_register(|mut c1con: CanControlRegister| {
             c1con.set_opmode(op_mode);
             c1con
         })
         .await?;
 
-        /* Delay 2ms checking every 500us for op mode change */
-
-        const MAX_ATTEMPTS: usize = 5;
+        let mut elapsed_us = 0;
 
-        for i in 0..MAX_ATTEMPTS {
+        loop {
             let c1con = self.read_register::<CanControlRegister>().await?;
 
             if c1con.opmode() == op_mode {
+                return Ok(());
+            }
+
+            if elapsed_us >= timeout_us {
+                return Err(ConfigError::ChangeOpModeTimeout);
+            }
+
+            delay.delay_us(POLL_INTERVAL_US).await;
+            elapsed_us += POLL_INTERVAL_US;
+        }
+    }
+
+    /// Like [`MCP2518FD::set_op_mode`], but first checks `C1CON.BUSY` and
+    /// returns [`ConfigError::ControllerBusy`] without requesting the mode
+    /// change if the controller is in the middle of a frame.
+    pub async fn try_set_op_mode_when_idle(
+        &mut self,
+        op_mode: OperationMode,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), ConfigError> {
+        if self.read_register::<CanControlRegister>().await?.busy() {
+            return Err(ConfigError::ControllerBusy);
+        }
+
+        self.set_op_mode(op_mode, delay).await
+    }
+
+    /// Switches to Listen Only mode, in which the node receives every frame
+    /// but never transmits anything on the bus, not even ACKs or error frames.
+    /// Useful for passive bus sniffing.
+    pub async fn enter_listen_only(&mut self, delay: &mut impl DelayNs) -> Result<(), ConfigError> {
+        self.set_op_mode(OperationMode::ListenOnly, delay).await
+    }
+
+    /// Switches to Restricted Operation mode, in which the node receives
+    /// frames and sends ACKs but doesn't transmit any frames or error frames
+    pub async fn enter_restricted(&mut self, delay: &mut impl DelayNs) -> Result<(), ConfigError> {
+        self.set_op_mode(OperationMode::Restricted, delay).await
+    }
+
+    /// Switches to Normal CAN FD mode. On a quiet bus, prefer
+    /// [`MCP2518FD::enter_normal_when_ready`], which waits longer for bus
+    /// integration.
+    pub async fn enter_normal_fd(&mut self, delay: &mut impl DelayNs) -> Result<(), ConfigError> {
+        self.set_op_mode(OperationMode::NormalCanFD, delay).await
+    }
+
+    /// Switches to Normal CAN 2.0 mode, in which CAN FD frames can't be sent
+    /// and received FD frames cause error frames
+    pub async fn enter_normal_can2(&mut self, delay: &mut impl DelayNs) -> Result<(), ConfigError> {
+        self.set_op_mode(OperationMode::NormalCan2, delay).await
+    }
+
+    /// Temporarily switches to Configuration mode, runs `f`, and then restores
+    /// the operation mode the chip was in before, even if `f` fails.
+    ///
+    /// Filter, FIFO, and bit timing changes can only be done in Configuration
+    /// mode. The chip finishes transmitting or receiving the current frame
+    /// before switching, but messages still queued for transmission stay queued
+    /// and are sent once the previous mode is restored. If that's not what you
+    /// want, abort or wait for them before calling this.
+    ///
+    /// An error from `f` takes precedence over an error restoring the mode.
+    pub async fn with_configuration_mode<T, E>(
+        &mut self,
+        delay: &mut impl DelayNs,
+        #[cfg(not(feature = "async"))] f: impl FnOnce(&mut Self) -> Result<T, E>,
+        #[cfg(feature = "async")] f: impl AsyncFnOnce(&mut Self) -> Result<T, E>,
+    ) -> Result<T, E>
+    where
+        E: From<ConfigError>,
+    {
+        let previous_mode = self.get_op_mode().await.map_err(ConfigError::from)?;
+
+        if previous_mode == OperationMode::Configuration {
+            return f(self).await;
+        }
+
+        self.set_op_mode(OperationMode::Configuration, delay)
+            .await?;
+
+        let result = f(self).await;
+        let restored = self.set_op_mode(previous_mode, delay).await;
+
+        let value = result?;
+        restored?;
+
+        Ok(value)
+    }
+
+    /// Requests Normal CAN FD mode and waits up to `timeout_us` microseconds
+    /// for the controller to integrate onto the bus.
+    ///
+    /// Before leaving Configuration mode the controller must see 11
+    /// consecutive recessive bits. On a busy bus this is quick, but on a quiet
+    /// or disconnected bus it may never happen. Unlike
+    /// [`MCP2518FD::set_op_mode`], this distinguishes a rejected mode request
+    /// ([`ConfigError::ModeRequestRejected`]) from a controller that is still
+    /// waiting for the bus ([`ConfigError::BusIntegrationTimeout`]).
+    pub async fn enter_normal_when_ready(
+        &mut self,
+        delay: &mut impl DelayNs,
+        timeout_us: u32,
+    ) -> Result<(), ConfigError> {
+        const POLL_INTERVAL_US: u32 = 100;
+
+        self.modify_register(|mut c1con: CanControlRegister| {
+            c1con.set_opmode(OperationMode::NormalCanFD);
+            c1con
+        })
+        .await?;
+
+        let mut elapsed_us = 0;
+
+        loop {
+            let c1con = self.read_register::<CanControlRegister>().await?;
+
+            if c1con.opmode() == OperationMode::NormalCanFD {
+                return Ok(());
+            }
+
+            // Still waiting for bus integration for as long as the request
+            // stands, whichever mode the controller is leaving
+            if c1con.requested_opmode() != OperationMode::NormalCanFD {
+                return Err(ConfigError::ModeRequestRejected);
+            }
+
+            if elapsed_us >= timeout_us {
+                return Err(ConfigError::BusIntegrationTimeout);
+            }
+
+            delay.delay_us(POLL_INTERVAL_US).await;
+            elapsed_us += POLL_INTERVAL_US;
+        }
+    }
+
+    /// Recovers the controller from bus off. Returns `Ok(false)` straight away
+    /// if it isn't bus off.
+    ///
+    /// Otherwise the controller is switched to Configuration mode, which resets
+    /// the error counters, and then back to the mode it was in. This waits up
+    /// to `timeout_us` microseconds for the controller to integrate onto the
+    /// bus again with `TXBO` cleared, and returns
+    /// [`ConfigError::BusOffRecoveryTimeout`] if it doesn't.
+    ///
+    /// Frames that were queued stay queued and are retransmitted once the
+    /// controller is back. With restricted retransmission (`C1CON.RTXAT`, set
+    /// by [`MCP2518FD::configure`]) the attempts spent before going bus off
+    /// still count towards each FIFO's [`RetransmissionAttempts`]. With
+    /// unlimited retransmissions, a frame that keeps failing because of a
+    /// wiring fault will take the node bus off again.
+    pub async fn recover_from_bus_off(
+        &mut self,
+        delay: &mut impl DelayNs,
+        timeout_us: u32,
+    ) -> Result<bool, ConfigError> {
+        const POLL_INTERVAL_US: u32 = 100;
+
+        if !self.is_bus_off().await? {
+            return Ok(false);
+        }
+
+        let previous_mode = self.get_op_mode().await?;
+
+        self.set_op_mode(OperationMode::Configuration, delay)
+            .await?;
+
+        // Bus integration may take longer than set_op_mode waits for, so only
+        // request the mode here and poll below
+        self.modify_register(|mut c1con: CanControlRegister| {
+            c1con.set_opmode(previous_mode);
+            c1con
+        })
+        .await?;
+
+        let mut elapsed_us = 0;
+
+        loop {
+            let c1con = self.read_register::<CanControlRegister>().await?;
+
+            if c1con.opmode() == previous_mode && !self.is_bus_off().await? {
+                return Ok(true);
+            }
+
+            if elapsed_us >= timeout_us {
+                return Err(ConfigError::BusOffRecoveryTimeout);
+            }
+
+            delay.delay_us(POLL_INTERVAL_US).await;
+            elapsed_us += POLL_INTERVAL_US;
+        }
+    }
+
+    /// Puts the chip into Sleep mode, stopping its oscillator. If
+    /// `wake_on_can` is set, the wake up interrupt (`C1INT.WAKIE`) is enabled
+    /// so that bus activity wakes the chip and asserts nINT.
+    ///
+    /// Returns [`ConfigError::ControllerBusy`] without changing modes if the
+    /// TXQ or a TX FIFO still has a transmission pending, and
+    /// [`ConfigError::ChangeOpModeTimeout`] if the controller doesn't enter
+    /// Sleep mode within about 2ms. The registers stay readable while the
+    /// chip sleeps, only clearing `OSC.OSCDIS` wakes it up, see
+    /// [`MCP2518FD::wake`].
+    pub async fn sleep(
+        &mut self,
+        wake_on_can: bool,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), ConfigError> {
+        if !self.all_tx_fifos_empty().await? {
+            return Err(ConfigError::ControllerBusy);
+        }
+
+        let c1int = self.read_register::<InterruptRegister>().await?;
+
+        // Writing 1 to the flags leaves them untouched
+        let mut c1int = InterruptRegister(c1int.0 | 0xFFFF);
+        c1int.set_wakie(wake_on_can);
+
+        self.write_register(c1int).await?;
+
+        self.set_op_mode(OperationMode::Sleep, delay).await
+    }
+
+    /// Wakes the chip up from Sleep mode and switches it to `op_mode`.
+    ///
+    /// The chip is woken up by clearing `OSC.OSCDIS`, after which its
+    /// oscillator needs time to restart. This polls `OSC.OSCRDY` and
+    /// `OSC.SCLKRDY` for up to 5ms and returns
+    /// [`ConfigError::OscillatorNotReady`] if the clock doesn't stabilize. Keep
+    /// the SPI clock low until then. If the chip was woken up by bus activity,
+    /// clear the wake up flag with [`MCP2518FD::clear_system_interrupts`].
+    pub async fn wake(
+        &mut self,
+        op_mode: OperationMode,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), ConfigError> {
+        const MAX_ATTEMPTS: usize = 10;
+
+        self.modify_register(|mut osc: OscillatorControlRegister| {
+            osc.set_oscdis(false);
+            osc
+        })
+        .await?;
+
+        for i in 0..MAX_ATTEMPTS {
+            let osc = self.read_register::<OscillatorControlRegister>().await?;
+
+            if osc.oscrdy() && osc.sclkrdy() {
                 break;
             } else if i == MAX_ATTEMPTS - 1 {
-                return Err(ConfigError::ChangeOpModeTimeout);
+                return Err(ConfigError::OscillatorNotReady);
             }
 
             delay.delay_us(500u32).await;
         }
 
-        Ok(())
+        self.set_op_mode(op_mode, delay).await
     }
 
     pub async fn configure_osc(
@@ -253,6 +850,14 @@ where
                 SysClkDivider::DivByTwo => true,
             });
 
+            // Frequency of the clock on the CLKO pin, unless it outputs SOF
+            osc.set_clkodiv(match oscillator_settings.clock_output {
+                ClockOutput::SysClk => 0b00,
+                ClockOutput::DivBy2 => 0b01,
+                ClockOutput::DivBy4 => 0b10,
+                ClockOutput::DivBy10 => 0b11,
+            });
+
             // Enable the clock
             osc.set_oscdis(false);
 
@@ -293,65 +898,165 @@ where
         Ok(())
     }
 
+    /// Switches the given pin between its interrupt function and GPIO. The
+    /// output latch is left as-is, so set it with
+    /// [`MCP2518FD::set_gpio_output`] beforehand to avoid glitching the pin
+    /// when it becomes an output.
+    pub async fn configure_gpio(&mut self, pin: GpioPin, mode: PinMode) -> Result<(), Error> {
+        self.modify_register(|mut iocon: IoControlRegister| {
+            iocon.set_pin_mode(pin, mode);
+            iocon
+        })
+        .await
+    }
+
+    /// Drives the given pin high or low. The level only appears on the pin
+    /// while it is configured as [`PinMode::Output`].
+    pub async fn set_gpio_output(&mut self, pin: GpioPin, high: bool) -> Result<(), Error> {
+        self.modify_register(|mut iocon: IoControlRegister| {
+            iocon.set_latch(pin, high);
+            iocon
+        })
+        .await
+    }
+
+    /// Reads the current level of the given pin
+    pub async fn read_gpio_input(&mut self, pin: GpioPin) -> Result<bool, Error> {
+        Ok(self.read_register::<IoControlRegister>().await?.input(pin))
+    }
+
+    /// Writes the nominal and data bit timing as well as the transmitter delay
+    /// compensation. `C1NBTCFG`, `C1DBTCFG`, and `C1TDC` are adjacent, so all
+    /// three are read in one SPI transaction and written in another.
     pub async fn configure_bit_timing(
         &mut self,
         bit_time_config: BitTimeConfiguration,
     ) -> Result<(), ConfigError> {
-        self.modify_register(|mut cinbtcfg: NominalBitTimeConfigurationRegister| {
-            cinbtcfg.set_brp(bit_time_config.nominal.baud_rate_prescaler);
-            cinbtcfg.set_tseg1(bit_time_config.nominal.time_segment_1);
-            cinbtcfg.set_tseg2(bit_time_config.nominal.time_segment_2.value());
-            cinbtcfg.set_sjw(bit_time_config.nominal.synchronization_jump_width.value());
+        let [nbtcfg, dbtcfg, tdc] = self.read_sfr_words(&SFRAddress::C1NBTCFG).await?;
+
+        let mut cinbtcfg = NominalBitTimeConfigurationRegister::from(nbtcfg);
+        let mut cidbtcfg = DataBitTimeConfigurationRegister::from(dbtcfg);
+        let mut citdc = TransmitterDelayCompensationRegister::from(tdc);
+
+        cinbtcfg.set_brp(bit_time_config.nominal.baud_rate_prescaler);
+        cinbtcfg.set_tseg1(bit_time_config.nominal.time_segment_1);
+        cinbtcfg.set_tseg2(bit_time_config.nominal.time_segment_2.value());
+        cinbtcfg.set_sjw(bit_time_config.nominal.synchronization_jump_width.value());
+
+        cidbtcfg.set_brp(bit_time_config.data.baud_rate_prescaler);
+        cidbtcfg.set_tseg1(bit_time_config.data.time_segment_1.value());
+        cidbtcfg.set_tseg2(bit_time_config.data.time_segment_2.value());
+        cidbtcfg.set_sjw(bit_time_config.data.synchronization_jump_width.value());
+
+        match bit_time_config.data.transmitter_delay_compensation {
+            TransmitterDelayCompensation::Disabled => {
+                citdc.set_tdcmod(TransmitterDelayCompensationMode::Disabled);
+                citdc.set_tdcv(0);
+            }
+            TransmitterDelayCompensation::Manual(tdcv) => {
+                citdc.set_tdcmod(TransmitterDelayCompensationMode::Manual);
+                citdc.set_tdcv(tdcv.value());
+            }
+            TransmitterDelayCompensation::Automatic => {
+                citdc.set_tdcmod(TransmitterDelayCompensationMode::Automatic);
+                citdc.set_tdcv(0);
+            }
+        }
 
-            cinbtcfg
-        })
-        .await?;
+        citdc.set_sid11en(bit_time_config.enable_sid11);
+        citdc.set_edgflten(bit_time_config.enable_edge_filtering);
 
-        self.modify_register(|mut cidbtcfg: DataBitTimeConfigurationRegister| {
-            cidbtcfg.set_brp(bit_time_config.data.baud_rate_prescaler);
-            cidbtcfg.set_tseg1(bit_time_config.data.time_segment_1.value());
-            cidbtcfg.set_tseg2(bit_time_config.data.time_segment_2.value());
-            cidbtcfg.set_sjw(bit_time_config.data.synchronization_jump_width.value());
+        citdc.set_tdco(bit_time_config.data.transmitter_delay_compensation_offset);
 
-            cidbtcfg
-        })
+        self.write_sfr_words(
+            &SFRAddress::C1NBTCFG,
+            [cinbtcfg.0, cidbtcfg.0, citdc.0],
+            [
+                NominalBitTimeConfigurationRegister::VOLATILE_BITS,
+                DataBitTimeConfigurationRegister::VOLATILE_BITS,
+                TransmitterDelayCompensationRegister::VOLATILE_BITS,
+            ],
+        )
         .await?;
 
-        self.modify_register(|mut citdc: TransmitterDelayCompensationRegister| {
-            citdc.set_tdcmod(TransmitterDelayCompensationMode::Automatic);
-            citdc.set_tdco(
-                bit_time_config
-                    .data
-                    .transmitter_delay_compensation_offset
-                    .value(),
-            );
-            citdc.set_tdcv(0);
+        Ok(())
+    }
 
-            citdc
-        })
-        .await?;
+    /// Reads back the bit timing configuration that is currently in effect.
+    ///
+    /// In automatic transmitter delay compensation mode the loop delay
+    /// measured by the chip isn't reported, read `C1TDC.TDCV` for that.
+    pub async fn get_bit_timing(&mut self) -> Result<BitTimeConfiguration, Error> {
+        // C1NBTCFG, C1DBTCFG, and C1TDC are adjacent, so read them all at once
+        let [nbtcfg, dbtcfg, tdc] = self.read_sfr_words(&SFRAddress::C1NBTCFG).await?;
+
+        let nbtcfg = NominalBitTimeConfigurationRegister::from(nbtcfg);
+        let dbtcfg = DataBitTimeConfigurationRegister::from(dbtcfg);
+        let tdc = TransmitterDelayCompensationRegister::from(tdc);
+
+        let nominal = NominalBitTimeConfiguration {
+            baud_rate_prescaler: nbtcfg.brp(),
+            time_segment_1: nbtcfg.tseg1(),
+            time_segment_2: u7::new(nbtcfg.tseg2()),
+            synchronization_jump_width: u7::new(nbtcfg.sjw()),
+        };
 
-        Ok(())
+        let data = DataBitTimeConfiguration {
+            baud_rate_prescaler: dbtcfg.brp(),
+            time_segment_1: u5::new(dbtcfg.tseg1()),
+            time_segment_2: u4::new(dbtcfg.tseg2()),
+            synchronization_jump_width: u4::new(dbtcfg.sjw()),
+            transmitter_delay_compensation_offset: tdc.tdco(),
+            transmitter_delay_compensation: match tdc.tdcmod() {
+                Ok(TransmitterDelayCompensationMode::Disabled) => {
+                    TransmitterDelayCompensation::Disabled
+                }
+                Ok(TransmitterDelayCompensationMode::Manual) => {
+                    TransmitterDelayCompensation::Manual(u6::new(tdc.tdcv()))
+                }
+                _ => TransmitterDelayCompensation::Automatic,
+            },
+        };
+
+        Ok(BitTimeConfiguration::new(nominal, data)
+            .with_sid11(tdc.sid11en())
+            .with_edge_filtering(tdc.edgflten()))
+    }
+
+    /// Reports whether CAN FD frames sent with the BRS bit set (see
+    /// [`TxMessage::with_bit_rate_switched`]) will actually switch to a faster
+    /// data bit rate with the current configuration. This requires that bit
+    /// rate switching isn't disabled (`C1CON.BRSDIS`), that the controller
+    /// isn't in CAN 2.0 mode, and that the data bit time is shorter than the
+    /// nominal bit time.
+    pub async fn data_phase_active(&mut self) -> Result<bool, Error> {
+        let c1con = self.read_register::<CanControlRegister>().await?;
+
+        if c1con.brsdis() || c1con.opmode() == OperationMode::NormalCan2 {
+            return Ok(false);
+        }
+
+        let bit_timing = self.get_bit_timing().await?;
+
+        Ok(bit_timing.data.bit_time_sysclk() < bit_timing.nominal.bit_time_sysclk())
     }
 
     /// Enables/Disables the transmit event FIFO by setting C1CON.STEF and appropriate C1TEFCON bits.
-    /// Be aware that fifo_size MUST be <= 32 and > 0, any other values will be clamped to 32.
+    /// Be aware that fifo_size MUST be <= 32 and > 0, any other values will be clamped into that
+    /// range.
     ///
-    /// Also please keep in mind that the total RAM size is 2K and this code does absolutely
-    /// zero validation that your configuration is under this limit. The documentation recommends
-    /// configuring the TEF first, then TEQ, then FIFOs as necessary.
+    /// Also please keep in mind that the total RAM size is 2K. If the TEF doesn't fit next to
+    /// everything else that is currently allocated, [`ConfigError::RamExhausted`] is returned and
+    /// nothing is changed. The documentation recommends configuring the TEF first, then TEQ, then
+    /// FIFOs as necessary.
     pub async fn configure_tx_event_fifo(
         &mut self,
         tx_event_fifo_config: Option<TxEventFifoConfiguration>,
     ) -> Result<(), ConfigError> {
-        self.modify_register(|mut c1con: CanControlRegister| {
-            c1con.set_stef(tx_event_fifo_config.is_some());
-            c1con
-        })
-        .await?;
+        let tef_control = match &tx_event_fifo_config {
+            Some(config) => {
+                let mut tef_control = self.read_register::<TxEventFifoControlRegister>().await?;
 
-        if let Some(config) = tx_event_fifo_config {
-            self.modify_register(|mut tef_control: TxEventFifoControlRegister| {
                 tef_control.set_fifo_size(config.fifo_size);
 
                 tef_control.set_teftsen(config.enable_timestamps);
@@ -361,32 +1066,50 @@ where
                 tef_control.set_tefhie(config.enable_fifo_half_full_interrupt);
                 tef_control.set_tefneie(config.enable_fifo_not_empty_interrupt);
 
-                tef_control
-            })
-            .await?;
+                self.check_ram_budget(TEF_RAM_INDEX, tef_control.ram_bytes())
+                    .await?;
+
+                Some(tef_control)
+            }
+            None => None,
+        };
+
+        self.modify_register(|mut c1con: CanControlRegister| {
+            c1con.set_stef(tx_event_fifo_config.is_some());
+            c1con
+        })
+        .await?;
+
+        if let Some(tef_control) = tef_control {
+            self.write_register(tef_control).await?;
         }
 
         Ok(())
     }
 
     /// Enables/Disables the transmit queue by setting C1CON.TXEN and appropriate C1TXQCON bits.
-    /// Be aware that fifo_size MUST be <= 32 and > 0, any other values will be clamped to 32.
+    /// Be aware that fifo_size MUST be <= 32 and > 0, any other values will be clamped into that
+    /// range. A message_priority above [`HIGHEST_FIFO_PRIORITY`] is rejected with
+    /// [`ConfigError::InvalidPriority`].
     ///
-    /// Also please keep in mind that the total RAM size is 2K and this code does absolutely
-    /// zero validation that your configuration is under this limit. The documentation recommends
-    /// configuring the TEF first, then TEQ, then FIFOs as necessary.
+    /// Also please keep in mind that the total RAM size is 2K. If the TXQ doesn't fit next to
+    /// everything else that is currently allocated, [`ConfigError::RamExhausted`] is returned and
+    /// nothing is changed. The documentation recommends configuring the TEF first, then TEQ, then
+    /// FIFOs as necessary.
     pub async fn configure_tx_queue(
         &mut self,
         tx_queue_config: Option<TxQueueConfiguration>,
     ) -> Result<(), ConfigError> {
-        self.modify_register(|mut c1con: CanControlRegister| {
-            c1con.set_txqen(tx_queue_config.is_some());
-            c1con
-        })
-        .await?;
+        if let Some(config) = &tx_queue_config {
+            if config.message_priority > HIGHEST_FIFO_PRIORITY {
+                return Err(ConfigError::InvalidPriority(config.message_priority));
+            }
+        }
+
+        let tx_queue_control = match &tx_queue_config {
+            Some(config) => {
+                let mut tx_queue_control = self.read_register::<TxQueueControlRegister>().await?;
 
-        if let Some(config) = tx_queue_config {
-            self.modify_register(|mut tx_queue_control: TxQueueControlRegister| {
                 tx_queue_control.set_retransmission_attempts(config.retransmission_attempts);
                 tx_queue_control.set_txpri(config.message_priority);
                 tx_queue_control.set_fifo_size(config.fifo_size);
@@ -396,69 +1119,316 @@ where
                 tx_queue_control.set_txqeie(config.enable_queue_empty_interrupt);
                 tx_queue_control.set_txqnie(config.enable_queue_not_full_interrupt);
 
-                tx_queue_control
-            })
-            .await?;
+                self.check_ram_budget(TXQ_RAM_INDEX, tx_queue_control.ram_bytes())
+                    .await?;
+
+                Some(tx_queue_control)
+            }
+            None => None,
+        };
+
+        self.modify_register(|mut c1con: CanControlRegister| {
+            c1con.set_txqen(tx_queue_config.is_some());
+            c1con
+        })
+        .await?;
+
+        if let Some(tx_queue_control) = tx_queue_control {
+            self.write_register(tx_queue_control).await?;
         }
 
         Ok(())
     }
 
+    /// Checks that the object at `index` (see [`check_ram_budget`]) still
+    /// fits in RAM when it needs `requested` bytes, given how everything else
+    /// is currently allocated
+    async fn check_ram_budget(&mut self, index: usize, requested: u32) -> Result<(), ConfigError> {
+        let sizes = self.ram_object_sizes().await?;
+
+        check_ram_budget(&sizes, index, requested)
+    }
+
+    /// Sets the time base counter prescaler, which determines the resolution of
+    /// message timestamps, and when during a frame the timestamp is taken
+    pub async fn configure_timestamp(
+        &mut self,
+        timestamp_config: TimestampConfiguration,
+    ) -> Result<(), ConfigError> {
+        self.modify_register(|mut tscon: TimeStampControlRegister| {
+            tscon.set_tbcpre(timestamp_config.prescaler.value());
+            tscon.set_tseof(timestamp_config.timestamp_point == TimestampPoint::EndOfFrame);
+            tscon
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Reads the current value of the time base counter, which message
+    /// timestamps are taken from
+    pub async fn read_time_base_counter(&mut self) -> Result<u32, Error> {
+        Ok(self
+            .read_register::<TimeBasedCounterRegister>()
+            .await?
+            .tbc())
+    }
+
+    /// Overwrites the time base counter, e.g. to synchronize it with other
+    /// nodes. The counter keeps running from the new value.
+    pub async fn set_time_base_counter(&mut self, value: u32) -> Result<(), Error> {
+        let mut tbc = TimeBasedCounterRegister(0);
+        tbc.set_tbc(value);
+
+        self.write_register(tbc).await
+    }
+
+    /// Zeroes the time base counter, see [`MCP2518FD::set_time_base_counter`]
+    pub async fn reset_time_base_counter(&mut self) -> Result<(), Error> {
+        self.set_time_base_counter(0).await
+    }
+
     /// Configures a FIFO based on the settings provided. As per documentation, a single FIFO must
     /// be dedicated to RX or TX and all objects in that queue must have the same payload size.
+    ///
+    /// The three FIFO level interrupt enables are shared between both modes and their meaning
+    /// depends on `TXEN`, which is always derived from the [`settings::FifoMode`]:
+    ///
+    /// | Bit        | Transmit          | Receive          |
+    /// |------------|-------------------|------------------|
+    /// | `TFNRFNIE` | FIFO not full     | FIFO not empty   |
+    /// | `TFHRFHIE` | FIFO half empty   | FIFO half full   |
+    /// | `TFERFFIE` | FIFO empty        | FIFO full        |
+    ///
+    /// Bits that only apply to the other mode are cleared, so a FIFO that was previously
+    /// configured for the opposite direction doesn't keep stale settings around. A fifo_size
+    /// outside of 1 to 32 is clamped into that range, while a transmit priority above
+    /// [`HIGHEST_FIFO_PRIORITY`] is rejected with [`ConfigError::InvalidPriority`].
+    ///
+    /// If the FIFO doesn't fit in the 2K of RAM next to everything else that is currently
+    /// allocated, [`ConfigError::RamExhausted`] is returned and nothing is changed.
     pub async fn configure_fifo(
         &mut self,
         fifo_number: FifoNumber,
         fifo_config: FifoConfiguration,
-    ) -> Result<(), Error> {
-        self.modify_repeated_register(fifo_number, |mut fifo_control: FifoControlRegister| {
-            fifo_control.set_fifo_size(fifo_config.fifo_size);
-            fifo_control.set_payload_size(fifo_config.payload_size);
-
-            match fifo_config.mode {
-                settings::FifoMode::Transmit(TxFifoConfiguration {
-                    priority,
-                    retransmission_attempts,
-                    enable_auto_rtr,
-                    enable_transmit_attempts_exhausted_interrupt,
-                    enable_fifo_empty_interrupt,
-                    enable_fifo_half_empty_interrupt,
-                    enable_fifo_not_full_interrupt,
-                }) => {
-                    fifo_control.set_txen(true);
-
-                    fifo_control.set_txpri(priority);
-                    fifo_control.set_retransmission_attempts(retransmission_attempts);
-                    fifo_control.set_rtren(enable_auto_rtr);
-                    fifo_control.set_txatie(enable_transmit_attempts_exhausted_interrupt);
-                    fifo_control.set_tferffie(enable_fifo_empty_interrupt);
-                    fifo_control.set_tfhrfhie(enable_fifo_half_empty_interrupt);
-                    fifo_control.set_tfnrfnie(enable_fifo_not_full_interrupt);
-                }
-                settings::FifoMode::Receive(RxFifoConfiguration {
-                    enable_message_timestamps,
-                    enable_fifo_overflow_interrupt,
-                    enable_fifo_full_interrupt,
-                    enable_fifo_half_full_interrupt,
-                    enable_fifo_not_empty_interrupt,
-                }) => {
-                    fifo_control.set_txen(false);
-
-                    fifo_control.set_rxtsen(enable_message_timestamps);
-                    fifo_control.set_rxovie(enable_fifo_overflow_interrupt);
-                    fifo_control.set_tferffie(enable_fifo_full_interrupt);
-                    fifo_control.set_tfhrfhie(enable_fifo_half_full_interrupt);
-                    fifo_control.set_tfnrfnie(enable_fifo_not_empty_interrupt);
+    ) -> Result<(), ConfigError> {
+        let mut fifo_control = self
+            .read_repeated_register::<FifoControlRegister>(fifo_number)
+            .await?;
+
+        fifo_control.set_fifo_size(fifo_config.fifo_size);
+        fifo_control.set_payload_size(fifo_config.payload_size);
+
+        match fifo_config.mode {
+            settings::FifoMode::Transmit(TxFifoConfiguration {
+                priority,
+                retransmission_attempts,
+                enable_auto_rtr,
+                enable_transmit_attempts_exhausted_interrupt,
+                enable_fifo_empty_interrupt,
+                watermark,
+                enable_fifo_not_full_interrupt,
+            }) => {
+                if priority > HIGHEST_FIFO_PRIORITY {
+                    return Err(ConfigError::InvalidPriority(priority));
                 }
+
+                fifo_control.set_txen(true);
+
+                fifo_control.set_rxtsen(false);
+                fifo_control.set_rxovie(false);
+
+                fifo_control.set_txpri(priority);
+                fifo_control.set_retransmission_attempts(retransmission_attempts);
+                fifo_control.set_rtren(enable_auto_rtr);
+                fifo_control.set_txatie(enable_transmit_attempts_exhausted_interrupt);
+                fifo_control.set_tferffie(enable_fifo_empty_interrupt);
+                fifo_control.set_tfhrfhie(watermark.is_enabled());
+                fifo_control.set_tfnrfnie(enable_fifo_not_full_interrupt);
+            }
+            settings::FifoMode::Receive(RxFifoConfiguration {
+                enable_message_timestamps,
+                enable_fifo_overflow_interrupt,
+                enable_fifo_full_interrupt,
+                watermark,
+                enable_fifo_not_empty_interrupt,
+            }) => {
+                fifo_control.set_txen(false);
+
+                fifo_control.set_txpri(0);
+                fifo_control.set_rtren(false);
+                fifo_control.set_txatie(false);
+
+                fifo_control.set_rxtsen(enable_message_timestamps);
+                fifo_control.set_rxovie(enable_fifo_overflow_interrupt);
+                fifo_control.set_tferffie(enable_fifo_full_interrupt);
+                fifo_control.set_tfhrfhie(watermark.is_enabled());
+                fifo_control.set_tfnrfnie(enable_fifo_not_empty_interrupt);
             }
+        }
 
-            fifo_control
-        })
-        .await?;
+        self.check_ram_budget(fifo_ram_index(fifo_number), fifo_control.ram_bytes())
+            .await?;
+
+        self.write_repeated_register(fifo_number, fifo_control)
+            .await?;
 
         Ok(())
     }
 
+    /// Reads back the configuration of the given FIFO, as it would be passed
+    /// to [`MCP2518FD::configure_fifo`]
+    pub async fn get_fifo_configuration(
+        &mut self,
+        fifo_number: FifoNumber,
+    ) -> Result<FifoConfiguration, Error> {
+        let fifo_control = self
+            .read_repeated_register::<FifoControlRegister>(fifo_number)
+            .await?;
+
+        let mode = if fifo_control.txen() {
+            settings::FifoMode::Transmit(TxFifoConfiguration {
+                priority: fifo_control.txpri(),
+                retransmission_attempts: fifo_control.retransmission_attempts(),
+                enable_auto_rtr: fifo_control.rtren(),
+                enable_transmit_attempts_exhausted_interrupt: fifo_control.txatie(),
+                enable_fifo_empty_interrupt: fifo_control.tferffie(),
+                watermark: match fifo_control.tfhrfhie() {
+                    true => TxWatermark::HalfEmpty,
+                    false => TxWatermark::Disabled,
+                },
+                enable_fifo_not_full_interrupt: fifo_control.tfnrfnie(),
+            })
+        } else {
+            settings::FifoMode::Receive(RxFifoConfiguration {
+                enable_message_timestamps: fifo_control.rxtsen(),
+                enable_fifo_overflow_interrupt: fifo_control.rxovie(),
+                enable_fifo_full_interrupt: fifo_control.tferffie(),
+                watermark: match fifo_control.tfhrfhie() {
+                    true => RxWatermark::HalfFull,
+                    false => RxWatermark::Disabled,
+                },
+                enable_fifo_not_empty_interrupt: fifo_control.tfnrfnie(),
+            })
+        };
+
+        Ok(FifoConfiguration::new(
+            fifo_control.fifo_size(),
+            fifo_control.payload_size(),
+            mode,
+        ))
+    }
+
+    /// Updates the interrupt enables of several RX FIFOs. Each FIFO has its own
+    /// control register, so this still takes a read-modify-write per FIFO, but
+    /// a failure on one FIFO doesn't stop the rest from being updated.
+    ///
+    /// Returns the set of FIFOs that could not be updated, either because of
+    /// an SPI error or because they are configured for transmission. An empty
+    /// set means every FIFO was updated.
+    pub async fn set_fifo_interrupts_bulk(
+        &mut self,
+        fifo_interrupts: &[(FifoNumber, RxFifoInterrupts)],
+    ) -> FifoSet {
+        let mut failed = FifoSet::new();
+
+        for &(fifo_number, interrupts) in fifo_interrupts {
+            if self
+                .set_rx_fifo_interrupts(fifo_number, interrupts)
+                .await
+                .is_err()
+            {
+                failed.insert(fifo_number);
+            }
+        }
+
+        failed
+    }
+
+    async fn set_rx_fifo_interrupts(
+        &mut self,
+        fifo_number: FifoNumber,
+        interrupts: RxFifoInterrupts,
+    ) -> Result<(), Error> {
+        let mut fifo_control = self
+            .read_repeated_register::<FifoControlRegister>(fifo_number)
+            .await?;
+
+        if fifo_control.txen() {
+            return Err(Error::FifoNotRx);
+        }
+
+        fifo_control.set_rxovie(interrupts.enable_fifo_overflow_interrupt);
+        fifo_control.set_tferffie(interrupts.enable_fifo_full_interrupt);
+        fifo_control.set_tfhrfhie(interrupts.watermark.is_enabled());
+        fifo_control.set_tfnrfnie(interrupts.enable_fifo_not_empty_interrupt);
+
+        self.write_repeated_register(fifo_number, fifo_control)
+            .await
+    }
+
+    /// Discards all messages in the given FIFO and resets its head and tail
+    /// pointers by setting `FRESET`, leaving its configuration untouched. The
+    /// reset is complete once `FRESET` reads back as 0.
+    ///
+    /// A TX FIFO must not be transmitting while it is reset, so abort any
+    /// pending transmissions first (see
+    /// [`MCP2518FD::abort_fifo_transmission`]) or do this in Configuration
+    /// mode.
+    pub async fn reset_fifo(&mut self, fifo_number: FifoNumber) -> Result<(), Error> {
+        self.modify_repeated_register(fifo_number, |mut fifocon: FifoControlRegister| {
+            fifocon.set_freset();
+            fifocon
+        })
+        .await
+    }
+
+    /// Discards all messages in the TXQ, see [`MCP2518FD::reset_fifo`]. Abort
+    /// any pending transmissions first (see
+    /// [`MCP2518FD::abort_tx_queue_transmission`]).
+    pub async fn reset_tx_queue(&mut self) -> Result<(), Error> {
+        self.modify_register(|mut txqcon: TxQueueControlRegister| {
+            txqcon.set_freset();
+            txqcon
+        })
+        .await
+    }
+
+    /// Discards all objects in the TEF, see [`MCP2518FD::reset_fifo`]
+    pub async fn reset_tef(&mut self) -> Result<(), Error> {
+        self.modify_register(|mut tefcon: TxEventFifoControlRegister| {
+            tefcon.set_freset();
+            tefcon
+        })
+        .await
+    }
+
+    /// Resets all 31 FIFOs back to their default configuration (a single 8
+    /// byte RX object with all interrupts disabled) and discards any messages
+    /// they hold. The bit timing, TEF, and TXQ configuration is left as-is.
+    ///
+    /// All FIFO registers are laid out contiguously, so this is done in a
+    /// single SPI transaction (the status flags are cleared as well, and the
+    /// read-only user address registers are unaffected). This must be done in
+    /// Configuration mode, since the FIFO size and payload size can't be
+    /// changed otherwise.
+    pub async fn clear_all_fifos(&mut self) -> Result<(), Error> {
+        const FIFO_REGISTERS_SIZE: usize = 3 * 4;
+        const FIFO_BLOCK_SIZE: usize = MAX_FIFO_COUNT * FIFO_REGISTERS_SIZE;
+
+        let mut fifo_control = FifoControlRegister(0);
+        fifo_control.set_retransmission_attempts(RetransmissionAttempts::UnlimitedRetries);
+        fifo_control.set_freset();
+
+        let mut block = [0u8; FIFO_BLOCK_SIZE];
+
+        for registers in block.chunks_exact_mut(FIFO_REGISTERS_SIZE) {
+            registers[0..4].copy_from_slice(&fifo_control.0.to_le_bytes());
+        }
+
+        self.write_sfr_block(&SFRAddress::C1FIFOCON1, &block).await
+    }
+
     /// Configures one of the 32 acceptance filters. If the filter_config is
     /// None, the filter will be disabled instead.
     ///
@@ -494,54 +1464,11 @@ where
             return Ok(());
         };
 
-        // Set filter object bits and filter mode
-        self.modify_repeated_register(
-            filter_number,
-            |mut object_register: FilterObjectRegister| {
-                match filter_config.filter_bits {
-                    Id::Standard(id) => {
-                        object_register.set_sid(id.as_raw());
-                        object_register.set_eid(0);
-                    }
-                    Id::Extended(id) => {
-                        object_register.set_sid(id.standard_id().as_raw());
-                        object_register
-                            .set_eid(id.as_raw() & ((!StandardId::MAX.as_raw() as u32) >> 11));
-                    }
-                }
-
-                object_register.set_exide(match filter_config.mode {
-                    FilterMatchMode::StandardOnly | FilterMatchMode::Both => false,
-                    FilterMatchMode::ExtendedOnly => true,
-                });
-
-                object_register
-            },
-        )
-        .await?;
-
-        // Set the mask bits and exclusion mode
-        self.modify_repeated_register(filter_number, |mut mask_register: MaskRegister| {
-            match filter_config.mask_bits {
-                Id::Standard(id) => {
-                    mask_register.set_msid(id.as_raw());
-                    mask_register.set_meid(0);
-                }
-                Id::Extended(id) => {
-                    mask_register.set_msid(id.standard_id().as_raw());
-                    mask_register
-                        .set_meid(id.as_raw() & ((!StandardId::MAX.as_raw() as u32) >> 11));
-                }
-            }
-
-            mask_register.set_mide(match filter_config.mode {
-                FilterMatchMode::Both => false,
-                FilterMatchMode::StandardOnly | FilterMatchMode::ExtendedOnly => true,
-            });
-
-            mask_register
-        })
-        .await?;
+        // Set filter object bits, mask bits, and match mode
+        self.write_repeated_register(filter_number, filter_config.object_register())
+            .await?;
+        self.write_repeated_register(filter_number, filter_config.mask_register())
+            .await?;
 
         // Set the BP and reenable the filter
         self.modify_repeated_register(
@@ -557,118 +1484,535 @@ where
         Ok(())
     }
 
-    /* Transmit and Receive Functions */
+    /// Configures several acceptance filters at once, see
+    /// [`MCP2518FD::configure_filter`]. Consecutive entries whose filters
+    /// share a filter control register (filters 0-3 share C1FLTCON0, 4-7
+    /// share C1FLTCON1, and so on) are grouped, so the control register is
+    /// read once and written once at the end to set the buffer pointers and
+    /// enable the filters. If any of them was enabled, it is also written
+    /// once before to disable them.
+    pub async fn configure_filters(
+        &mut self,
+        filters: &[(FilterNumber, FilterConfiguration)],
+    ) -> Result<(), Error> {
+        let mut remaining = filters;
+
+        while let Some(((first, _), _)) = remaining.split_first() {
+            let (control_register_number, _) = first.get_control_register();
+
+            let group_len = remaining
+                .iter()
+                .take_while(|(filter_number, _)| {
+                    filter_number.get_control_register().0 == control_register_number
+                })
+                .count();
+            let (group, rest) = remaining.split_at(group_len);
+            remaining = rest;
+
+            let mut control = self
+                .read_repeated_register::<FilterControlRegister>(control_register_number)
+                .await?;
+
+            // The filter objects and masks can only be written while disabled
+            let was_enabled = control.0;
+            for (filter_number, _) in group {
+                control.set_enabled(filter_number.get_control_register().1, false);
+            }
 
-    /// Pushes a new message into the TXQ without setting the TXREQ bit to
-    /// request transmission.
-    ///
-    /// Use this function only if you need to queue multiple messages before
-    /// transmitting all at once. To push a single message and immediately
-    /// request transmission, use [`MCP2518FD::tx_queue_transmit_message`].
-    pub async fn tx_queue_push_message(&mut self, message: &TxMessage) -> Result<(), Error> {
-        /* Make sure TXQ is enabled */
+            if control.0 != was_enabled {
+                self.write_repeated_register(
+                    control_register_number,
+                    FilterControlRegister(control.0),
+                )
+                .await?;
+            }
 
-        if !self.read_register::<CanControlRegister>().await?.txqen() {
-            return Err(Error::TxQueueDisabled);
-        }
+            for (filter_number, filter_config) in group {
+                self.write_repeated_register(*filter_number, filter_config.object_register())
+                    .await?;
+                self.write_repeated_register(*filter_number, filter_config.mask_register())
+                    .await?;
 
-        let mut control_register = self.read_register::<TxQueueControlRegister>().await?;
+                let (_, filter_index) = filter_number.get_control_register();
+                control.set_buffer_pointer(filter_index, filter_config.buffer_pointer);
+                control.set_enabled(filter_index, true);
+            }
 
-        /* Make sure FIFO is big enough */
+            self.write_repeated_register(control_register_number, control)
+                .await?;
+        }
 
-        if control_register.payload_size().num_bytes() < message.data().len() {
-            return Err(Error::FifoTooSmall);
+        Ok(())
+    }
+
+    /// Sets up a TX FIFO that automatically answers remote frames requesting
+    /// `response.id()` with `response`, as used by CANopen and other legacy
+    /// protocols.
+    ///
+    /// The chip is switched to Configuration mode to turn `fifo_number` into a
+    /// single message TX FIFO at [`HIGHEST_FIFO_PRIORITY`] with `RTREN` set and
+    /// to point `filter_number` at it, matching the response ID exactly. The
+    /// previous operation mode is then restored and the response is loaded into
+    /// the FIFO without setting TXREQ. When a matching remote frame is
+    /// received, the controller sets TXREQ by itself and sends the response.
+    ///
+    /// FIFOs are held in reset while in Configuration mode, so the chip must
+    /// be in one of the normal operation modes when calling this, otherwise
+    /// [`ConfigError::InConfigurationMode`] is returned. Since the FIFO is
+    /// emptied by every reply, push the response again with
+    /// [`MCP2518FD::tx_fifo_push_message`] to answer the next request, which is
+    /// also how the payload is updated.
+    ///
+    /// Switching to Configuration mode resets every FIFO, and resizing
+    /// `fifo_number` moves the RAM of all higher numbered FIFOs. Call this
+    /// right after [`MCP2518FD::configure`], before queuing any other messages.
+    pub async fn configure_rtr_responder<const N: usize>(
+        &mut self,
+        fifo_number: FifoNumber,
+        filter_number: FilterNumber,
+        response: &TxMessage<N>,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), ConfigError> {
+        let payload_size =
+            PayloadSize::for_len(response.data().len()).unwrap_or(PayloadSize::Bytes64);
+
+        let fifo_config = FifoConfiguration::new(
+            1,
+            payload_size,
+            settings::FifoMode::Transmit(
+                TxFifoConfiguration::new(HIGHEST_FIFO_PRIORITY).with_auto_rtr(true),
+            ),
+        );
+        let filter_config = FilterConfiguration::match_exact(fifo_number, response.id());
+
+        let previous_mode = self.get_op_mode().await?;
+
+        if previous_mode == OperationMode::Configuration {
+            return Err(ConfigError::InConfigurationMode);
         }
 
-        /* Make sure FIFO is not full */
+        self.set_op_mode(OperationMode::Configuration, delay)
+            .await?;
 
-        let status_register = self.read_register::<TxQueueStatusRegister>().await?;
+        let configured = match self.configure_fifo(fifo_number, fifo_config).await {
+            Ok(()) => self
+                .configure_filter(filter_number, Some(filter_config))
+                .await
+                .map_err(ConfigError::from),
+            Err(e) => Err(e),
+        };
 
-        if !status_register.txqnif() {
-            return Err(Error::FifoFull);
-        }
+        let restored = self.set_op_mode(previous_mode, delay).await;
 
-        /* Write message to RAM */
+        configured?;
+        restored?;
 
-        let ram_address = self
-            .read_repeated_register::<UserAddressRegister>(UserAddressKind::TxQueue)
-            .await?
-            .calculate_ram_address();
+        self.tx_fifo_push_message(fifo_number, response).await?;
 
-        let (length, bytes) = message.as_bytes();
+        Ok(())
+    }
 
-        // We need to make sure that the data we are writing to ram has a length
-        // which is a multiple of 4. By adding (4 - length % 4), we extend the
-        // length to the next multiple 4 boundary. This isn't always a good
-        // solution but in this specific case it works because we know that the
-        // there is definitely at least that much ram allocated for the TX
-        // message (we only do this when the DLC is < 8 and the minimum number
-        // of bytes allocated for a TX message is 8)
-        let data = &bytes[..length + (4 - length % 4)];
+    /// Reads back the configuration of one of the 32 acceptance filters.
+    /// Returns `None` if the filter is disabled or its buffer pointer doesn't
+    /// hold a valid FIFO number.
+    ///
+    /// See [`FilterConfiguration::from_registers`] for how the IDs are
+    /// reconstructed.
+    pub async fn get_filter_configuration(
+        &mut self,
+        filter_number: FilterNumber,
+    ) -> Result<Option<FilterConfiguration>, Error> {
+        let (control_register_number, filter_index) = filter_number.get_control_register();
 
-        self.write_ram(ram_address as u16, data).await?;
+        let control = self
+            .read_repeated_register::<FilterControlRegister>(control_register_number)
+            .await?;
 
-        /* Increment tail pointer but do NOT request transmission */
+        if !control.is_enabled(filter_index) {
+            return Ok(None);
+        }
 
-        control_register.set_uinc();
+        let Some(buffer_pointer) = control.get_buffer_pointer(filter_index) else {
+            return Ok(None);
+        };
 
-        self.write_register(control_register).await?;
+        let object = self
+            .read_repeated_register::<FilterObjectRegister>(filter_number)
+            .await?;
+        let mask = self
+            .read_repeated_register::<MaskRegister>(filter_number)
+            .await?;
 
-        Ok(())
+        Ok(Some(FilterConfiguration::from_registers(
+            buffer_pointer,
+            &object,
+            &mask,
+        )))
     }
 
-    /// Requests transmission of all messages in the TXQ by setting the TXREQ
-    /// bit.
+    /// Reads back every enabled acceptance filter and determines which FIFO
+    /// (if any) a message with the given ID and data would be routed to.
+    /// Filters are checked in order and the lowest numbered matching filter
+    /// wins, just like on the chip.
     ///
-    /// Use this function only if you already previously queued one or more
-    /// messages with [`MCP2518FD::tx_queue_push_message`]. To push a single
-    /// message and immediately request transmission, prefer
-    /// [`MCP2518FD::tx_queue_transmit_message`].
-    pub async fn tx_queue_request_transmission(&mut self) -> Result<(), Error> {
-        self.modify_register(|mut txqcon: TxQueueControlRegister| {
-            txqcon.set_txreq(true);
-            txqcon
-        })
-        .await?;
+    /// For standard frames the data is compared as configured by
+    /// `CiCON.DNCNT`, see [`FilterObjectRegister::matches`]. The filter
+    /// control, object, and mask registers are adjacent, so they are all read
+    /// in a single burst (2 transactions with the `spi-crc` feature), plus
+    /// one transaction for `C1CON`.
+    pub async fn would_accept(&mut self, id: Id, data: &[u8]) -> Result<Option<FifoNumber>, Error> {
+        let data_bits = self
+            .read_register::<CanControlRegister>()
+            .await?
+            .data_bits_to_match();
 
-        Ok(())
-    }
+        // C1FLTCON0 to C1FLTCON7, followed by a C1FLTOBJm and C1MASKm pair
+        // for each filter
+        const FILTER_CONTROL_COUNT: usize = MAX_FILTER_COUNT / 4;
+        let block: [u32; FILTER_CONTROL_COUNT + 2 * MAX_FILTER_COUNT] =
+            self.read_sfr_words(&SFRAddress::C1FLTCON0).await?;
 
-    /// Pushes a message into the TXQ and immediately requests transmission by
-    /// setting the TXREQ bit.
-    ///
-    /// To push multiple messages before requesting transmission, see
-    /// [`MCP2518FD::tx_queue_push_message`] and
-    /// [`MCP2518FD::tx_queue_request_transmission`].
-    pub async fn tx_queue_transmit_message(&mut self, message: &TxMessage) -> Result<(), Error> {
-        self.tx_queue_push_message(message).await?;
-        self.tx_queue_request_transmission().await?;
+        let (controls, pairs) = block.split_at(FILTER_CONTROL_COUNT);
 
-        Ok(())
-    }
+        for (filter, pair) in pairs.chunks_exact(2).enumerate() {
+            let (control_register_number, filter_index) = FilterNumber::try_from(filter as u8)
+                .unwrap()
+                .get_control_register();
+            let control = FilterControlRegister::from(controls[control_register_number as usize]);
 
-    /// Pushes a new message into the given TX FIFO without setting the TXREQ
-    /// bit to request transmission.
-    ///
-    /// Use this function only if you need to queue multiple messages before
-    /// transmitting all at once. To push a single message and immediately
-    /// request transmission, use [`MCP2518FD::tx_fifo_transmit_message`].
-    pub async fn tx_fifo_push_message(
-        &mut self,
-        fifo_number: FifoNumber,
-        message: &TxMessage,
-    ) -> Result<(), Error> {
-        let mut control_register = self
-            .read_repeated_register::<FifoControlRegister>(fifo_number)
-            .await?;
+            if !control.is_enabled(filter_index) {
+                continue;
+            }
 
-        /* Make sure it's a transmit FIFO */
+            let object = FilterObjectRegister::from(pair[0]);
+            let mask = MaskRegister::from(pair[1]);
 
-        if !control_register.txen() {
-            return Err(Error::FifoNotTx);
+            if object.matches(&mask, id, data, data_bits) {
+                return Ok(control.get_buffer_pointer(filter_index));
+            }
         }
 
-        /* Make sure FIFO is big enough */
+        Ok(None)
+    }
+
+    /// Checks whether the given filter routes messages to an RX FIFO whose
+    /// payload size is smaller than the largest CAN FD frame (64 bytes).
+    ///
+    /// The chip can't know the size of incoming frames in advance, so this
+    /// doesn't mean data will be lost, only that any frame larger than the
+    /// FIFO's payload size will be truncated. Returns `false` if the filter is
+    /// disabled.
+    pub async fn filter_may_truncate(
+        &mut self,
+        filter_number: FilterNumber,
+    ) -> Result<bool, Error> {
+        let (control_register_number, filter_index) = filter_number.get_control_register();
+
+        let control = self
+            .read_repeated_register::<FilterControlRegister>(control_register_number)
+            .await?;
+
+        if !control.is_enabled(filter_index) {
+            return Ok(false);
+        }
+
+        let Some(fifo_number) = control.get_buffer_pointer(filter_index) else {
+            return Ok(false);
+        };
+
+        let fifo_control = self
+            .read_repeated_register::<FifoControlRegister>(fifo_number)
+            .await?;
+
+        Ok(!fifo_control.txen() && fifo_control.payload_size() != PayloadSize::Bytes64)
+    }
+
+    /// Reads all 8 filter control registers and returns, for each of the 32
+    /// filters (indexed by filter number), the FIFO it routes to and whether it
+    /// is enabled. The FIFO is `None` if the buffer pointer doesn't hold a
+    /// valid FIFO number.
+    pub async fn routing_table(
+        &mut self,
+    ) -> Result<[(Option<FifoNumber>, bool); MAX_FILTER_COUNT], Error> {
+        let mut table = [(None, false); MAX_FILTER_COUNT];
+
+        for (control_number, filters) in table.chunks_exact_mut(4).enumerate() {
+            let control = self
+                .read_repeated_register::<FilterControlRegister>(
+                    FilterControlNumber::try_from(control_number as u8).unwrap(),
+                )
+                .await?;
+
+            for (index, entry) in filters.iter_mut().enumerate() {
+                let index = FilterControlIndex::try_from(index as u8).unwrap();
+
+                *entry = (control.get_buffer_pointer(index), control.is_enabled(index));
+            }
+        }
+
+        Ok(table)
+    }
+
+    /// Reads back the TEF, TXQ, and FIFO configuration registers and sums up
+    /// how many bytes of the chip's 2 KB of RAM are currently allocated.
+    ///
+    /// The chip always allocates RAM for all 31 FIFOs (at least one 16 byte
+    /// object each, an 8 byte header and 8 bytes of payload), even the ones
+    /// that were never configured.
+    pub async fn used_ram_bytes(&mut self) -> Result<u32, Error> {
+        let sizes = self.ram_object_sizes().await?;
+
+        Ok(sizes.iter().sum())
+    }
+
+    /// Reads how many bytes each RAM object takes up with its current
+    /// configuration, in allocation order (see [`RAM_OBJECT_COUNT`]). The TEF
+    /// and TXQ take up nothing while disabled.
+    async fn ram_object_sizes(&mut self) -> Result<[u32; RAM_OBJECT_COUNT], Error> {
+        let c1con = self.read_register::<CanControlRegister>().await?;
+
+        let mut sizes = [0; RAM_OBJECT_COUNT];
+
+        if c1con.stef() {
+            sizes[TEF_RAM_INDEX] = self
+                .read_register::<TxEventFifoControlRegister>()
+                .await?
+                .ram_bytes();
+        }
+
+        if c1con.txqen() {
+            sizes[TXQ_RAM_INDEX] = self
+                .read_register::<TxQueueControlRegister>()
+                .await?
+                .ram_bytes();
+        }
+
+        for fifo in 1..=MAX_FIFO_COUNT as u8 {
+            let fifo_number = FifoNumber::try_from(fifo).unwrap();
+
+            sizes[fifo_ram_index(fifo_number)] = self
+                .read_repeated_register::<FifoControlRegister>(fifo_number)
+                .await?
+                .ram_bytes();
+        }
+
+        Ok(sizes)
+    }
+
+    /// Calculates how many bytes of the chip's RAM are still free for
+    /// growing FIFOs. See [`MCP2518FD::used_ram_bytes`].
+    pub async fn free_ram_bytes(&mut self) -> Result<u32, Error> {
+        let used = self.used_ram_bytes().await?;
+
+        Ok(RAM_SIZE_BYTES.saturating_sub(used))
+    }
+
+    /// Disables all 32 filters by clearing the 8 filter control registers,
+    /// leaving the filter objects and masks untouched. Unlike
+    /// [`MCP2518FD::clear_all_filters`] this can be used at runtime before
+    /// setting up a new acceptance scheme.
+    pub async fn disable_all_filters(&mut self) -> Result<(), Error> {
+        for control_number in 0..(MAX_FILTER_COUNT / 4) as u8 {
+            self.write_repeated_register(
+                FilterControlNumber::try_from(control_number).unwrap(),
+                FilterControlRegister(0),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Disables all 32 filters and clears their filter object and mask
+    /// registers, leaving the acceptance filters in their blank state. This is
+    /// done in a single SPI transaction since all filter registers are laid
+    /// out contiguously.
+    ///
+    /// The filter objects and masks can only be modified while the filters
+    /// are disabled, which is taken care of since the control registers come
+    /// first. Should be called in Configuration mode.
+    pub async fn clear_all_filters(&mut self) -> Result<(), Error> {
+        const FILTER_BLOCK_SIZE: usize =
+            (SFRAddress::C1MASK31 as usize + 4) - SFRAddress::C1FLTCON0 as usize;
+
+        self.write_sfr_block(&SFRAddress::C1FLTCON0, &[0u8; FILTER_BLOCK_SIZE])
+            .await
+    }
+
+    /* Transmit and Receive Functions */
+
+    /// Pushes a new message into the TXQ without setting the TXREQ bit to
+    /// request transmission.
+    ///
+    /// This takes 4 SPI transactions: C1CON, the TXQ control, status, and
+    /// user address registers in one read, the message object, and the
+    /// control register write to increment the tail pointer.
+    ///
+    /// Use this function only if you need to queue multiple messages before
+    /// transmitting all at once. To push a single message and immediately
+    /// request transmission, use [`MCP2518FD::tx_queue_transmit_message`].
+    pub async fn tx_queue_push_message<const N: usize>(
+        &mut self,
+        message: &TxMessage<N>,
+    ) -> Result<(), Error> {
+        /* Make sure TXQ is enabled */
+
+        let c1con = self.read_register::<CanControlRegister>().await?;
+
+        if !c1con.txqen() {
+            return Err(Error::TxQueueDisabled);
+        }
+
+        /* Make sure the controller can send FD frames */
+
+        if message.header().fdf() && c1con.opmode() == OperationMode::NormalCan2 {
+            return Err(Error::FdFrameInClassicMode);
+        }
+
+        // C1TXQCON, C1TXQSTA, and C1TXQUA are adjacent, so read all three in
+        // a single transaction
+        let [control, status, user_address] = self.read_sfr_words(&SFRAddress::C1TXQCON).await?;
+
+        let mut control_register = TxQueueControlRegister::from(control);
+        let status_register = TxQueueStatusRegister::from(status);
+        let user_address_register = UserAddressRegister::from(user_address);
+
+        /* Make sure FIFO is big enough */
+
+        if control_register.payload_size().num_bytes() < message.data().len() {
+            return Err(Error::FifoTooSmall);
+        }
+
+        /* Make sure FIFO is not full */
+
+        if !status_register.txqnif() {
+            return Err(Error::FifoFull);
+        }
+
+        /* Write message to RAM */
+
+        let ram_address = user_address_register.calculate_ram_address();
+
+        let (length, bytes) = message.as_bytes();
+
+        // We need to make sure that the data we are writing to ram has a length
+        // which is a multiple of 4, so round it up to the next multiple of 4.
+        // Lengths that already are one are left alone, so this never writes
+        // past the RAM allocated for the object (payload sizes are all
+        // multiples of 4 as well)
+        let data = &bytes[..(length + 3) & !3];
+
+        self.write_ram(ram_address as u16, data).await?;
+
+        /* Increment tail pointer but do NOT request transmission */
+
+        control_register.set_uinc();
+
+        self.write_register(control_register).await?;
+
+        self.stats.frames_transmitted = self.stats.frames_transmitted.wrapping_add(1);
+
+        Ok(())
+    }
+
+    /// Requests transmission of all messages in the TXQ by setting the TXREQ
+    /// bit.
+    ///
+    /// Use this function only if you already previously queued one or more
+    /// messages with [`MCP2518FD::tx_queue_push_message`]. To push a single
+    /// message and immediately request transmission, prefer
+    /// [`MCP2518FD::tx_queue_transmit_message`].
+    pub async fn tx_queue_request_transmission(&mut self) -> Result<(), Error> {
+        self.modify_register(|mut txqcon: TxQueueControlRegister| {
+            txqcon.set_txreq(true);
+            txqcon
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Pushes a message into the TXQ and immediately requests transmission by
+    /// setting the TXREQ bit.
+    ///
+    /// To push multiple messages before requesting transmission, see
+    /// [`MCP2518FD::tx_queue_push_message`] and
+    /// [`MCP2518FD::tx_queue_request_transmission`].
+    pub async fn tx_queue_transmit_message<const N: usize>(
+        &mut self,
+        message: &TxMessage<N>,
+    ) -> Result<(), Error> {
+        self.tx_queue_push_message(message).await?;
+        self.tx_queue_request_transmission().await?;
+
+        Ok(())
+    }
+
+    /// Pushes a message into the TXQ, requests transmission, and then waits
+    /// up to `timeout_us` microseconds for the TXQ to run empty (`TXQEIF`),
+    /// which means the frame and anything queued before it left the node.
+    /// Returns [`Error::TransmitTimeout`] otherwise, in which case
+    /// [`MCP2518FD::get_tx_queue_error`] may tell why.
+    ///
+    /// Useful for request/response protocols that must know a request went
+    /// out before waiting for the response.
+    pub async fn transmit_and_wait<const N: usize>(
+        &mut self,
+        message: &TxMessage<N>,
+        delay: &mut impl DelayNs,
+        timeout_us: u32,
+    ) -> Result<(), Error> {
+        const POLL_INTERVAL_US: u32 = 100;
+
+        self.tx_queue_transmit_message(message).await?;
+
+        let mut elapsed_us = 0;
+
+        loop {
+            if self.get_tx_queue_status().await?.txqeif() {
+                return Ok(());
+            }
+
+            if elapsed_us >= timeout_us {
+                return Err(Error::TransmitTimeout);
+            }
+
+            delay.delay_us(POLL_INTERVAL_US).await;
+            elapsed_us += POLL_INTERVAL_US;
+        }
+    }
+
+    /// Pushes a new message into the given TX FIFO without setting the TXREQ
+    /// bit to request transmission.
+    ///
+    /// Use this function only if you need to queue multiple messages before
+    /// transmitting all at once. To push a single message and immediately
+    /// request transmission, use [`MCP2518FD::tx_fifo_transmit_message`].
+    pub async fn tx_fifo_push_message<const N: usize>(
+        &mut self,
+        fifo_number: FifoNumber,
+        message: &TxMessage<N>,
+    ) -> Result<(), Error> {
+        /* Make sure the controller can send FD frames */
+
+        if message.header().fdf()
+            && self.read_register::<CanControlRegister>().await?.opmode()
+                == OperationMode::NormalCan2
+        {
+            return Err(Error::FdFrameInClassicMode);
+        }
+
+        let mut control_register = self
+            .read_repeated_register::<FifoControlRegister>(fifo_number)
+            .await?;
+
+        /* Make sure it's a transmit FIFO */
+
+        if !control_register.txen() {
+            return Err(Error::FifoNotTx);
+        }
+
+        /* Make sure FIFO is big enough */
 
         if control_register.payload_size().num_bytes() < message.data().len() {
             return Err(Error::FifoTooSmall);
@@ -694,13 +2038,11 @@ where
         let (length, bytes) = message.as_bytes();
 
         // We need to make sure that the data we are writing to ram has a length
-        // which is a multiple of 4. By adding (4 - length % 4), we extend the
-        // length to the next multiple 4 boundary. This isn't always a good
-        // solution but in this specific case it works because we know that the
-        // there is definitely at least that much ram allocated for the TX
-        // message (we only do this when the DLC is < 8 and the minimum number
-        // of bytes allocated for a TX message is 8)
-        let data = &bytes[..length + (4 - length % 4)];
+        // which is a multiple of 4, so round it up to the next multiple of 4.
+        // Lengths that already are one are left alone, so this never writes
+        // past the RAM allocated for the object (payload sizes are all
+        // multiples of 4 as well)
+        let data = &bytes[..(length + 3) & !3];
 
         self.write_ram(ram_address as u16, data).await?;
 
@@ -711,6 +2053,8 @@ where
         self.write_repeated_register(fifo_number, control_register)
             .await?;
 
+        self.stats.frames_transmitted = self.stats.frames_transmitted.wrapping_add(1);
+
         Ok(())
     }
 
@@ -740,10 +2084,10 @@ where
     /// To push multiple messages before requesting transmission, see
     /// [`MCP2518FD::tx_fifo_push_message`] and
     /// [`MCP2518FD::tx_fifo_request_transmission`].
-    pub async fn tx_fifo_transmit_message(
+    pub async fn tx_fifo_transmit_message<const N: usize>(
         &mut self,
         fifo_number: FifoNumber,
-        message: &TxMessage,
+        message: &TxMessage<N>,
     ) -> Result<(), Error> {
         self.tx_fifo_push_message(fifo_number, message).await?;
         self.tx_fifo_request_transmission(fifo_number).await?;
@@ -751,6 +2095,150 @@ where
         Ok(())
     }
 
+    /// Requests transmission of every given TX FIFO, and the TXQ if
+    /// `include_txq` is set, with a single write of `C1TXREQ`. Compared to
+    /// calling [`MCP2518FD::tx_fifo_request_transmission`] for each FIFO, this
+    /// takes one SPI transfer instead of a read-modify-write per FIFO, and all
+    /// the FIFOs start taking part in arbitration at the same time.
+    ///
+    /// Bits that are written as 0 are left alone by the controller, so FIFOs
+    /// that aren't listed keep any request that is already pending.
+    pub async fn request_transmission_multi(
+        &mut self,
+        fifos: &[FifoNumber],
+        include_txq: bool,
+    ) -> Result<(), Error> {
+        let mut txreq = TransmitRequestRegister(fifos.iter().copied().collect::<FifoSet>().bits());
+
+        if include_txq {
+            txreq.set_txreq_txq();
+        }
+
+        self.write_register(txreq).await?;
+
+        Ok(())
+    }
+
+    /// Reads the status register of the given FIFO. Besides the interrupt
+    /// flags, `FIFOCI` tells which message slot the FIFO is at, and the
+    /// not empty/half full/full flags (for RX FIFOs) give a cheap estimate of
+    /// how many messages are waiting without reading any of them.
+    pub async fn get_fifo_status(
+        &mut self,
+        fifo_number: FifoNumber,
+    ) -> Result<FifoStatusRegister, Error> {
+        self.read_repeated_register::<FifoStatusRegister>(fifo_number)
+            .await
+    }
+
+    /// Reads the status register of the TXQ. See
+    /// [`MCP2518FD::get_fifo_status`].
+    pub async fn get_tx_queue_status(&mut self) -> Result<TxQueueStatusRegister, Error> {
+        self.read_register::<TxQueueStatusRegister>().await
+    }
+
+    /// Reads why the last transmission from the TXQ failed
+    pub async fn get_tx_queue_error(&mut self) -> Result<TxError, Error> {
+        Ok(self.get_tx_queue_status().await?.tx_error())
+    }
+
+    /// Reads why the last transmission from the given TX FIFO failed
+    pub async fn get_tx_fifo_error(&mut self, fifo_number: FifoNumber) -> Result<TxError, Error> {
+        Ok(self.get_fifo_status(fifo_number).await?.tx_error())
+    }
+
+    /// Clears the error flags read by [`MCP2518FD::get_tx_fifo_error`] of the
+    /// given TX FIFO, or those of the TXQ if `fifo_number` is `None`. The
+    /// other flags in the status register are left untouched.
+    pub async fn clear_tx_errors(&mut self, fifo_number: Option<FifoNumber>) -> Result<(), Error> {
+        match fifo_number {
+            Some(fifo_number) => {
+                self.modify_repeated_register(fifo_number, |mut fifosta: FifoStatusRegister| {
+                    fifosta.clear_tx_errors();
+                    fifosta
+                })
+                .await
+            }
+            None => {
+                self.modify_register(|mut txqsta: TxQueueStatusRegister| {
+                    txqsta.clear_tx_errors();
+                    txqsta
+                })
+                .await
+            }
+        }
+    }
+
+    /// Reads `C1TXREQ` to find which of the TXQ and the TX FIFOs still have a
+    /// pending transmit request. A bit is cleared by the controller once all
+    /// messages in that FIFO have been sent or the request was aborted.
+    pub async fn pending_transmissions(&mut self) -> Result<TransmitRequestRegister, Error> {
+        self.read_register::<TransmitRequestRegister>().await
+    }
+
+    /// Checks with a single read of `C1TXREQ` that neither the TXQ nor any TX
+    /// FIFO has a pending transmit request. This is the precondition for
+    /// safely entering Sleep mode or reconfiguring the chip.
+    pub async fn all_tx_fifos_empty(&mut self) -> Result<bool, Error> {
+        let txreq = self.read_register::<TransmitRequestRegister>().await?;
+
+        Ok(u32::from(txreq) == 0)
+    }
+
+    /// Aborts every pending transmission in the TXQ and all TX FIFOs by setting
+    /// `C1CON.ABAT`, and waits up to 2ms for the controller to finish. A frame
+    /// that is already on the bus is completed first.
+    ///
+    /// Returns [`Error::AbortTimeout`] if the controller doesn't clear `ABAT`
+    /// in time, for example because it is retransmitting a frame that keeps
+    /// losing arbitration.
+    pub async fn abort_all_transmissions(&mut self, delay: &mut impl DelayNs) -> Result<(), Error> {
+        self.modify_register(|mut c1con: CanControlRegister| {
+            c1con.set_abat(true);
+            c1con
+        })
+        .await?;
+
+        /* Delay 2ms checking every 500us for the abort to complete */
+
+        const MAX_ATTEMPTS: usize = 5;
+
+        for i in 0..MAX_ATTEMPTS {
+            let c1con = self.read_register::<CanControlRegister>().await?;
+
+            if !c1con.abat() {
+                break;
+            } else if i == MAX_ATTEMPTS - 1 {
+                return Err(Error::AbortTimeout);
+            }
+
+            delay.delay_us(500u32).await;
+        }
+
+        Ok(())
+    }
+
+    /// Requests an abort of the pending transmissions in the given TX FIFO by
+    /// clearing its TXREQ bit. A frame that is already on the bus is completed
+    /// first, so poll `TXREQ` if you need to know when the FIFO is idle.
+    pub async fn abort_fifo_transmission(&mut self, fifo_number: FifoNumber) -> Result<(), Error> {
+        self.modify_repeated_register(fifo_number, |mut fifocon: FifoControlRegister| {
+            fifocon.set_txreq(false);
+            fifocon
+        })
+        .await
+    }
+
+    /// Requests an abort of the pending transmissions in the TXQ by clearing
+    /// its TXREQ bit. See [`MCP2518FD::abort_fifo_transmission`].
+    pub async fn abort_tx_queue_transmission(&mut self) -> Result<(), Error> {
+        self.modify_register(|mut txqcon: TxQueueControlRegister| {
+            txqcon.set_txreq(false);
+            txqcon
+        })
+        .await
+    }
+
     /// Checks to see if there are any messages in the TEF
     pub async fn tx_event_fifo_has_next(&mut self) -> Result<bool, Error> {
         let status_register = self.read_register::<TxEventFifoStatusRegister>().await?;
@@ -781,31 +2269,12 @@ where
 
         let control_register = self.read_register::<TxEventFifoControlRegister>().await?;
 
-        let obj = if control_register.teftsen() {
-            let mut buf = [0u8; 12];
-
-            self.read_ram(ram_address as u16, &mut buf).await?;
+        let mut buf = [0u8; TxEventObject::ram_size(true)];
+        let buf = &mut buf[..TxEventObject::ram_size(control_register.teftsen())];
 
-            TxEventObject {
-                header: TxHeader([
-                    u32::from_le_bytes(buf[0..4].try_into().unwrap()),
-                    u32::from_le_bytes(buf[4..8].try_into().unwrap()),
-                ]),
-                timestamp: Some(u32::from_le_bytes(buf[8..12].try_into().unwrap())),
-            }
-        } else {
-            let mut buf = [0u8; 8];
+        self.read_ram(ram_address as u16, buf).await?;
 
-            self.read_ram(ram_address as u16, &mut buf).await?;
-
-            TxEventObject {
-                header: TxHeader([
-                    u32::from_le_bytes(buf[0..4].try_into().unwrap()),
-                    u32::from_le_bytes(buf[4..8].try_into().unwrap()),
-                ]),
-                timestamp: None,
-            }
-        };
+        let obj = TxEventObject::from_ram_bytes(buf);
 
         Ok(Some(obj))
     }
@@ -832,18 +2301,74 @@ where
         Ok(Some(obj))
     }
 
-    /// Checks to see if there are any messages in the given receive FIFO
-    pub async fn rx_fifo_has_next(&mut self, fifo_number: FifoNumber) -> Result<bool, Error> {
-        /* Make sure it's a receive FIFO */
+    /// Reads TEF objects into `out` until either the TEF is empty or `out` is
+    /// full, incrementing the TEF tail pointer after each one. Returns how
+    /// many objects were written to the start of `out`.
+    ///
+    /// `C1TEFCON` is only read once to find out whether objects carry a
+    /// timestamp. Each object then takes 3 SPI transactions: `C1TEFSTA` and
+    /// `C1TEFUA` in one read, the object itself, and the pointer increment.
+    ///
+    /// If an SPI error occurs after some objects were already popped from
+    /// the TEF, those are returned and the error is left for the next call
+    /// to run into, so no object is lost. An object is only counted once the
+    /// tail pointer was incremented past it.
+    pub async fn tx_event_fifo_drain(&mut self, out: &mut [TxEventObject]) -> Result<usize, Error> {
+        let control_register = self.read_register::<TxEventFifoControlRegister>().await?;
 
-        let control_register = self
-            .read_repeated_register::<FifoControlRegister>(fifo_number)
-            .await?;
+        let mut count = 0;
 
-        if control_register.txen() {
-            return Err(Error::FifoNotRx);
+        for slot in out.iter_mut() {
+            *slot = match self.tx_event_fifo_pop_object(&control_register).await {
+                Ok(Some(obj)) => obj,
+                Ok(None) => break,
+                Err(_) if count > 0 => break,
+                Err(error) => return Err(error),
+            };
+
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Reads the next TEF object, given the already read `C1TEFCON`, and
+    /// increments the tail pointer past it
+    async fn tx_event_fifo_pop_object(
+        &mut self,
+        control_register: &TxEventFifoControlRegister,
+    ) -> Result<Option<TxEventObject>, Error> {
+        // C1TEFSTA and C1TEFUA are adjacent, so the status and the address of
+        // the next object are read in a single transaction
+        let [status, user_address] = self.read_sfr_words(&SFRAddress::C1TEFSTA).await?;
+
+        let status_register = TxEventFifoStatusRegister::from(status);
+
+        if !status_register.tefneif() {
+            return Ok(None);
         }
 
+        let ram_address = UserAddressRegister::from(user_address).calculate_ram_address();
+
+        let mut buf = [0u8; TxEventObject::ram_size(true)];
+        let buf = &mut buf[..TxEventObject::ram_size(control_register.teftsen())];
+
+        self.read_ram(ram_address as u16, buf).await?;
+
+        let mut tefcon = TxEventFifoControlRegister::from(control_register.0);
+        tefcon.set_uinc();
+
+        self.write_register(tefcon).await?;
+
+        Ok(Some(TxEventObject::from_ram_bytes(buf)))
+    }
+
+    /// Checks to see if there are any messages in the given receive FIFO
+    pub async fn rx_fifo_has_next(&mut self, fifo_number: FifoNumber) -> Result<bool, Error> {
+        /* Make sure it's a receive FIFO */
+
+        self.rx_fifo_control(fifo_number).await?;
+
         /* Check is the FIFO has any messages in it */
 
         let status_register = self
@@ -853,30 +2378,22 @@ where
         Ok(status_register.tfnrfnif())
     }
 
-    /// If there is a message available in the given RX FIFO it will be read,
-    /// but the FIFO head pointer will **NOT** be incremented
-    ///
-    /// Unless you have a specific use case for this, you most likely want to
-    /// use [`MCP2518FD::rx_fifo_get_next`]
-    pub async fn rx_fifo_peek_next(
+    /// Gets the payload length of the next message in the given RX FIFO
+    /// without reading its data or removing it from the FIFO. Returns `None` if
+    /// the FIFO is empty.
+    pub async fn rx_fifo_next_len(
         &mut self,
         fifo_number: FifoNumber,
-    ) -> Result<Option<RxMessage>, Error> {
-        /* Make sure there is data to read */
-
+    ) -> Result<Option<usize>, Error> {
         if !self.rx_fifo_has_next(fifo_number).await? {
             return Ok(None);
         }
 
-        /* Get the address of the next object */
-
         let ram_address = self
             .read_repeated_register::<UserAddressRegister>(UserAddressKind::Fifo(fifo_number))
             .await?
             .calculate_ram_address();
 
-        /* Read the message header to see how much data we need to read */
-
         let mut buf = [0u8; 8];
 
         self.read_ram(ram_address as u16, &mut buf).await?;
@@ -886,58 +2403,149 @@ where
             u32::from_le_bytes(buf[4..8].try_into().unwrap()),
         ]);
 
-        /* Read timestamp (if applicable) */
+        Ok(len_for_dlc(header.dlc(), header.fdf()))
+    }
+
+    /// If there is a message available in the given RX FIFO it will be read,
+    /// but the FIFO head pointer will **NOT** be incremented
+    ///
+    /// Unless you have a specific use case for this, you most likely want to
+    /// use [`MCP2518FD::rx_fifo_get_next`]
+    ///
+    /// This takes 3 SPI transactions: the FIFO control register, the status
+    /// and user address registers in one read, and the entire message object
+    /// (header, timestamp, and payload) in one read.
+    ///
+    /// Returns [`Error::FifoNotRx`] if the FIFO is configured for
+    /// transmission, since its objects aren't received messages.
+    pub async fn rx_fifo_peek_next(
+        &mut self,
+        fifo_number: FifoNumber,
+    ) -> Result<Option<RxMessage>, Error> {
+        self.rx_fifo_peek_next_into(fifo_number).await
+    }
+
+    /// Same as [`MCP2518FD::rx_fifo_peek_next`], but reads into an
+    /// [`RxMessage`] with a payload buffer of `N` bytes, e.g. `RxMessage<8>`
+    /// on a classic CAN bus.
+    ///
+    /// If the next message has a longer payload, [`Error::BufferTooSmall`]
+    /// is returned and the message is left in the FIFO. Read it with a larger
+    /// buffer or drop it with [`MCP2518FD::rx_fifo_discard_next`].
+    pub async fn rx_fifo_peek_next_into<const N: usize>(
+        &mut self,
+        fifo_number: FifoNumber,
+    ) -> Result<Option<RxMessage<N>>, Error> {
+        /* Make sure it's a receive FIFO with data to read */
+
+        let control_register = self.rx_fifo_control(fifo_number).await?;
 
+        self.rx_fifo_read_object(fifo_number, &control_register)
+            .await
+    }
+
+    /// Reads the control register of the given FIFO, making sure it's
+    /// configured for receiving
+    async fn rx_fifo_control(
+        &mut self,
+        fifo_number: FifoNumber,
+    ) -> Result<FifoControlRegister, Error> {
         let control_register = self
             .read_repeated_register::<FifoControlRegister>(fifo_number)
             .await?;
 
-        #[cfg(not(feature = "async"))]
-        let timestamp = control_register
-            .rxtsen()
-            .then(|| {
-                let mut ts = [0u8; 4];
-                self.read_ram((ram_address + 4 * 2) as u16, &mut ts)?;
+        if control_register.txen() {
+            return Err(Error::FifoNotRx);
+        }
 
-                Ok(u32::from_le_bytes(ts[..].try_into().unwrap()))
-            })
-            .transpose()?;
+        Ok(control_register)
+    }
 
-        #[cfg(feature = "async")]
-        let timestamp = OptionFuture::from(control_register.rxtsen().then_some(async {
-            let mut ts = [0u8; 4];
-            self.read_ram((ram_address + 4 * 2) as u16, &mut ts).await?;
+    /// Gets the RAM address of the next object in an RX FIFO, or `None` if the
+    /// FIFO is empty
+    async fn rx_fifo_next_address(
+        &mut self,
+        fifo_number: FifoNumber,
+    ) -> Result<Option<u16>, Error> {
+        // C1FIFOSTAm and C1FIFOUAm are adjacent, so the status and the address
+        // of the next object are read in a single transaction
+        let [status, user_address] = self
+            .read_sfr_words(&FifoStatusRegister::get_address_for(fifo_number))
+            .await?;
 
-            Ok(u32::from_le_bytes(ts[..].try_into().unwrap()))
-        }))
-        .await
-        .transpose()?;
-
-        /* Read the content of the message */
-
-        let mut data = [0u8; MAX_FD_BUFFER_SIZE];
-        let data_len = len_for_dlc(header.dlc(), header.fdf()).unwrap();
-        let data_offset = if timestamp.is_some() { 3 } else { 2 };
-
-        // We need to make sure that the data we are reading from ram has a
-        // length which is a multiple of 4. By adding (4 - length % 4), we
-        // extend the length to the next multiple 4 boundary. This isn't always
-        // a good solution but in this specific case it works because we know
-        // that the there is definitely at least that much ram allocated for the
-        // RX message (we only do this when the DLC is < 8 and the minimum
-        // number of bytes allocated for a RX message is 8)
-        let read_len = data_len + (4 - data_len % 4);
-
-        self.read_ram(
-            (ram_address + 4 * data_offset) as u16,
-            &mut data[..read_len],
-        )
-        .await?;
+        if !FifoStatusRegister::from(status).tfnrfnif() {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            UserAddressRegister::from(user_address).calculate_ram_address() as u16,
+        ))
+    }
+
+    /// Increments the head pointer of an RX FIFO whose control register has
+    /// already been read, past the object that was just read
+    async fn rx_fifo_increment_head(
+        &mut self,
+        fifo_number: FifoNumber,
+        control_register: &FifoControlRegister,
+    ) -> Result<(), Error> {
+        let mut fifocon = FifoControlRegister(control_register.0);
+        fifocon.set_uinc();
+
+        self.write_repeated_register(fifo_number, fifocon).await?;
+
+        self.stats.frames_received = self.stats.frames_received.wrapping_add(1);
+
+        Ok(())
+    }
+
+    /// Reads the next message object of an RX FIFO whose control register
+    /// has already been read, without incrementing the head pointer. Returns
+    /// `None` if the FIFO is empty.
+    async fn rx_fifo_read_object<const N: usize>(
+        &mut self,
+        fifo_number: FifoNumber,
+        control_register: &FifoControlRegister,
+    ) -> Result<Option<RxMessage<N>>, Error> {
+        let Some(ram_address) = self.rx_fifo_next_address(fifo_number).await? else {
+            return Ok(None);
+        };
+
+        /* Read the whole object in a single transaction */
+
+        // The size of every object in the FIFO is fixed by its configuration,
+        // so the header, timestamp (if applicable), and data can all be read
+        // at once without looking at the DLC first. Object sizes are always a
+        // multiple of 4 bytes.
+        let header_len = HEADER_SIZE_DWORDS * 4;
+        let data_offset = rx_data_offset(control_register);
+        let object_len = data_offset + control_register.payload_size().num_bytes();
+
+        let mut buf = [0u8; HEADER_SIZE_DWORDS * 4 + 4 + MAX_FD_BUFFER_SIZE];
+
+        self.read_ram(ram_address, &mut buf[..object_len]).await?;
+
+        let header = RxHeader([
+            u32::from_le_bytes(buf[0..4].try_into().unwrap()),
+            u32::from_le_bytes(buf[4..8].try_into().unwrap()),
+        ]);
+
+        let timestamp = control_register
+            .rxtsen()
+            .then(|| u32::from_le_bytes(buf[header_len..data_offset].try_into().unwrap()));
 
         /* Assemble RxMessage */
 
-        Ok(Some(
-            RxMessage::new(header, timestamp, &data[..data_len]).unwrap(),
+        let data_len = rx_data_len(&header, control_register);
+
+        if data_len > N {
+            return Err(Error::BufferTooSmall(data_len));
+        }
+
+        Ok(RxMessage::new(
+            header,
+            timestamp,
+            &buf[data_offset..data_offset + data_len],
         ))
     }
 
@@ -948,39 +2556,364 @@ where
     /// To only check if a message is available without pulling it from the
     /// FIFO, see [`MCP2518FD::rx_fifo_has_next`] and
     /// [`MCP2518FD::rx_fifo_peek_next`]
+    ///
+    /// This takes 4 SPI transactions: the 3 of
+    /// [`MCP2518FD::rx_fifo_peek_next`] and the control register write to
+    /// increment the head pointer.
+    ///
+    /// Returns [`Error::FifoNotRx`] if the FIFO is configured for
+    /// transmission, without touching its head pointer.
     pub async fn rx_fifo_get_next(
         &mut self,
         fifo_number: FifoNumber,
     ) -> Result<Option<RxMessage>, Error> {
-        let msg = self.rx_fifo_peek_next(fifo_number).await?;
+        self.rx_fifo_get_next_into(fifo_number).await
+    }
+
+    /// Same as [`MCP2518FD::rx_fifo_get_next`], but reads into an
+    /// [`RxMessage`] with a payload buffer of `N` bytes, e.g. `RxMessage<8>`
+    /// on a classic CAN bus.
+    ///
+    /// If the next message has a longer payload, [`Error::BufferTooSmall`]
+    /// is returned and the head pointer is **NOT** incremented, so the
+    /// message stays at the head of the FIFO. Read it with a larger buffer or
+    /// drop it with [`MCP2518FD::rx_fifo_discard_next`], otherwise the FIFO
+    /// eventually fills up.
+    pub async fn rx_fifo_get_next_into<const N: usize>(
+        &mut self,
+        fifo_number: FifoNumber,
+    ) -> Result<Option<RxMessage<N>>, Error> {
+        let control_register = self.rx_fifo_control(fifo_number).await?;
+
+        self.rx_fifo_pop_object(fifo_number, &control_register)
+            .await
+    }
+
+    /// If there is a message available in the given RX FIFO, its payload is
+    /// read straight into `buf` and the FIFO head pointer is incremented.
+    /// Returns the header and the payload length, so `&buf[..len]` is the
+    /// data. Unlike [`MCP2518FD::rx_fifo_get_next`] no [`RxMessage`] is built,
+    /// so `buf` only needs to be as large as the biggest expected payload.
+    /// The timestamp, if enabled for the FIFO, is skipped.
+    ///
+    /// If the payload doesn't fit in `buf`, [`Error::BufferTooSmall`] is
+    /// returned and the message is left in the FIFO. Read it with a larger
+    /// buffer or drop it with [`MCP2518FD::rx_fifo_discard_next`].
+    ///
+    /// RAM is read in whole DWORDs, so a payload that isn't a multiple of 4
+    /// bytes takes one extra SPI transaction when `buf` has no room for the
+    /// padding.
+    pub async fn rx_fifo_read_into(
+        &mut self,
+        fifo_number: FifoNumber,
+        buf: &mut [u8],
+    ) -> Result<Option<(RxMessageHeader, usize)>, Error> {
+        let control_register = self.rx_fifo_control(fifo_number).await?;
 
-        let Some(msg) = msg else {
+        let Some(ram_address) = self.rx_fifo_next_address(fifo_number).await? else {
             return Ok(None);
         };
 
-        self.modify_repeated_register(fifo_number, |mut tefcon: FifoControlRegister| {
-            tefcon.set_uinc();
-            tefcon
+        /* Read the header to find out how long the payload is */
+
+        let mut header_buf = [0u8; HEADER_SIZE_DWORDS * 4];
+
+        self.read_ram(ram_address, &mut header_buf).await?;
+
+        let header = RxHeader([
+            u32::from_le_bytes(header_buf[0..4].try_into().unwrap()),
+            u32::from_le_bytes(header_buf[4..8].try_into().unwrap()),
+        ]);
+
+        let data_offset = rx_data_offset(&control_register);
+        let data_len = rx_data_len(&header, &control_register);
+
+        if data_len > buf.len() {
+            return Err(Error::BufferTooSmall(data_len));
+        }
+
+        /* Read the payload straight into the caller's buffer */
+
+        let data_address = ram_address + data_offset as u16;
+        let padded_len = data_len.next_multiple_of(4);
+
+        if padded_len <= buf.len() {
+            if padded_len > 0 {
+                self.read_ram(data_address, &mut buf[..padded_len]).await?;
+            }
+        } else {
+            let aligned_len = data_len & !0b11;
+
+            if aligned_len > 0 {
+                self.read_ram(data_address, &mut buf[..aligned_len]).await?;
+            }
+
+            let mut tail = [0u8; 4];
+
+            self.read_ram(data_address + aligned_len as u16, &mut tail)
+                .await?;
+
+            buf[aligned_len..data_len].copy_from_slice(&tail[..data_len - aligned_len]);
+        }
+
+        self.rx_fifo_increment_head(fifo_number, &control_register)
+            .await?;
+
+        Ok(Some((header, data_len)))
+    }
+
+    /// Drops the next message of the given RX FIFO without reading it by
+    /// incrementing the head pointer. Returns `false` if the FIFO was already
+    /// empty. This is the way out when a message doesn't fit the buffer
+    /// passed to [`MCP2518FD::rx_fifo_get_next_into`] or
+    /// [`MCP2518FD::rx_fifo_read_into`].
+    pub async fn rx_fifo_discard_next(&mut self, fifo_number: FifoNumber) -> Result<bool, Error> {
+        if !self.rx_fifo_has_next(fifo_number).await? {
+            return Ok(false);
+        }
+
+        self.modify_repeated_register(fifo_number, |mut fifocon: FifoControlRegister| {
+            fifocon.set_uinc();
+            fifocon
         })
         .await?;
 
+        Ok(true)
+    }
+
+    /// Reads messages from the given RX FIFO into `out` until either the FIFO
+    /// is empty or `out` is full, incrementing the FIFO head pointer after
+    /// each one. Returns how many messages were written to the start of
+    /// `out`, which is less than `out.len()` if the FIFO ran empty first.
+    ///
+    /// The FIFO control register is only read once, so each message takes 3
+    /// SPI transactions instead of the 4 of [`MCP2518FD::rx_fifo_get_next`],
+    /// plus one final status read to find out the FIFO is empty.
+    ///
+    /// If an SPI error occurs after some messages were already popped from
+    /// the FIFO, those are returned and the error is left for the next call
+    /// to run into, so no message is lost. A message is only counted once
+    /// the head pointer was incremented past it.
+    pub async fn rx_fifo_drain<const N: usize>(
+        &mut self,
+        fifo_number: FifoNumber,
+        out: &mut [RxMessage<N>],
+    ) -> Result<usize, Error> {
+        let control_register = self.rx_fifo_control(fifo_number).await?;
+
+        let mut count = 0;
+
+        for slot in out.iter_mut() {
+            *slot = match self
+                .rx_fifo_pop_object(fifo_number, &control_register)
+                .await
+            {
+                Ok(Some(msg)) => msg,
+                Ok(None) => break,
+                Err(_) if count > 0 => break,
+                Err(error) => return Err(error),
+            };
+
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Reads the next message object of an RX FIFO whose control register
+    /// has already been read and increments the head pointer past it
+    async fn rx_fifo_pop_object<const N: usize>(
+        &mut self,
+        fifo_number: FifoNumber,
+        control_register: &FifoControlRegister,
+    ) -> Result<Option<RxMessage<N>>, Error> {
+        let Some(msg) = self
+            .rx_fifo_read_object(fifo_number, control_register)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        self.rx_fifo_increment_head(fifo_number, control_register)
+            .await?;
+
         Ok(Some(msg))
     }
 
+    /// Masks the not empty interrupt of the given RX FIFO so that the nINT pin
+    /// is released even though messages are still pending.
+    ///
+    /// The RX FIFO interrupts are level-triggered, so they keep firing until
+    /// the FIFO is fully drained. Call this method when the interrupt first
+    /// fires to get edge-like behavior, drain the FIFO at your leisure, and
+    /// then call [`MCP2518FD::rx_fifo_release_interrupt`] to unmask it again.
+    pub async fn rx_fifo_latch_interrupt(&mut self, fifo_number: FifoNumber) -> Result<(), Error> {
+        self.modify_repeated_register(fifo_number, |mut fifocon: FifoControlRegister| {
+            fifocon.set_tfnrfnie(false);
+            fifocon
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Unmasks the not empty interrupt of the given RX FIFO, but only if the
+    /// FIFO has been fully drained. Returns `true` if the interrupt was
+    /// re-enabled, or `false` if there are still messages left to read (in
+    /// which case the interrupt stays masked).
+    ///
+    /// See [`MCP2518FD::rx_fifo_latch_interrupt`] for more information.
+    pub async fn rx_fifo_release_interrupt(
+        &mut self,
+        fifo_number: FifoNumber,
+    ) -> Result<bool, Error> {
+        if self.rx_fifo_has_next(fifo_number).await? {
+            return Ok(false);
+        }
+
+        self.modify_repeated_register(fifo_number, |mut fifocon: FifoControlRegister| {
+            fifocon.set_tfnrfnie(true);
+            fifocon
+        })
+        .await?;
+
+        Ok(true)
+    }
+
     /* Interrupt related operations */
 
     pub async fn get_highest_interrupt_codes(&mut self) -> Result<InterruptCodeRegister, Error> {
         self.read_register::<InterruptCodeRegister>().await
     }
 
+    /// Reads `C1VEC` and decodes the highest priority pending interrupt, which
+    /// makes for a single entry point in an interrupt handler. Returns `None`
+    /// if no interrupt is pending. Use
+    /// [`MCP2518FD::get_highest_interrupt_codes`] for the raw codes.
+    pub async fn poll_interrupt(&mut self) -> Result<Option<CanEvent>, Error> {
+        Ok(self.get_highest_interrupt_codes().await?.event())
+    }
+
     pub async fn get_top_level_interrupt_statuses(&mut self) -> Result<InterruptRegister, Error> {
         self.read_register::<InterruptRegister>().await
     }
 
+    /// Reads `C1INT` to check which top level interrupts are enabled, see
+    /// [`InterruptRegister::is_enabled`]
+    pub async fn get_interrupt_enables(&mut self) -> Result<InterruptRegister, Error> {
+        self.read_register::<InterruptRegister>().await
+    }
+
+    /// Enables or disables a single top level interrupt at runtime, without
+    /// touching the other enables or clearing any interrupt flags
+    pub async fn set_interrupt_enabled(
+        &mut self,
+        kind: InterruptKind,
+        enabled: bool,
+    ) -> Result<(), Error> {
+        self.modify_register(|c1int: InterruptRegister| {
+            // Writing 1 to the flags leaves them untouched
+            let mut c1int = InterruptRegister(c1int.0 | 0xFFFF);
+            c1int.set_enabled(kind, enabled);
+            c1int
+        })
+        .await
+    }
+
+    /// Disables all top level interrupts in a single write, leaving the
+    /// interrupt flags untouched. Re-enable them individually with
+    /// [`MCP2518FD::set_interrupt_enabled`].
+    pub async fn mask_all_interrupts(&mut self) -> Result<(), Error> {
+        self.write_register(InterruptRegister(0xFFFF)).await
+    }
+
+    /// Checks whether bus activity woke the chip up from Sleep mode
+    /// (`C1INT.WAKIF`)
+    pub async fn wake_interrupt_pending(&mut self) -> Result<bool, Error> {
+        Ok(self.read_register::<InterruptRegister>().await?.wakif())
+    }
+
+    /// Clears the wake up interrupt flag, see
+    /// [`MCP2518FD::wake_interrupt_pending`]
+    pub async fn clear_wake_interrupt(&mut self) -> Result<(), Error> {
+        self.clear_system_interrupts(SystemInterrupts::WAKE_UP)
+            .await
+    }
+
+    /// Clears the given system interrupt flags in `C1INT` with a single
+    /// read-modify-write. The interrupt enables and any flag not in `flags`
+    /// are left untouched, even if it was raised after the read.
+    ///
+    /// To acknowledge everything that was pending when `C1INT` was read, pass
+    /// [`InterruptRegister::pending_system_interrupts`].
+    #[doc(alias = "clear_interrupt_flags")]
+    pub async fn clear_system_interrupts(&mut self, flags: SystemInterrupts) -> Result<(), Error> {
+        self.modify_register(|c1int: InterruptRegister| {
+            // The flags are cleared by writing 0, writing 1 has no effect
+            InterruptRegister((c1int.0 | 0xFFFF) & !(flags.bits() as u32))
+        })
+        .await
+    }
+
+    /// Checks for a pending invalid message interrupt (`C1INT.IVMIF`) and, if
+    /// set, clears it and increments the invalid message count. Returns whether
+    /// the interrupt was pending.
+    ///
+    /// The chip has no counter of its own, so calling this whenever the
+    /// interrupt fires gives a cheap network health metric through
+    /// [`MCP2518FD::invalid_message_count`].
+    pub async fn service_invalid_message_interrupt(&mut self) -> Result<bool, Error> {
+        let c1int = self.read_register::<InterruptRegister>().await?;
+
+        if !c1int.ivmif() {
+            return Ok(false);
+        }
+
+        // Writing 1 to the other flags leaves them untouched, so none that
+        // were raised since the read get cleared by accident
+        let mut c1int = InterruptRegister(c1int.0 | 0xFFFF);
+        c1int.clear_ivmif();
+
+        self.write_register(c1int).await?;
+
+        self.invalid_message_count = self.invalid_message_count.wrapping_add(1);
+
+        Ok(true)
+    }
+
+    /// Number of invalid message interrupts serviced through
+    /// [`MCP2518FD::service_invalid_message_interrupt`] since the driver was
+    /// created or the count was last reset
+    pub fn invalid_message_count(&self) -> u32 {
+        self.invalid_message_count
+    }
+
+    pub fn reset_invalid_message_count(&mut self) {
+        self.invalid_message_count = 0;
+    }
+
+    /// Reads `C1INT`, `C1RXIF`, `C1TXIF`, `C1RXOVIF`, and `C1TXATIF` in a
+    /// single SPI transaction, since they are adjacent. Prefer this over the
+    /// individual getters in an interrupt handler that needs several of them.
+    pub async fn read_all_interrupt_statuses(&mut self) -> Result<InterruptStatuses, Error> {
+        let words = self.read_sfr_words(&SFRAddress::C1INT).await?;
+
+        Ok(InterruptStatuses::from_words(words))
+    }
+
     pub async fn get_rx_interrupt_statuses(&mut self) -> Result<RxInterruptStatusRegister, Error> {
         self.read_register::<RxInterruptStatusRegister>().await
     }
 
+    /// Reads `C1RXIF` to find every RX FIFO with a pending interrupt, so an
+    /// interrupt handler can service all of them with
+    /// `for fifo_number in can.pending_rx_fifos()?.iter()`
+    pub async fn pending_rx_fifos(&mut self) -> Result<FifoSet, Error> {
+        let rxif = self.get_rx_interrupt_statuses().await?;
+
+        Ok(FifoSet::from_bits(rxif.into()))
+    }
+
     pub async fn get_rx_overflow_interrupt_statuses(
         &mut self,
     ) -> Result<RxOverflowInterruptStatusRegister, Error> {
@@ -988,10 +2921,51 @@ where
             .await
     }
 
+    /// Reads `C1RXOVIF` to find every RX FIFO that has overflowed (dropped at
+    /// least one message) since its overflow flag was last cleared. See
+    /// [`MCP2518FD::clear_rx_overflow`].
+    pub async fn overflowed_rx_fifos(&mut self) -> Result<FifoSet, Error> {
+        let rxovif = self.get_rx_overflow_interrupt_statuses().await?;
+
+        Ok(FifoSet::from_bits(rxovif.into()))
+    }
+
+    /// Clears the overflow flag (`RXOVIF`) of the given RX FIFO. Only that flag
+    /// is cleared, the rest of the FIFO's status register is left untouched.
+    ///
+    /// Drain the FIFO first, since the messages that didn't fit were dropped
+    /// and the flag would be raised again by the next message arriving at a
+    /// FIFO that is still full.
+    ///
+    /// If the flag was set, the overflow is counted in [`DriverStats`].
+    pub async fn clear_rx_overflow(&mut self, fifo_number: FifoNumber) -> Result<(), Error> {
+        let mut fifosta = self
+            .read_repeated_register::<FifoStatusRegister>(fifo_number)
+            .await?;
+
+        if fifosta.rxovif() {
+            self.stats.rx_overflows = self.stats.rx_overflows.wrapping_add(1);
+        }
+
+        fifosta.clear_rxovif();
+
+        self.write_repeated_register(fifo_number, fifosta).await
+    }
+
     pub async fn get_tx_interrupt_statuses(&mut self) -> Result<TxInterruptStatusRegister, Error> {
         self.read_register::<TxInterruptStatusRegister>().await
     }
 
+    /// Reads `C1TXIF` to find every TX FIFO with a pending interrupt, see
+    /// [`MCP2518FD::pending_rx_fifos`]. The TXQ isn't a FIFO and so is not
+    /// part of the set, check `tfif_txq` of
+    /// [`MCP2518FD::get_tx_interrupt_statuses`] for it.
+    pub async fn pending_tx_fifos(&mut self) -> Result<FifoSet, Error> {
+        let txif = self.get_tx_interrupt_statuses().await?;
+
+        Ok(FifoSet::from_bits(txif.into()))
+    }
+
     pub async fn get_tx_attempt_interrupt_statuses(
         &mut self,
     ) -> Result<TxAttemptInterruptStatusRegister, Error> {
@@ -999,9 +2973,117 @@ where
             .await
     }
 
-    /* Generic register ops with mapping */
-
-    pub async fn modify_repeated_register<R, F>(
+    /// Clears the attempts exhausted flag (`TXATIF`) of the given TX FIFO, or
+    /// of the TXQ if `fifo_number` is `None`. Only that flag is cleared, the
+    /// rest of the status register is left untouched.
+    ///
+    /// If the flag was set, the failed transmission is counted in
+    /// [`DriverStats`].
+    pub async fn clear_tx_attempts_exhausted(
+        &mut self,
+        fifo_number: Option<FifoNumber>,
+    ) -> Result<(), Error> {
+        let was_set = match fifo_number {
+            Some(fifo_number) => {
+                let mut fifosta = self
+                    .read_repeated_register::<FifoStatusRegister>(fifo_number)
+                    .await?;
+                let was_set = fifosta.txatif();
+
+                fifosta.clear_txatif();
+
+                self.write_repeated_register(fifo_number, fifosta).await?;
+
+                was_set
+            }
+            None => {
+                let mut txqsta = self.read_register::<TxQueueStatusRegister>().await?;
+                let was_set = txqsta.txatif();
+
+                txqsta.clear_txatif();
+
+                self.write_register(txqsta).await?;
+
+                was_set
+            }
+        };
+
+        if was_set {
+            self.stats.tx_attempt_failures = self.stats.tx_attempt_failures.wrapping_add(1);
+        }
+
+        Ok(())
+    }
+
+    /* Diagnostics */
+
+    /// Reads the transmit and receive error counters together with the fault
+    /// confinement flags. See
+    /// [`TransmitReceiveErrorCountRegister::error_state`].
+    pub async fn get_error_counts(&mut self) -> Result<TransmitReceiveErrorCountRegister, Error> {
+        self.read_register::<TransmitReceiveErrorCountRegister>()
+            .await
+    }
+
+    /// Checks whether the controller went bus off after too many transmit
+    /// errors
+    pub async fn is_bus_off(&mut self) -> Result<bool, Error> {
+        Ok(self.get_error_counts().await?.txbo())
+    }
+
+    /// Reads the bus diagnostic registers, which count errors separately for
+    /// the nominal (arbitration) and data phase and keep track of the kind of
+    /// errors that occurred
+    pub async fn get_bus_diagnostics(
+        &mut self,
+    ) -> Result<(BusDiagnosticRegister0, BusDiagnosticRegister1), Error> {
+        let bdiag0 = self.read_register::<BusDiagnosticRegister0>().await?;
+        let bdiag1 = self.read_register::<BusDiagnosticRegister1>().await?;
+
+        Ok((bdiag0, bdiag1))
+    }
+
+    /// Resets all bus diagnostic counters and flags. Both registers are
+    /// cleared in a single SPI transaction.
+    pub async fn clear_bus_diagnostics(&mut self) -> Result<(), Error> {
+        self.write_sfr_block(&SFRAddress::C1BDIAG0, &[0u8; 8]).await
+    }
+
+    /// Frame counters kept by the driver since it was created or the counters
+    /// were last reset, see [`DriverStats`]
+    pub fn stats(&self) -> DriverStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = DriverStats::default();
+    }
+
+    /// Reads the CRC the chip computed for the last CRC-protected SPI
+    /// transaction that failed its check. Comparing it against the CRC sent by
+    /// the host helps pinpoint whether a mismatch happened in the command or
+    /// the data phase.
+    pub async fn read_last_crc(&mut self) -> Result<u16, Error> {
+        let crc = self.read_register::<CrcRegister>().await?;
+
+        Ok(crc.crc())
+    }
+
+    /// Reads every SFR into a [`RegisterSnapshot`], e.g. to log the complete
+    /// chip state when something misbehaves. The controller and the chip
+    /// specific registers are each read in a single burst, so this only
+    /// takes 2 SPI transactions (4 with the `spi-crc` feature, which limits
+    /// the length of a burst).
+    pub async fn dump_registers(&mut self) -> Result<RegisterSnapshot, Error> {
+        Ok(RegisterSnapshot {
+            controller: self.read_sfr_words(&SFRAddress::C1CON).await?,
+            chip: self.read_sfr_words(&SFRAddress::OSC).await?,
+        })
+    }
+
+    /* Generic register ops with mapping */
+
+    pub async fn modify_repeated_register<R, F>(
         &mut self,
         index: R::Index,
         transform: F,
@@ -1034,8 +3116,15 @@ where
         R: RepeatedRegister + Into<u32>,
     {
         let address = R::get_address_for(index);
+        let value = value.into();
+
+        self.write_sfr(&address, value).await?;
 
-        self.write_sfr(&address, value.into()).await
+        if self.verify_writes {
+            self.verify_sfr(&address, value, R::VOLATILE_BITS).await?;
+        }
+
+        Ok(())
     }
 
     pub async fn modify_register<R, F>(&mut self, transform: F) -> Result<(), Error>
@@ -1062,46 +3151,146 @@ where
         R: Register + Into<u32>,
     {
         let address = R::get_address();
+        let value = value.into();
+
+        self.write_sfr(&address, value).await?;
 
-        self.write_sfr(&address, value.into()).await
+        if self.verify_writes {
+            self.verify_sfr(&address, value, R::VOLATILE_BITS).await?;
+        }
+
+        Ok(())
     }
 
     /* Raw SFR Ops (Minimal type checking) */
 
     async fn read_sfr(&mut self, address: &SFRAddress) -> Result<u32, Error> {
-        let mut instruction = Instruction(OpCode::READ);
-        instruction.set_address(*address as u16);
-
         let mut buf = [0u8; 4];
 
-        self.spi
-            .transaction(&mut [
-                Operation::Write(&instruction.into_spi_data()),
-                Operation::Read(&mut buf),
-            ])
-            .await
-            .map_err(|_| Error::SPIRead)?;
+        self.read_bytes(*address as u16, &mut buf, false).await?;
 
         Ok(u32::from_le_bytes(buf))
     }
 
     async fn write_sfr(&mut self, address: &SFRAddress, value: u32) -> Result<(), Error> {
-        let mut instruction = Instruction(OpCode::WRITE);
-        instruction.set_address(*address as u16);
-
-        self.spi
-            .transaction(&mut [
-                Operation::Write(&instruction.into_spi_data()),
-                // The "instruction" needs to be converted to BE bytes but the actual SFR register
-                // needs to be in LE format!!!
-                Operation::Write(&value.to_le_bytes()),
-            ])
+        // The "instruction" needs to be converted to BE bytes but the actual SFR register
+        // needs to be in LE format!!!
+        self.write_bytes(*address as u16, &value.to_le_bytes(), false)
             .await
-            .map_err(|_| Error::SPIRead)?;
+    }
+
+    /// Reads any SFR by address, bypassing the typed register API. This is an
+    /// escape hatch for diagnostics and for registers the driver doesn't
+    /// cover yet, prefer [`MCP2518FD::read_register`] wherever possible.
+    pub async fn read_sfr_raw(&mut self, address: SFRAddress) -> Result<u32, Error> {
+        self.read_sfr(&address).await
+    }
+
+    /// Writes any SFR by address, bypassing the typed register API and write
+    /// verification. This is an escape hatch for diagnostics and for
+    /// registers the driver doesn't cover yet. Nothing stops this from
+    /// changing settings the rest of the driver relies on, so prefer
+    /// [`MCP2518FD::write_register`] wherever possible.
+    pub async fn write_sfr_raw(&mut self, address: SFRAddress, value: u32) -> Result<(), Error> {
+        self.write_sfr(&address, value).await
+    }
+
+    /// Reads an SFR back and compares it against the value that was written,
+    /// ignoring any bits in `volatile_bits`
+    async fn verify_sfr(
+        &mut self,
+        address: &SFRAddress,
+        wrote: u32,
+        volatile_bits: u32,
+    ) -> Result<(), Error> {
+        let read = self.read_sfr(address).await?;
+
+        check_readback(*address, wrote, read, volatile_bits)
+    }
+
+    /// Writes a contiguous block of SFRs in a single SPI transaction, starting
+    /// at the given address
+    async fn write_sfr_block(&mut self, address: &SFRAddress, data: &[u8]) -> Result<(), Error> {
+        self.write_bytes(*address as u16, data, false).await
+    }
+
+    /// Reads a contiguous block of SFRs in a single SPI transaction, starting
+    /// at the given address
+    async fn read_sfr_block(&mut self, address: &SFRAddress, data: &mut [u8]) -> Result<(), Error> {
+        self.read_bytes(*address as u16, data, false).await
+    }
+
+    /// Reads `N` adjacent SFRs in a single SPI transaction, starting at the
+    /// given address
+    async fn read_sfr_words<const N: usize>(
+        &mut self,
+        address: &SFRAddress,
+    ) -> Result<[u32; N], Error> {
+        let mut block = [[0u8; 4]; N];
+
+        self.read_sfr_block(address, block.as_flattened_mut())
+            .await?;
+
+        Ok(block.map(u32::from_le_bytes))
+    }
+
+    /// Writes `N` adjacent SFRs in a single SPI transaction, starting at the
+    /// given address. With `Settings::verify_writes` they are read back in a
+    /// second transaction and compared, ignoring the matching `volatile_bits`.
+    async fn write_sfr_words<const N: usize>(
+        &mut self,
+        address: &SFRAddress,
+        words: [u32; N],
+        volatile_bits: [u32; N],
+    ) -> Result<(), Error> {
+        self.write_sfr_block(address, words.map(u32::to_le_bytes).as_flattened())
+            .await?;
+
+        if self.verify_writes {
+            let read_back = self.read_sfr_words::<N>(address).await?;
+
+            for (i, (wrote, read)) in words.into_iter().zip(read_back).enumerate() {
+                // Only reserved words are missing from SFRAddress
+                let register =
+                    SFRAddress::try_from(*address as u16 + i as u16 * 4).unwrap_or(*address);
+
+                check_readback(register, wrote, read, volatile_bits[i])?;
+            }
+        }
 
         Ok(())
     }
 
+    /// Reads the device ID register, which holds the chip's ID and silicon
+    /// revision
+    pub async fn read_device_id(&mut self) -> Result<DeviceIdRegister, Error> {
+        self.read_register::<DeviceIdRegister>().await
+    }
+
+    /// Reads the device ID register as a first sanity check of the SPI
+    /// connection. Only the lowest byte of `DEVID` is implemented, so anything
+    /// in the upper bytes means the data got garbled or MISO is floating high.
+    ///
+    /// A register that reads as all zeros is accepted, since that's what an
+    /// MCP2517FD reads. A MISO line that is stuck low is caught by
+    /// [`MCP2518FD::verify_spi_communications`] instead.
+    ///
+    /// Returns the register so the caller can check which chip it is talking
+    /// to without reading it again.
+    pub async fn verify_device_id(&mut self) -> Result<DeviceIdRegister, ConfigError> {
+        let devid = self
+            .read_device_id()
+            .await
+            .map_err(|_| ConfigError::SpiCommunicationFailed)?;
+        let raw = devid.0;
+
+        if raw >> 8 != 0 {
+            return Err(ConfigError::SpiCommunicationFailed);
+        }
+
+        Ok(devid)
+    }
+
     /* RAM related functions */
 
     /// Verify SPI connection is working by writing to an available ram location.
@@ -1121,6 +3310,7 @@ where
         Ok(())
     }
 
+    #[allow(clippy::needless_range_loop, clippy::identity_op)]
     pub async fn verify_spi_communications_long(&mut self) -> Result<(), ConfigError> {
         let address = 0x400;
 
@@ -1161,6 +3351,50 @@ where
             return Err(Error::InvalidReadLength(data.len()));
         }
 
+        self.read_bytes(address, data, true).await
+    }
+
+    /// Writes to a contiguous range in RAM from the provided buffer
+    pub async fn write_ram(&mut self, address: u16, data: &[u8]) -> Result<(), Error> {
+        is_valid_ram_address(address as u32, data.len())
+            .then_some(())
+            .ok_or(Error::InvalidRamAddress(address))?;
+
+        if data.len() % 4 != 0 {
+            return Err(Error::InvalidWriteLength(data.len()));
+        }
+
+        self.write_bytes(address, data, true).await
+    }
+
+    /// Writes to a contiguous range in RAM and then reads it back to make sure
+    /// the chip stored exactly what was sent. Mostly useful during development
+    /// to rule out RAM write issues when debugging corrupted messages.
+    pub async fn write_ram_verified(&mut self, address: u16, data: &[u8]) -> Result<(), Error> {
+        self.write_ram(address, data).await?;
+
+        let mut buf = [0u8; MAX_FD_BUFFER_SIZE];
+
+        for (i, chunk) in data.chunks(MAX_FD_BUFFER_SIZE).enumerate() {
+            let chunk_address = address + (i * MAX_FD_BUFFER_SIZE) as u16;
+            let read_back = &mut buf[..chunk.len()];
+
+            self.read_ram(chunk_address, read_back).await?;
+
+            if let Some(offset) = chunk.iter().zip(read_back.iter()).position(|(a, b)| a != b) {
+                return Err(Error::RamVerifyMismatch(chunk_address + offset as u16));
+            }
+        }
+
+        Ok(())
+    }
+
+    /* SPI transfers */
+
+    /// Reads `data.len()` bytes starting at `address` with a plain READ
+    /// instruction. `ram` is only relevant with the `spi-crc` feature.
+    #[cfg(not(feature = "spi-crc"))]
+    async fn read_bytes(&mut self, address: u16, data: &mut [u8], _ram: bool) -> Result<(), Error> {
         let mut instruction = Instruction(OpCode::READ);
         instruction.set_address(address);
 
@@ -1175,16 +3409,10 @@ where
         Ok(())
     }
 
-    /// Writes to a contiguous range in RAM from the provided buffer
-    pub async fn write_ram(&mut self, address: u16, data: &[u8]) -> Result<(), Error> {
-        is_valid_ram_address(address as u32, data.len())
-            .then_some(())
-            .ok_or(Error::InvalidRamAddress(address))?;
-
-        if data.len() % 4 != 0 {
-            return Err(Error::InvalidWriteLength(data.len()));
-        }
-
+    /// Writes `data` starting at `address` with a plain WRITE instruction.
+    /// `ram` is only relevant with the `spi-crc` feature.
+    #[cfg(not(feature = "spi-crc"))]
+    async fn write_bytes(&mut self, address: u16, data: &[u8], _ram: bool) -> Result<(), Error> {
         let mut instruction = Instruction(OpCode::WRITE);
         instruction.set_address(address);
 
@@ -1198,6 +3426,253 @@ where
 
         Ok(())
     }
+
+    /// Reads `data.len()` bytes starting at `address` with READ_CRC
+    /// instructions, checking the CRC the chip appends to every transfer.
+    /// `ram` selects whether the length is sent in words (RAM) or bytes
+    /// (SFRs).
+    #[cfg(feature = "spi-crc")]
+    async fn read_bytes(&mut self, address: u16, data: &mut [u8], ram: bool) -> Result<(), Error> {
+        for (i, chunk) in data.chunks_mut(CRC_MAX_CHUNK_LEN).enumerate() {
+            let address = address + (i * CRC_MAX_CHUNK_LEN) as u16;
+            let header = crc_instruction_header(OpCode::READ_CRC, address, chunk.len(), ram);
+            let mut crc = [0u8; 2];
+
+            self.spi
+                .transaction(&mut [
+                    Operation::Write(&header),
+                    Operation::Read(chunk),
+                    Operation::Read(&mut crc),
+                ])
+                .await
+                .map_err(|_| Error::SPIRead)?;
+
+            if u16::from_be_bytes(crc) != crc16(crc16(CRC_INITIAL_VALUE, &header), chunk) {
+                return Err(Error::CrcMismatch(address));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes `data` starting at `address` with WRITE_CRC instructions. The
+    /// chip still executes a write whose CRC doesn't match, but flags it in
+    /// `CRC.CRCERRIF`, see [`MCP2518FD::read_last_crc`]. `ram` selects
+    /// whether the length is sent in words (RAM) or bytes (SFRs).
+    #[cfg(feature = "spi-crc")]
+    async fn write_bytes(&mut self, address: u16, data: &[u8], ram: bool) -> Result<(), Error> {
+        for (i, chunk) in data.chunks(CRC_MAX_CHUNK_LEN).enumerate() {
+            let address = address + (i * CRC_MAX_CHUNK_LEN) as u16;
+            let header = crc_instruction_header(OpCode::WRITE_CRC, address, chunk.len(), ram);
+            let crc = crc16(crc16(CRC_INITIAL_VALUE, &header), chunk).to_be_bytes();
+
+            self.spi
+                .transaction(&mut [
+                    Operation::Write(&header),
+                    Operation::Write(chunk),
+                    Operation::Write(&crc),
+                ])
+                .await
+                .map_err(|_| Error::SPIWrite)?;
+        }
+
+        Ok(())
+    }
+}
+
+/* embedded-can implementation */
+
+/// Blocking adapter so the driver can be used by generic CAN stacks. Frames are
+/// transmitted and received through the FIFOs selected with
+/// [`MCP2518FD::with_can_fifos`]. A full TX FIFO and an empty RX FIFO are
+/// reported as [`nb::Error::WouldBlock`].
+#[cfg(not(feature = "async"))]
+impl<SPI, SPIE> embedded_can::nb::Can for MCP2518FD<SPI>
+where
+    SPI: SpiDevice<u8, Error = SPIE>,
+    SPIE: Debug,
+{
+    type Frame = TxMessage;
+    type Error = Error;
+
+    /// Queues the frame and requests its transmission. Pending frames are
+    /// never replaced, so this always returns `Ok(None)` on success.
+    fn transmit(&mut self, frame: &TxMessage) -> nb::Result<Option<TxMessage>, Error> {
+        let result = match self.can_tx_fifo {
+            Some(fifo_number) => self.tx_fifo_transmit_message(fifo_number, frame),
+            None => self.tx_queue_transmit_message(frame),
+        };
+
+        match result {
+            Ok(()) => Ok(None),
+            Err(Error::FifoFull) => Err(nb::Error::WouldBlock),
+            Err(error) => Err(nb::Error::Other(error)),
+        }
+    }
+
+    fn receive(&mut self) -> nb::Result<TxMessage, Error> {
+        match self.rx_fifo_get_next(self.can_rx_fifo)? {
+            Some(message) => Ok(message.to_tx()),
+            None => Err(nb::Error::WouldBlock),
+        }
+    }
+}
+
+/* Async receive stream */
+
+#[cfg(feature = "async")]
+impl<SPI, SPIE> MCP2518FD<SPI>
+where
+    SPI: SpiDevice<u8, Error = SPIE>,
+    SPIE: Debug,
+{
+    /// Turns the driver into a stream of received messages. Whenever no RX
+    /// FIFO has an interrupt pending (`C1RXIF`), the stream awaits the future
+    /// returned by `wait_for_interrupt`, which should resolve once the nINT or
+    /// nRX pin is asserted. Pending FIFOs are then drained lowest number
+    /// first, incrementing the head pointer after every message.
+    ///
+    /// Only FIFOs with at least one of their RX interrupts enabled (usually
+    /// `enable_fifo_not_empty_interrupt`) show up in `C1RXIF`, so messages in
+    /// other FIFOs are never yielded. SPI errors are yielded as they happen
+    /// and don't end the stream, but the stream waits for the next interrupt
+    /// before touching the chip again, so a failing bus doesn't turn into a
+    /// busy loop.
+    ///
+    /// With embassy this could look like:
+    ///
+    /// ```ignore
+    /// let mut messages = pin!(can.receive_stream(|| int_pin.wait_for_low()));
+    ///
+    /// while let Some(message) = messages.next().await {
+    ///     info!("Received {:?}", message?);
+    /// }
+    /// ```
+    pub fn receive_stream<'a, F, Fut>(
+        &'a mut self,
+        wait_for_interrupt: F,
+    ) -> impl futures::Stream<Item = Result<RxMessage, Error>> + 'a
+    where
+        F: FnMut() -> Fut + 'a,
+        Fut: core::future::Future<Output = ()> + 'a,
+    {
+        futures::stream::unfold(
+            (self, wait_for_interrupt, false),
+            |(driver, mut wait_for_interrupt, failed)| async move {
+                if failed {
+                    wait_for_interrupt().await;
+                }
+
+                loop {
+                    let pending = match driver.get_rx_interrupt_statuses().await {
+                        Ok(pending) => pending.0,
+                        Err(error) => {
+                            return Some((Err(error), (driver, wait_for_interrupt, true)))
+                        }
+                    };
+
+                    // Bit 0 is reserved, so garbage there doesn't count as
+                    // a pending FIFO
+                    let Some(fifo_number) = FifoSet::from_bits(pending).iter().next() else {
+                        wait_for_interrupt().await;
+                        continue;
+                    };
+
+                    // The flag may have been cleared since C1RXIF was read, in
+                    // which case the FIFO is simply checked again
+                    match driver.rx_fifo_get_next(fifo_number).await {
+                        Ok(Some(message)) => {
+                            return Some((Ok(message), (driver, wait_for_interrupt, false)))
+                        }
+                        Ok(None) => continue,
+                        Err(error) => {
+                            return Some((Err(error), (driver, wait_for_interrupt, true)))
+                        }
+                    }
+                }
+            },
+        )
+    }
+}
+
+/* RX FIFO objects */
+
+/// Offset of the payload within the objects of an RX FIFO, which have the
+/// timestamp between the header and the payload if it is enabled
+fn rx_data_offset(control_register: &FifoControlRegister) -> usize {
+    if control_register.rxtsen() {
+        HEADER_SIZE_DWORDS * 4 + 4
+    } else {
+        HEADER_SIZE_DWORDS * 4
+    }
+}
+
+/// Payload length of a received message. A DLC larger than the FIFO payload
+/// size can't be received, but clamp it anyway so a corrupted header can't
+/// make us read past the object.
+fn rx_data_len(
+    header: &RxHeader<[u32; HEADER_SIZE_DWORDS]>,
+    control_register: &FifoControlRegister,
+) -> usize {
+    len_for_dlc(header.dlc(), header.fdf())
+        .unwrap()
+        .min(control_register.payload_size().num_bytes())
+}
+
+/* Write verification */
+
+/// Compares a register read back after a write against the value that was
+/// written, ignoring any bits in `volatile_bits`
+fn check_readback(
+    register: SFRAddress,
+    wrote: u32,
+    read: u32,
+    volatile_bits: u32,
+) -> Result<(), Error> {
+    if (read ^ wrote) & !volatile_bits != 0 {
+        return Err(Error::ReadbackMismatch {
+            register,
+            wrote,
+            read,
+        });
+    }
+
+    Ok(())
+}
+
+/* RAM allocation */
+
+/// Number of objects the chip allocates RAM for: the TEF, the TXQ, and the
+/// 31 FIFOs, in that order
+const RAM_OBJECT_COUNT: usize = MAX_FIFO_COUNT + 2;
+
+const TEF_RAM_INDEX: usize = 0;
+const TXQ_RAM_INDEX: usize = 1;
+
+fn fifo_ram_index(fifo_number: FifoNumber) -> usize {
+    TXQ_RAM_INDEX + fifo_number as usize
+}
+
+/// Checks that the RAM object at `index` still fits when it needs
+/// `requested` bytes, given the current size of every object. The chip
+/// allocates RAM for all of them, so the sizes of all objects (including
+/// FIFOs that were never configured) have to add up to at most 2 KB.
+fn check_ram_budget(
+    sizes: &[u32; RAM_OBJECT_COUNT],
+    index: usize,
+    requested: u32,
+) -> Result<(), ConfigError> {
+    let others = sizes.iter().sum::<u32>() - sizes[index];
+    let available = RAM_SIZE_BYTES.saturating_sub(others);
+
+    if requested > available {
+        return Err(ConfigError::RamExhausted {
+            requested,
+            available,
+        });
+    }
+
+    Ok(())
 }
 
 /* Low level SPI instruction encoding */
@@ -1220,6 +3695,2439 @@ struct OpCode;
 
 impl OpCode {
     pub const RESET: u16 = 0b0000 << 12;
+    #[cfg(not(feature = "spi-crc"))]
     pub const READ: u16 = 0b0011 << 12;
+    #[cfg(not(feature = "spi-crc"))]
     pub const WRITE: u16 = 0b0010 << 12;
+    #[cfg(feature = "spi-crc")]
+    pub const READ_CRC: u16 = 0b1011 << 12;
+    #[cfg(feature = "spi-crc")]
+    pub const WRITE_CRC: u16 = 0b1010 << 12;
+}
+
+/* CRC-protected SPI instructions */
+
+/// Longest transfer done with a single CRC instruction. The length byte of
+/// the instruction counts bytes for SFRs, so transfers are split at the
+/// largest multiple of 4 that fits in it.
+#[cfg(feature = "spi-crc")]
+const CRC_MAX_CHUNK_LEN: usize = 252;
+
+#[cfg(feature = "spi-crc")]
+const CRC_INITIAL_VALUE: u16 = 0xFFFF;
+
+/// Builds the command, address, and length bytes of a READ_CRC or WRITE_CRC
+/// instruction. The length is given in words for RAM and in bytes for SFRs.
+#[cfg(feature = "spi-crc")]
+fn crc_instruction_header(op_code: u16, address: u16, len: usize, ram: bool) -> [u8; 3] {
+    let mut instruction = Instruction(op_code);
+    instruction.set_address(address);
+
+    let [command, address] = instruction.into_spi_data();
+    let len = if ram { len / 4 } else { len };
+
+    [command, address, len as u8]
+}
+
+/// CRC-16 over the given bytes, continuing from `crc`. Uses the polynomial
+/// 0x8005 without reflection or final XOR, starting from
+/// [`CRC_INITIAL_VALUE`].
+#[cfg(feature = "spi-crc")]
+pub(crate) fn crc16(mut crc: u16, data: &[u8]) -> u16 {
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockDelay, MockSpi};
+
+    const OSC: u16 = SFRAddress::OSC as u16;
+    const C1CON: u16 = SFRAddress::C1CON as u16;
+
+    /// C1CON with both OPMOD and REQOP set to `mode`
+    fn c1con_in_mode(mode: OperationMode) -> u32 {
+        let mode: u8 = mode.into();
+
+        ((mode as u32) << 21) | ((mode as u32) << 24)
+    }
+
+    /// Mirrors REQOP into OPMOD as soon as a mode is requested
+    fn instant_mode_changes(memory: &mut [u8], address: u16, _data: &[u8]) {
+        if address == C1CON {
+            let c1con = crate::mock::u32_at(memory, C1CON);
+            let reqop = (c1con >> 24) & 0b111;
+
+            crate::mock::set_u32(memory, C1CON, (c1con & !(0b111 << 21)) | (reqop << 21));
+        }
+    }
+
+    #[test]
+    fn error_display() {
+        extern crate std;
+        use std::string::ToString;
+
+        assert_eq!(
+            Error::InvalidRamAddress(0x3FC).to_string(),
+            "invalid RAM address 0x3fc"
+        );
+        assert_eq!(
+            Error::BufferTooSmall(12).to_string(),
+            "the buffer is too small for a 12 byte payload"
+        );
+        assert_eq!(
+            Error::ReadbackMismatch {
+                register: SFRAddress::C1CON,
+                wrote: 1,
+                read: 0
+            }
+            .to_string(),
+            "C1CON read back 0x00000000 after writing 0x00000001"
+        );
+        assert_eq!(
+            Error::TransmitTimeout.to_string(),
+            "timed out waiting for the frame to be sent"
+        );
+    }
+
+    #[test]
+    fn config_error_display() {
+        extern crate std;
+        use std::string::ToString;
+
+        assert_eq!(
+            ConfigError::UnexpectedDeviceId { id: 0, rev: 2 }.to_string(),
+            "unexpected device ID 0x0 (revision 2)"
+        );
+        assert_eq!(
+            ConfigError::RamExhausted {
+                requested: 2304,
+                available: 1552
+            }
+            .to_string(),
+            "requested 2304 bytes of RAM but only 1552 are available"
+        );
+        assert_eq!(
+            ConfigError::InvalidPriority(32).to_string(),
+            "priority 32 is above the highest priority 31"
+        );
+        assert_eq!(
+            ConfigError::BusIntegrationTimeout.to_string(),
+            "timed out integrating onto the bus"
+        );
+
+        // Wrapped driver errors are shown as they are
+        assert_eq!(
+            ConfigError::Other(Error::FifoFull).to_string(),
+            "the FIFO is full"
+        );
+    }
+
+    /// The catalogued check value of CRC-16/CMS, which uses the same
+    /// parameters as the chip
+    #[cfg(feature = "spi-crc")]
+    #[test]
+    fn crc16_check_value() {
+        assert_eq!(crc16(CRC_INITIAL_VALUE, b"123456789"), 0xAEE7);
+        assert_eq!(crc16(CRC_INITIAL_VALUE, &[]), CRC_INITIAL_VALUE);
+    }
+
+    #[cfg(feature = "spi-crc")]
+    #[test]
+    fn crc16_continues_across_chunks() {
+        // READ_CRC of one dword at C1CON, as sent ahead of the data
+        let header = [0xB0, 0x00, 0x01];
+        assert_eq!(crc16(CRC_INITIAL_VALUE, &header), 0x87C5);
+
+        let data = [0x60, 0x07, 0x98, 0x04];
+        let whole = [header.as_slice(), data.as_slice()].concat();
+
+        assert_eq!(
+            crc16(crc16(CRC_INITIAL_VALUE, &header), &data),
+            crc16(CRC_INITIAL_VALUE, &whole)
+        );
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn wake_clears_oscdis_before_polling() {
+        crate::mock::block_on(|| async {
+            let spi = MockSpi::new().on_write(|memory, address, data| {
+                instant_mode_changes(memory, address, data);
+
+                // The oscillator starts up once OSCDIS is cleared
+                if address == OSC && data[0] & (1 << 2) == 0 {
+                    let osc = crate::mock::u32_at(memory, OSC);
+                    crate::mock::set_u32(memory, OSC, osc | (1 << 10) | (1 << 12));
+                }
+            });
+
+            let mut can = MCP2518FD::new(spi);
+            can.spi.set_u32(OSC, 1 << 2);
+            can.spi.set_u32(C1CON, c1con_in_mode(OperationMode::Sleep));
+
+            can.wake(OperationMode::Configuration, &mut MockDelay::default())
+                .await
+                .unwrap();
+
+            let osc_writes = can.spi.writes_to(OSC);
+            assert_eq!(osc_writes.len(), 1);
+            assert_eq!(osc_writes[0][0] & (1 << 2), 0);
+
+            // OSCDIS is cleared before anything else is written, and OSC is
+            // read back at least once more to check OSCRDY and SCLKRDY
+            assert_eq!(can.spi.writes[0].0, OSC);
+            assert!(can.spi.reads_of(OSC) >= 2);
+
+            let op_mode = can.get_op_mode().await.unwrap();
+            assert!(op_mode == OperationMode::Configuration);
+        })
+    }
+
+    /// A sleeping chip whose oscillator reports ready on the `ready_on`th
+    /// read of OSC after OSCDIS was cleared, or never if `None`
+    fn sleeping_chip(ready_on: Option<usize>) -> MCP2518FD<MockSpi> {
+        let mut osc_reads = 0;
+
+        let spi = MockSpi::new()
+            .on_write(instant_mode_changes)
+            .on_read(move |memory, address| {
+                let osc = crate::mock::u32_at(memory, OSC);
+
+                if address == OSC && osc & (1 << 2) == 0 {
+                    osc_reads += 1;
+
+                    if ready_on.is_some_and(|ready_on| osc_reads >= ready_on) {
+                        crate::mock::set_u32(memory, OSC, osc | (1 << 10) | (1 << 12));
+                    }
+                }
+            });
+
+        let mut can = MCP2518FD::new(spi);
+        can.spi.set_u32(OSC, 1 << 2);
+        can.spi.set_u32(C1CON, c1con_in_mode(OperationMode::Sleep));
+
+        can
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn wake_polls_until_oscillator_ready() {
+        crate::mock::block_on(|| async {
+            let mut can = sleeping_chip(Some(3));
+            let mut delay = MockDelay::default();
+
+            can.wake(OperationMode::Configuration, &mut delay)
+                .await
+                .unwrap();
+
+            // One read to clear OSCDIS, then 3 polls
+            assert_eq!(can.spi.reads_of(OSC), 1 + 3);
+            assert_eq!(delay.elapsed_ns, 2 * 500_000);
+            let op_mode = can.get_op_mode().await.unwrap();
+            assert!(op_mode == OperationMode::Configuration);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn wake_oscillator_not_ready() {
+        crate::mock::block_on(|| async {
+            let mut can = sleeping_chip(None);
+
+            let result = can
+                .wake(OperationMode::Configuration, &mut MockDelay::default())
+                .await;
+            assert!(matches!(result, Err(ConfigError::OscillatorNotReady)));
+
+            // The mode change isn't requested on a chip without a clock
+            assert_eq!(can.spi.reads_of(OSC), 1 + 10);
+            assert!(can.spi.writes_to(C1CON).is_empty());
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn sleep_refuses_pending_transmission() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(MockSpi::new().on_write(instant_mode_changes));
+            can.spi
+                .set_u32(C1CON, c1con_in_mode(OperationMode::NormalCanFD));
+            can.spi.set_u32(SFRAddress::C1TXREQ as u16, 1 << 3);
+
+            let result = can.sleep(true, &mut MockDelay::default()).await;
+            assert!(matches!(result, Err(ConfigError::ControllerBusy)));
+            assert!(can.spi.writes.is_empty());
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn sleep_waits_for_sleep_mode() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(MockSpi::new().on_write(instant_mode_changes));
+            can.spi
+                .set_u32(C1CON, c1con_in_mode(OperationMode::NormalCanFD));
+
+            can.sleep(true, &mut MockDelay::default()).await.unwrap();
+
+            let op_mode = can.get_op_mode().await.unwrap();
+            assert!(op_mode == OperationMode::Sleep);
+            let c1int = can.read_register::<InterruptRegister>().await.unwrap();
+            assert!(c1int.wakie());
+
+            // A controller that never leaves Normal mode
+            let mut can = MCP2518FD::new(MockSpi::new());
+            can.spi
+                .set_u32(C1CON, c1con_in_mode(OperationMode::NormalCanFD));
+
+            let result = can.sleep(false, &mut MockDelay::default()).await;
+            assert!(matches!(result, Err(ConfigError::ChangeOpModeTimeout)));
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn mode_wrappers_request_their_mode() {
+        crate::mock::block_on(|| async {
+            // TXQEN and WAKFIL set, to check the rest of C1CON is kept
+            let other_bits = (1 << 20) | (1 << 8);
+
+            for (mode, reqop) in [
+                (OperationMode::ListenOnly, 0b011),
+                (OperationMode::Restricted, 0b111),
+                (OperationMode::NormalCanFD, 0b000),
+                (OperationMode::NormalCan2, 0b110),
+            ] {
+                let mut can = MCP2518FD::new(MockSpi::new().on_write(instant_mode_changes));
+                can.spi.set_u32(
+                    C1CON,
+                    c1con_in_mode(OperationMode::Configuration) | other_bits,
+                );
+                let mut delay = MockDelay::default();
+
+                let result = match mode {
+                    OperationMode::ListenOnly => can.enter_listen_only(&mut delay).await,
+                    OperationMode::Restricted => can.enter_restricted(&mut delay).await,
+                    OperationMode::NormalCanFD => can.enter_normal_fd(&mut delay).await,
+                    _ => can.enter_normal_can2(&mut delay).await,
+                };
+                result.unwrap();
+
+                // OPMOD is still Configuration when the request is written
+                assert_eq!(
+                    can.spi.words_written_to(C1CON),
+                    [(reqop << 24) | (0b100 << 21) | other_bits]
+                );
+                let op_mode = can.get_op_mode().await.unwrap();
+                assert!(op_mode == mode);
+            }
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn try_set_op_mode_when_idle_refuses_while_busy() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(MockSpi::new().on_write(instant_mode_changes));
+            // BUSY set
+            can.spi
+                .set_u32(C1CON, c1con_in_mode(OperationMode::NormalCanFD) | (1 << 11));
+
+            let result = can
+                .try_set_op_mode_when_idle(OperationMode::Configuration, &mut MockDelay::default())
+                .await;
+            assert!(matches!(result, Err(ConfigError::ControllerBusy)));
+            assert!(can.spi.writes_to(C1CON).is_empty());
+
+            can.spi
+                .set_u32(C1CON, c1con_in_mode(OperationMode::NormalCanFD));
+
+            can.try_set_op_mode_when_idle(OperationMode::Configuration, &mut MockDelay::default())
+                .await
+                .unwrap();
+            assert_eq!(can.spi.writes_to(C1CON).len(), 1);
+        })
+    }
+
+    /// A chip in Configuration mode that only switches to the requested mode
+    /// on the `switch_on`th poll of C1CON after the request
+    fn slow_mode_changes(switch_on: usize) -> MCP2518FD<MockSpi> {
+        let mut polls = 0;
+
+        let spi = MockSpi::new().on_read(move |memory, address| {
+            let c1con = crate::mock::u32_at(memory, C1CON);
+            let reqop = (c1con >> 24) & 0b111;
+
+            if address == C1CON && reqop != (c1con >> 21) & 0b111 {
+                polls += 1;
+
+                if polls >= switch_on {
+                    crate::mock::set_u32(memory, C1CON, (c1con & !(0b111 << 21)) | (reqop << 21));
+                }
+            }
+        });
+
+        let mut can = MCP2518FD::new(spi);
+        can.spi
+            .set_u32(C1CON, c1con_in_mode(OperationMode::Configuration));
+
+        can
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn set_op_mode_changes_on_7th_poll() {
+        crate::mock::block_on(|| async {
+            let mut can = slow_mode_changes(7);
+            let mut delay = MockDelay::default();
+
+            can.set_op_mode(OperationMode::NormalCanFD, &mut delay)
+                .await
+                .unwrap();
+
+            let op_mode = can.get_op_mode().await.unwrap();
+            assert!(op_mode == OperationMode::NormalCanFD);
+            assert_eq!(delay.elapsed_ns, 6 * 100_000);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn set_op_mode_with_timeout_gives_up() {
+        crate::mock::block_on(|| async {
+            let mut can = slow_mode_changes(7);
+            let mut delay = MockDelay::default();
+
+            let result = can
+                .set_op_mode_with_timeout(OperationMode::NormalCanFD, &mut delay, 500)
+                .await;
+            assert!(matches!(result, Err(ConfigError::ChangeOpModeTimeout)));
+            assert_eq!(delay.elapsed_ns, 500_000);
+
+            // A longer timeout rides it out
+            let mut can = slow_mode_changes(7);
+
+            can.set_op_mode_with_timeout(OperationMode::NormalCanFD, &mut delay, 600)
+                .await
+                .unwrap();
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn enter_normal_waits_for_bus_integration() {
+        crate::mock::block_on(|| async {
+            // Leaving a mode other than Configuration
+            let mut can = slow_mode_changes(3);
+            can.spi
+                .set_u32(C1CON, c1con_in_mode(OperationMode::ListenOnly));
+
+            can.enter_normal_when_ready(&mut MockDelay::default(), 1_000)
+                .await
+                .unwrap();
+
+            let op_mode = can.get_op_mode().await.unwrap();
+            assert!(op_mode == OperationMode::NormalCanFD);
+
+            // A bus that never goes idle
+            let mut can = slow_mode_changes(usize::MAX);
+            let mut delay = MockDelay::default();
+
+            let result = can.enter_normal_when_ready(&mut delay, 1_000).await;
+            assert!(matches!(result, Err(ConfigError::BusIntegrationTimeout)));
+            assert_eq!(delay.elapsed_ns, 1_000_000);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn enter_normal_reports_rejected_request() {
+        crate::mock::block_on(|| async {
+            // The controller drops the request and stays in Configuration mode
+            let spi = MockSpi::new().on_write(|memory, address, _data| {
+                if address == C1CON {
+                    let c1con = crate::mock::u32_at(memory, C1CON);
+                    let opmode = (c1con >> 21) & 0b111;
+
+                    crate::mock::set_u32(memory, C1CON, (c1con & !(0b111 << 24)) | (opmode << 24));
+                }
+            });
+
+            let mut can = MCP2518FD::new(spi);
+            can.spi
+                .set_u32(C1CON, c1con_in_mode(OperationMode::Configuration));
+
+            let result = can
+                .enter_normal_when_ready(&mut MockDelay::default(), 1_000)
+                .await;
+            assert!(matches!(result, Err(ConfigError::ModeRequestRejected)));
+        })
+    }
+
+    /// A bus off controller in Normal mode whose `C1TREC.TXBO` clears on the
+    /// `cleared_on`th read of C1TREC, or never if `None`
+    fn bus_off_chip(cleared_on: Option<usize>) -> MCP2518FD<MockSpi> {
+        const C1TREC: u16 = SFRAddress::C1TREC as u16;
+
+        let mut reads = 0;
+
+        let spi = MockSpi::new()
+            .on_write(instant_mode_changes)
+            .on_read(move |memory, address| {
+                if address == C1TREC {
+                    reads += 1;
+
+                    if cleared_on.is_some_and(|cleared_on| reads >= cleared_on) {
+                        crate::mock::set_u32(memory, C1TREC, 0);
+                    }
+                }
+            });
+
+        let mut can = MCP2518FD::new(spi);
+        can.spi
+            .set_u32(C1CON, c1con_in_mode(OperationMode::NormalCanFD));
+        can.spi.set_u32(C1TREC, 1 << 21);
+
+        can
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn recover_from_bus_off_waits_for_txbo() {
+        crate::mock::block_on(|| async {
+            // TXBO is still set for the initial check and the first poll
+            let mut can = bus_off_chip(Some(3));
+            let mut delay = MockDelay::default();
+
+            let recovered = can.recover_from_bus_off(&mut delay, 1_000).await;
+            assert!(matches!(recovered, Ok(true)));
+
+            let op_mode = can.get_op_mode().await.unwrap();
+            assert!(op_mode == OperationMode::NormalCanFD);
+            assert_eq!(delay.elapsed_ns, 100_000);
+
+            // Nothing to do unless the controller is bus off
+            let mut can = bus_off_chip(Some(1));
+
+            let recovered = can
+                .recover_from_bus_off(&mut MockDelay::default(), 1_000)
+                .await;
+            assert!(matches!(recovered, Ok(false)));
+            assert!(can.spi.writes.is_empty());
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn recover_from_bus_off_timeout() {
+        crate::mock::block_on(|| async {
+            let mut can = bus_off_chip(None);
+
+            let result = can
+                .recover_from_bus_off(&mut MockDelay::default(), 1_000)
+                .await;
+            assert!(matches!(result, Err(ConfigError::BusOffRecoveryTimeout)));
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn configure_reports_configuration_mode_timeout() {
+        crate::mock::block_on(|| async {
+            // DEVID reads 0 and the controller never leaves Sleep mode
+            let mut can = slow_mode_changes(usize::MAX);
+            can.spi.set_u32(C1CON, c1con_in_mode(OperationMode::Sleep));
+
+            let result = can
+                .configure(shared_bus_settings(), &mut MockDelay::default())
+                .await;
+            assert!(matches!(result, Err(ConfigError::ConfigurationModeTimeout)));
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn interrupt_enables_leave_flags_alone() {
+        crate::mock::block_on(|| async {
+            const C1INT: u16 = SFRAddress::C1INT as u16;
+
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            // RXIE and TXIE enabled, WAKIF and MODIF pending
+            can.spi.set_u32(C1INT, 0x0003_4008);
+
+            can.set_interrupt_enabled(InterruptKind::TbcOverflow, true)
+                .await
+                .unwrap();
+            can.spi.set_u32(C1INT, 0x0003_4008);
+            can.set_interrupt_enabled(InterruptKind::Rx, false)
+                .await
+                .unwrap();
+            can.mask_all_interrupts().await.unwrap();
+
+            // The flags are written as 1, which leaves them as they are
+            assert_eq!(
+                can.spi.words_written_to(C1INT),
+                [0x0007_FFFF, 0x0001_FFFF, 0x0000_FFFF]
+            );
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn clear_system_interrupts_clears_only_the_given_flags() {
+        crate::mock::block_on(|| async {
+            const C1INT: u16 = SFRAddress::C1INT as u16;
+
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            // RXIE and TXIE enabled, WAKIF and MODIF pending
+            can.spi.set_u32(C1INT, 0x0003_4008);
+
+            can.clear_system_interrupts(
+                SystemInterrupts::MODE_CHANGE | SystemInterrupts::TBC_OVERFLOW,
+            )
+            .await
+            .unwrap();
+
+            // MODIF and TBCIF are written as 0, WAKIF and every other flag
+            // as 1, and the enables are kept
+            assert_eq!(can.spi.words_written_to(C1INT), [0x0003_FFF3]);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn pending_rx_fifos_decodes_c1rxif() {
+        crate::mock::block_on(|| async {
+            const C1RXIF: u16 = SFRAddress::C1RXIF as u16;
+
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            // FIFO 1, 5 and 31. Bit 0 is reserved and always reads as 0.
+            can.spi.set_u32(C1RXIF, (1 << 1) | (1 << 5) | (1 << 31));
+
+            let pending = can.pending_rx_fifos().await.unwrap();
+            assert_eq!(pending.len(), 3);
+            assert!(pending.contains(FifoNumber::Fifo1));
+            assert!(pending.contains(FifoNumber::Fifo5));
+            assert!(pending.contains(FifoNumber::Fifo31));
+            assert!(!pending.contains(FifoNumber::Fifo2));
+            assert_eq!(can.spi.reads_of(C1RXIF), 1);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn poll_interrupt_decodes_c1vec() {
+        crate::mock::block_on(|| async {
+            const C1VEC: u16 = SFRAddress::C1VEC as u16;
+
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            // ICODE = 0x40 (no interrupt)
+            can.spi.set_u32(C1VEC, 0x40);
+            let event = can.poll_interrupt().await.unwrap();
+            assert_eq!(event, None);
+
+            // ICODE and RXCODE = FIFO 2
+            can.spi.set_u32(C1VEC, (2 << 24) | 2);
+            let event = can.poll_interrupt().await.unwrap();
+            assert_eq!(event, Some(CanEvent::RxAvailable(FifoNumber::Fifo2)));
+
+            // ICODE and TXCODE = FIFO 3, RXCODE = no interrupt
+            can.spi.set_u32(C1VEC, (0x40 << 24) | (3 << 16) | 3);
+            let event = can.poll_interrupt().await.unwrap();
+            assert_eq!(event, Some(CanEvent::TxFifo(FifoNumber::Fifo3)));
+
+            // ICODE = 0x47 (mode change)
+            can.spi.set_u32(C1VEC, 0x47);
+            let event = can.poll_interrupt().await.unwrap();
+            assert_eq!(event, Some(CanEvent::ModeChanged));
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn clear_rx_overflow_clears_rxovif_of_that_fifo() {
+        crate::mock::block_on(|| async {
+            // C1FIFOSTA3
+            const FIFO3STA: u16 = 0x78;
+
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            // FIFOCI = 5, RXOVIF and TFNRFNIF set
+            can.spi.set_u32(FIFO3STA, 0x0000_0509);
+
+            can.clear_rx_overflow(FifoNumber::Fifo3).await.unwrap();
+
+            assert_eq!(can.spi.writes.len(), 1);
+            assert_eq!(can.spi.words_written_to(FIFO3STA), [0x0000_0501]);
+            assert_eq!(can.stats().rx_overflows, 1);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn raw_sfr_access_matches_typed_registers() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(MockSpi::new());
+            // The reset value
+            can.spi.set_u32(C1CON, 0x0498_0760);
+
+            let raw = can.read_sfr_raw(SFRAddress::C1CON).await.unwrap();
+            let c1con = can.read_register::<CanControlRegister>().await.unwrap();
+            assert_eq!(raw, 0x0498_0760);
+            assert!(c1con.opmode() == OperationMode::Configuration);
+            assert_eq!(raw, u32::from(c1con));
+
+            // TXQEN, WAKFIL, and TXBWS = 16 bit times
+            let value = (4 << 28) | (1 << 20) | (1 << 8);
+            can.write_sfr_raw(SFRAddress::C1CON, value).await.unwrap();
+
+            assert_eq!(can.spi.words_written_to(C1CON), [value]);
+            let c1con = can.read_register::<CanControlRegister>().await.unwrap();
+            assert!(c1con.txqen());
+            assert!(c1con.wakfil());
+            assert!(matches!(
+                c1con.txbws(),
+                Ok(crate::memory::controller::configuration::InterTransmissionDelay::Delay16)
+            ));
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn tx_errors_decode_and_clear() {
+        crate::mock::block_on(|| async {
+            const C1TXQSTA: u16 = SFRAddress::C1TXQSTA as u16;
+            // C1FIFOSTA2
+            const FIFO2STA: u16 = 0x6C;
+
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            // TXABT wins over TXERR, which wins over TXLARB
+            for (bits, expected) in [
+                (0b000, TxError::None),
+                (0b010, TxError::ArbitrationLost),
+                (0b001, TxError::BusError),
+                (0b011, TxError::BusError),
+                (0b100, TxError::Aborted),
+                (0b110, TxError::Aborted),
+                (0b101, TxError::Aborted),
+                (0b111, TxError::Aborted),
+            ] {
+                can.spi.set_u32(C1TXQSTA, bits << 5);
+                can.spi.set_u32(FIFO2STA, bits << 5);
+
+                let txq_error = can.get_tx_queue_error().await.unwrap();
+                let fifo_error = can.get_tx_fifo_error(FifoNumber::Fifo2).await.unwrap();
+                assert_eq!(txq_error, expected, "{bits:03b}");
+                assert_eq!(fifo_error, expected, "{bits:03b}");
+            }
+
+            // Every error flag, TXATIF and TFNRFNIF set
+            can.spi.set_u32(C1TXQSTA, 0xF1);
+            can.spi.set_u32(FIFO2STA, 0xF1);
+
+            can.clear_tx_errors(None).await.unwrap();
+            can.clear_tx_errors(Some(FifoNumber::Fifo2)).await.unwrap();
+
+            // Only TXABT, TXLARB and TXERR are written as 0
+            assert_eq!(can.spi.words_written_to(C1TXQSTA), [0x11]);
+            assert_eq!(can.spi.words_written_to(FIFO2STA), [0x11]);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn interrupt_statuses_read_in_one_burst() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            can.spi.set_u32(SFRAddress::C1INT as u16, 0x0003_0002);
+            can.spi.set_u32(SFRAddress::C1RXIF as u16, 1 << 1);
+            can.spi.set_u32(SFRAddress::C1TXIF as u16, 1 << 2);
+            can.spi.set_u32(SFRAddress::C1RXOVIF as u16, 1 << 3);
+            can.spi.set_u32(SFRAddress::C1TXATIF as u16, 1 << 4);
+
+            let statuses = can.read_all_interrupt_statuses().await.unwrap();
+
+            assert_eq!(statuses.interrupts.0, 0x0003_0002);
+            assert!(statuses.interrupts.rxie() && statuses.interrupts.txie());
+            assert_eq!(statuses.rx.0, 1 << 1);
+            assert_eq!(statuses.tx.0, 1 << 2);
+            assert_eq!(statuses.rx_overflow.0, 1 << 3);
+            assert_eq!(statuses.tx_attempt.0, 1 << 4);
+
+            assert_eq!(can.spi.transactions, 1);
+            assert_eq!(can.spi.reads, [SFRAddress::C1INT as u16]);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn dump_registers_reads_two_bursts() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            can.spi.set_u32(C1CON, 0x0498_0760);
+            can.spi.set_u32(SFRAddress::C1MASK31 as u16, 0x1FFF_FFFF);
+            can.spi.set_u32(OSC, 0x0000_0460);
+            can.spi.set_u32(SFRAddress::DEVID as u16, 0x14);
+
+            let snapshot = can.dump_registers().await.unwrap();
+
+            assert_eq!(snapshot.get(SFRAddress::C1CON), 0x0498_0760);
+            assert_eq!(snapshot.get(SFRAddress::C1MASK31), 0x1FFF_FFFF);
+            assert_eq!(snapshot.get(SFRAddress::OSC), 0x0000_0460);
+            assert_eq!(snapshot.get(SFRAddress::DEVID), 0x14);
+
+            // The CRC instructions split the 752 byte controller block in 3
+            let controller_transactions = if cfg!(feature = "spi-crc") { 3 } else { 1 };
+
+            assert_eq!(can.spi.transactions, controller_transactions + 1);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn would_accept_reads_filters_in_one_burst() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            // Filter 5 accepts SID 0x123 with DB0[7] set into FIFO 2
+            let fltcon1 = SFRAddress::C1FLTCON1 as u16;
+            can.spi.set_u32(fltcon1, ((1 << 7) | 2) << 8);
+            can.spi
+                .set_u32(SFRAddress::C1FLTOBJ5 as u16, 0x123 | (1 << 11));
+            can.spi
+                .set_u32(SFRAddress::C1MASK5 as u16, 0x7FF | (0x3FFFF << 11));
+            // DNCNT = 1
+            can.spi.set_u32(C1CON, 1);
+
+            let id = Id::Standard(embedded_can::StandardId::new(0x123).unwrap());
+
+            let fifo = can.would_accept(id, &[0x80]).await.unwrap();
+            assert_eq!(fifo, Some(FifoNumber::Fifo2));
+            let fifo = can.would_accept(id, &[0x7F]).await.unwrap();
+            assert_eq!(fifo, None);
+
+            // The CRC instructions split the 288 byte block in two
+            let filter_block_transactions = if cfg!(feature = "spi-crc") { 2 } else { 1 };
+
+            assert_eq!(can.spi.reads_of(SFRAddress::C1FLTCON0 as u16), 2);
+            assert_eq!(can.spi.transactions, 2 * (1 + filter_block_transactions));
+        })
+    }
+
+    const FIFO1CON: u16 = SFRAddress::C1FIFOCON1 as u16;
+
+    /// A chip with a single message of `data_len` bytes (counting up from 0)
+    /// waiting in RX FIFO 1, which has a 64 byte payload size
+    fn rx_fifo_with_message(data_len: usize, fdf: bool) -> MCP2518FD<MockSpi> {
+        let mut can = MCP2518FD::new(MockSpi::new());
+
+        let mut fifocon = FifoControlRegister(0);
+        fifocon.set_payload_size(PayloadSize::Bytes64);
+        can.spi.set_u32(FIFO1CON, fifocon.0);
+        // C1FIFOSTA1.TFNRFNIF, and C1FIFOUA1 pointing at the start of RAM
+        can.spi.set_u32(SFRAddress::C1FIFOSTA1 as u16, 1);
+        can.spi.set_u32(SFRAddress::C1FIFOUA1 as u16, 0);
+
+        let dlc = crate::message::dlc_for_len(data_len, fdf).unwrap() as u32;
+        can.spi.set_u32(0x400, 0x123);
+        can.spi.set_u32(0x404, dlc | (fdf as u32) << 7);
+
+        for i in 0..data_len {
+            can.spi.memory[0x408 + i] = i as u8;
+        }
+
+        can
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn receive_stream_skips_reserved_bit() {
+        extern crate std;
+
+        use futures::StreamExt;
+        use std::rc::Rc;
+
+        let waits = Rc::new(core::cell::Cell::new(0));
+        let c1rxif = SFRAddress::C1RXIF as u16;
+
+        // Only the reserved bit 0 of C1RXIF is set until the first interrupt,
+        // after which FIFO 1 is pending
+        let spi = rx_fifo_with_message(8, false).free().on_read({
+            let waits = waits.clone();
+
+            move |memory, address| {
+                if address == c1rxif && waits.get() > 0 {
+                    crate::mock::set_u32(memory, c1rxif, 1 | (1 << 1));
+                }
+            }
+        });
+
+        let mut can = MCP2518FD::new(spi);
+        can.spi.set_u32(c1rxif, 1);
+
+        crate::mock::block_on(|| async {
+            let messages = can.receive_stream(|| {
+                waits.set(waits.get() + 1);
+                core::future::ready(())
+            });
+            let mut messages = core::pin::pin!(messages);
+
+            let message = messages.next().await.unwrap().unwrap();
+
+            assert_eq!(message.data(), &[0, 1, 2, 3, 4, 5, 6, 7]);
+            assert_eq!(waits.get(), 1);
+        })
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn receive_stream_waits_after_error() {
+        use futures::StreamExt;
+
+        let mut can = MCP2518FD::new(MockSpi::new());
+        can.spi.fail_after = Some(0);
+
+        let waits = core::cell::Cell::new(0);
+
+        crate::mock::block_on(|| async {
+            let messages = can.receive_stream(|| {
+                waits.set(waits.get() + 1);
+                core::future::ready(())
+            });
+            let mut messages = core::pin::pin!(messages);
+
+            assert!(matches!(messages.next().await, Some(Err(Error::SPIRead))));
+            assert_eq!(waits.get(), 0);
+
+            // The next attempt only comes after an interrupt
+            assert!(matches!(messages.next().await, Some(Err(Error::SPIRead))));
+            assert_eq!(waits.get(), 1);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn rx_fifo_get_next_into_8_byte_buffer() {
+        crate::mock::block_on(|| async {
+            let mut can = rx_fifo_with_message(8, false);
+
+            let message = can
+                .rx_fifo_get_next_into::<8>(FifoNumber::Fifo1)
+                .await
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(message.data(), &[0, 1, 2, 3, 4, 5, 6, 7]);
+            assert_eq!(can.stats().frames_received, 1);
+
+            // C1FIFOCON1 is only read once, and written back with UINC set
+            assert_eq!(can.spi.transactions, 4);
+            assert_eq!(can.spi.reads_of(FIFO1CON), 1);
+            let writes = can.spi.words_written_to(FIFO1CON);
+            assert_eq!(writes.len(), 1);
+            assert!(FifoControlRegister(writes[0]).uinc());
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn rx_fifo_get_next_64_byte_buffer() {
+        crate::mock::block_on(|| async {
+            let mut can = rx_fifo_with_message(64, true);
+
+            let message: RxMessage<64> = can
+                .rx_fifo_get_next(FifoNumber::Fifo1)
+                .await
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(message.data().len(), 64);
+            assert_eq!(message.data()[63], 63);
+            assert_eq!(can.spi.writes_to(FIFO1CON).len(), 1);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn rx_fifo_read_into_exact_size_buffer() {
+        crate::mock::block_on(|| async {
+            let mut can = rx_fifo_with_message(5, false);
+            let mut buf = [0xFF; 5];
+
+            let (header, len) = can
+                .rx_fifo_read_into(FifoNumber::Fifo1, &mut buf)
+                .await
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(header.sid(), 0x123);
+            assert_eq!(len, 5);
+            assert_eq!(buf, [0, 1, 2, 3, 4]);
+            assert_eq!(can.spi.writes_to(FIFO1CON).len(), 1);
+
+            let mut buf = [0; 4];
+            let mut can = rx_fifo_with_message(5, false);
+
+            let result = can.rx_fifo_read_into(FifoNumber::Fifo1, &mut buf).await;
+            assert!(matches!(result, Err(Error::BufferTooSmall(5))));
+            assert!(can.spi.writes_to(FIFO1CON).is_empty());
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn rx_fifo_peek_reads_object_in_one_burst() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            // A 64 byte payload RX FIFO 1 with timestamps, and a message
+            // whose data follows the timestamp
+            let mut fifocon = FifoControlRegister(0);
+            fifocon.set_payload_size(PayloadSize::Bytes64);
+            fifocon.set_rxtsen(true);
+            can.spi.set_u32(FIFO1CON, fifocon.0);
+            can.spi.set_u32(SFRAddress::C1FIFOSTA1 as u16, 1);
+
+            let dlc = crate::message::dlc_for_len(64, true).unwrap() as u32;
+            can.spi.set_u32(0x400, 0x123);
+            can.spi.set_u32(0x404, dlc | (1 << 7));
+            can.spi.set_u32(0x408, 0xDEADBEEF);
+            can.spi.memory[0x40C..0x44C].fill(0xAA);
+
+            let message = can
+                .rx_fifo_peek_next(FifoNumber::Fifo1)
+                .await
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(message.timestamp(), Some(0xDEADBEEF));
+            assert_eq!(message.data(), &[0xAA; 64]);
+
+            // C1FIFOCON1, C1FIFOSTA1 and C1FIFOUA1 in one burst, and the
+            // header, timestamp, and payload in another
+            assert_eq!(can.spi.transactions, 3);
+            assert_eq!(can.spi.reads_of(0x400), 1);
+            assert_eq!(can.spi.reads.len(), 3);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn rx_fifo_peek_tx_fifo() {
+        crate::mock::block_on(|| async {
+            let mut can = tx_fifo_with_room();
+
+            let result = can.rx_fifo_peek_next(FifoNumber::Fifo1).await;
+            assert!(matches!(result, Err(Error::FifoNotRx)));
+            let result = can.rx_fifo_get_next(FifoNumber::Fifo1).await;
+            assert!(matches!(result, Err(Error::FifoNotRx)));
+            assert!(can.spi.writes.is_empty());
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn rx_fifo_message_too_long_for_buffer() {
+        crate::mock::block_on(|| async {
+            let mut can = rx_fifo_with_message(12, true);
+
+            let result = can.rx_fifo_get_next_into::<8>(FifoNumber::Fifo1).await;
+            assert!(matches!(result, Err(Error::BufferTooSmall(12))));
+            // The head pointer wasn't incremented
+            assert!(can.spi.writes_to(FIFO1CON).is_empty());
+
+            let discarded = can.rx_fifo_discard_next(FifoNumber::Fifo1).await.unwrap();
+            assert!(discarded);
+
+            let writes = can.spi.writes_to(FIFO1CON);
+            assert_eq!(writes.len(), 1);
+            assert!(FifoControlRegister(u32::from_le_bytes(writes[0].try_into().unwrap())).uinc());
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn rx_fifo_drain_keeps_count_on_error() {
+        crate::mock::block_on(|| async {
+            let mut can = rx_fifo_with_message(8, false);
+            let mut out: [RxMessage<8>; 4] = Default::default();
+
+            // C1FIFOCON1, then 3 transactions per message, so the third message
+            // fails to read
+            can.spi.fail_after = Some(1 + 2 * 3);
+
+            let drained = can
+                .rx_fifo_drain(FifoNumber::Fifo1, &mut out)
+                .await
+                .unwrap();
+            assert_eq!(drained, 2);
+            assert_eq!(out[1].data(), &[0, 1, 2, 3, 4, 5, 6, 7]);
+            let drained = can.rx_fifo_drain(FifoNumber::Fifo1, &mut out).await;
+            assert!(drained.is_err());
+
+            // A message whose head pointer increment failed isn't counted
+            let mut can = rx_fifo_with_message(8, false);
+            can.spi.fail_after = Some(1 + 2 * 3 - 1);
+
+            let drained = can
+                .rx_fifo_drain(FifoNumber::Fifo1, &mut out)
+                .await
+                .unwrap();
+            assert_eq!(drained, 1);
+            assert_eq!(can.stats().frames_received, 1);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn tx_event_fifo_drain_keeps_count_on_error() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(MockSpi::new());
+            // C1TEFSTA.TEFNEIF, and C1TEFUA pointing at the start of RAM
+            can.spi.set_u32(SFRAddress::C1TEFSTA as u16, 1);
+            can.spi.set_u32(SFRAddress::C1TEFUA as u16, 0);
+            can.spi.set_u32(0x400, 0x123);
+            can.spi.set_u32(0x404, 5 << 9);
+
+            let mut out: [TxEventObject; 4] = Default::default();
+
+            // C1TEFCON, then 3 transactions per object, so the third object fails
+            // to read
+            can.spi.fail_after = Some(1 + 2 * 3);
+
+            let drained = can.tx_event_fifo_drain(&mut out).await.unwrap();
+            assert_eq!(drained, 2);
+            assert_eq!(out[1].sequence_number(), 5);
+            let drained = can.tx_event_fifo_drain(&mut out).await;
+            assert!(drained.is_err());
+
+            // An object whose tail pointer increment failed isn't counted
+            can.spi.transactions = 0;
+            can.spi.fail_after = Some(1 + 2 * 3 - 1);
+
+            let drained = can.tx_event_fifo_drain(&mut out).await.unwrap();
+            assert_eq!(drained, 1);
+        })
+    }
+
+    /// A chip with one TEF object at the start of RAM, followed by a word that
+    /// is only the object's timestamp if `timestamped` is set
+    fn tef_with_object(timestamped: bool) -> MCP2518FD<MockSpi> {
+        let mut can = MCP2518FD::new(MockSpi::new());
+
+        // C1TEFCON.TEFTSEN, C1TEFSTA.TEFNEIF, and C1TEFUA pointing at the
+        // start of RAM
+        can.spi
+            .set_u32(SFRAddress::C1TEFCON as u16, (timestamped as u32) << 5);
+        can.spi.set_u32(SFRAddress::C1TEFSTA as u16, 1);
+        can.spi.set_u32(SFRAddress::C1TEFUA as u16, 0);
+
+        // SID 0x123, DLC 8, sequence number 0x1234, timestamp 0xDEADBEEF
+        can.spi.set_u32(0x400, 0x123);
+        can.spi.set_u32(0x404, 8 | (0x1234 << 9));
+        can.spi.set_u32(0x408, 0xDEADBEEF);
+
+        can
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn tx_event_fifo_peek_timestamped() {
+        crate::mock::block_on(|| async {
+            let mut can = tef_with_object(true);
+
+            let object = can.tx_event_fifo_peek_next().await.unwrap().unwrap();
+
+            assert_eq!(object.header.sid(), 0x123);
+            assert_eq!(object.header.dlc(), 8);
+            assert_eq!(object.sequence_number(), 0x1234);
+            assert_eq!(object.timestamp, Some(0xDEADBEEF));
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn tx_event_fifo_peek_without_timestamp() {
+        crate::mock::block_on(|| async {
+            let mut can = tef_with_object(false);
+
+            let object = can.tx_event_fifo_peek_next().await.unwrap().unwrap();
+
+            assert_eq!(object.header.sid(), 0x123);
+            assert_eq!(object.header.dlc(), 8);
+            assert_eq!(object.sequence_number(), 0x1234);
+            assert_eq!(object.timestamp, None);
+        })
+    }
+
+    /// Every FIFO with its reset configuration, the TEF and TXQ disabled
+    fn reset_ram_object_sizes() -> [u32; RAM_OBJECT_COUNT] {
+        let mut sizes = [FifoControlRegister(0).ram_bytes(); RAM_OBJECT_COUNT];
+        sizes[TEF_RAM_INDEX] = 0;
+        sizes[TXQ_RAM_INDEX] = 0;
+        sizes
+    }
+
+    #[test]
+    fn ram_budget_just_fits() {
+        let sizes = reset_ram_object_sizes();
+        // The other 30 FIFOs keep their 16 bytes
+        let available = RAM_SIZE_BYTES - 30 * 16;
+
+        assert!(check_ram_budget(&sizes, fifo_ram_index(FifoNumber::Fifo1), available).is_ok());
+        assert!(check_ram_budget(&sizes, TEF_RAM_INDEX, available - 16).is_ok());
+    }
+
+    #[test]
+    fn ram_budget_overrun() {
+        let mut sizes = reset_ram_object_sizes();
+        // 32 objects with a 64 byte payload
+        sizes[TXQ_RAM_INDEX] = 32 * 72;
+
+        assert!(matches!(
+            check_ram_budget(&sizes, TXQ_RAM_INDEX, 32 * 72),
+            Err(ConfigError::RamExhausted {
+                requested: 2304,
+                available: 1552
+            })
+        ));
+
+        // Shrinking an object that is already too big is still checked
+        // against what the others take up
+        sizes[fifo_ram_index(FifoNumber::Fifo31)] = 1600;
+
+        assert!(matches!(
+            check_ram_budget(&sizes, TXQ_RAM_INDEX, 16),
+            Err(ConfigError::RamExhausted {
+                requested: 16,
+                available: 0
+            })
+        ));
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn tx_queue_priority_checked_before_spi() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            let config = settings::TxQueueConfiguration::new(32, 1, PayloadSize::Bytes8);
+
+            let result = can.configure_tx_queue(Some(config)).await;
+            assert!(matches!(result, Err(ConfigError::InvalidPriority(32))));
+            assert_eq!(can.spi.transactions, 0);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn tx_fifo_configuration_round_trip() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            let tx_config = || {
+                FifoConfiguration::new(
+                    4,
+                    PayloadSize::Bytes64,
+                    settings::FifoMode::Transmit(
+                        TxFifoConfiguration::new(5)
+                            .with_retransmission_attempts(RetransmissionAttempts::ThreeRetries)
+                            .with_auto_rtr(true)
+                            .with_watermark(TxWatermark::HalfEmpty)
+                            .with_fifo_not_full_interrupt(true),
+                    ),
+                )
+            };
+
+            can.configure_fifo(FifoNumber::Fifo2, tx_config())
+                .await
+                .unwrap();
+
+            let read_back = can.get_fifo_configuration(FifoNumber::Fifo2).await.unwrap();
+            assert_eq!(read_back, tx_config());
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn rx_fifo_configuration_round_trip() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            let rx_config = || {
+                FifoConfiguration::new(
+                    32,
+                    PayloadSize::Bytes12,
+                    settings::FifoMode::Receive(
+                        RxFifoConfiguration::new()
+                            .with_message_timestamps(true)
+                            .with_fifo_overflow_interrupt(true)
+                            .with_watermark(RxWatermark::HalfFull)
+                            .with_fifo_not_empty_interrupt(true),
+                    ),
+                )
+            };
+
+            can.configure_fifo(FifoNumber::Fifo3, rx_config())
+                .await
+                .unwrap();
+
+            let read_back = can.get_fifo_configuration(FifoNumber::Fifo3).await.unwrap();
+            assert_eq!(read_back, rx_config());
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn configure_fifo_clears_bits_of_the_other_direction() {
+        crate::mock::block_on(|| async {
+            // TXPRI, RTREN and TXATIE
+            const TX_ONLY_BITS: u32 = (0x1F << 16) | (1 << 6) | (1 << 4);
+            // RXTSEN and RXOVIE
+            const RX_ONLY_BITS: u32 = (1 << 5) | (1 << 3);
+            const TXEN: u32 = 1 << 7;
+
+            let tx_config = || {
+                FifoConfiguration::new(
+                    4,
+                    PayloadSize::Bytes8,
+                    settings::FifoMode::Transmit(
+                        TxFifoConfiguration::new(31)
+                            .with_auto_rtr(true)
+                            .with_transmit_attempts_exhausted_interrupt(true),
+                    ),
+                )
+            };
+            let rx_config = FifoConfiguration::new(
+                4,
+                PayloadSize::Bytes8,
+                settings::FifoMode::Receive(
+                    RxFifoConfiguration::new()
+                        .with_message_timestamps(true)
+                        .with_fifo_overflow_interrupt(true),
+                ),
+            );
+
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            can.configure_fifo(FifoNumber::Fifo1, tx_config())
+                .await
+                .unwrap();
+            let fifocon = crate::mock::u32_at(&can.spi.memory, FIFO1CON);
+            assert_eq!(fifocon & (TX_ONLY_BITS | TXEN), TX_ONLY_BITS | TXEN);
+
+            can.configure_fifo(FifoNumber::Fifo1, rx_config)
+                .await
+                .unwrap();
+            let fifocon = crate::mock::u32_at(&can.spi.memory, FIFO1CON);
+            assert_eq!(fifocon & (TX_ONLY_BITS | TXEN), 0);
+            assert_eq!(fifocon & RX_ONLY_BITS, RX_ONLY_BITS);
+
+            can.configure_fifo(FifoNumber::Fifo1, tx_config())
+                .await
+                .unwrap();
+            let fifocon = crate::mock::u32_at(&can.spi.memory, FIFO1CON);
+            assert_eq!(fifocon & RX_ONLY_BITS, 0);
+            assert_eq!(fifocon & TXEN, TXEN);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn configure_bit_timing_writes_manual_tdcv() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            let mut config = BitTimeConfiguration::calculate(40_000_000, 500_000, 2_000_000, 80)
+                .unwrap()
+                .with_sid11(true);
+            config.data = config
+                .data
+                .with_manual_transmitter_delay_compensation(u6::new(4), 8)
+                .unwrap();
+
+            can.configure_bit_timing(config).await.unwrap();
+
+            // C1NBTCFG, C1DBTCFG and C1TDC are written together
+            let writes = can.spi.writes_to(SFRAddress::C1NBTCFG as u16);
+            assert_eq!(writes.len(), 1);
+            // SID11EN, TDCMOD = Manual, TDCO = 8 and TDCV = 4
+            assert_eq!(writes[0][8..12], 0x0101_0804u32.to_le_bytes());
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn bit_timing_round_trip() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            let config = BitTimeConfiguration::calculate(40_000_000, 500_000, 2_000_000, 80)
+                .unwrap()
+                .with_sid11(true)
+                .with_edge_filtering(true);
+
+            can.configure_bit_timing(config.clone()).await.unwrap();
+
+            let read_back = can.get_bit_timing().await.unwrap();
+            assert_eq!(read_back, config);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn disable_all_filters_clears_every_control_register() {
+        crate::mock::block_on(|| async {
+            const C1FLTCON0: u16 = SFRAddress::C1FLTCON0 as u16;
+
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            // Every filter enabled and pointing at FIFO 1
+            for control_number in 0..8 {
+                can.spi.set_u32(C1FLTCON0 + 4 * control_number, 0x8181_8181);
+            }
+
+            can.disable_all_filters().await.unwrap();
+
+            assert_eq!(can.spi.writes.len(), 8);
+            for control_number in 0..8 {
+                let address = C1FLTCON0 + 4 * control_number;
+                assert_eq!(can.spi.words_written_to(address), [0]);
+            }
+            assert!(can.spi.reads.is_empty());
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn configure_filters_writes_the_control_register_once() {
+        crate::mock::block_on(|| async {
+            const C1FLTCON0: u16 = SFRAddress::C1FLTCON0 as u16;
+
+            let filters = [
+                FilterNumber::Filter0,
+                FilterNumber::Filter1,
+                FilterNumber::Filter2,
+                FilterNumber::Filter3,
+            ]
+            .map(|filter_number| {
+                let config = FilterConfiguration::match_exact(
+                    FifoNumber::Fifo2,
+                    embedded_can::StandardId::new(0x100).unwrap(),
+                );
+                (filter_number, config)
+            });
+
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            can.configure_filters(&filters).await.unwrap();
+
+            // FLTEN and F0BP = 2 for every filter
+            assert_eq!(can.spi.words_written_to(C1FLTCON0), [0x8282_8282]);
+            assert_eq!(can.spi.reads_of(C1FLTCON0), 1);
+
+            // Filter 1 enabled, so it has to be disabled first
+            let mut can = MCP2518FD::new(MockSpi::new());
+            can.spi.set_u32(C1FLTCON0, 0x0000_8100);
+
+            can.configure_filters(&filters).await.unwrap();
+
+            assert_eq!(
+                can.spi.words_written_to(C1FLTCON0),
+                [0x0000_0100, 0x8282_8282]
+            );
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    /// Configures `config` into filter 7 and reads it back
+    async fn filter_round_trip(config: FilterConfiguration) -> Option<FilterConfiguration> {
+        let mut can = MCP2518FD::new(MockSpi::new());
+
+        can.configure_filter(FilterNumber::Filter7, Some(config))
+            .await
+            .unwrap();
+
+        can.get_filter_configuration(FilterNumber::Filter7)
+            .await
+            .unwrap()
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn standard_only_filter_round_trip() {
+        crate::mock::block_on(|| async {
+            let config = FilterConfiguration::match_masked(
+                FifoNumber::Fifo3,
+                embedded_can::StandardId::new(0x123).unwrap(),
+                embedded_can::StandardId::new(0x7F0).unwrap(),
+            )
+            .unwrap();
+
+            let read_back = filter_round_trip(config).await;
+            assert_eq!(read_back, Some(config));
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn extended_only_filter_round_trip() {
+        crate::mock::block_on(|| async {
+            let config = FilterConfiguration::match_masked(
+                FifoNumber::Fifo31,
+                embedded_can::ExtendedId::new(0x18FEF100).unwrap(),
+                embedded_can::ExtendedId::new(0x1FFFFF00).unwrap(),
+            )
+            .unwrap();
+
+            let read_back = filter_round_trip(config).await;
+            assert_eq!(read_back, Some(config));
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn both_filter_round_trip() {
+        crate::mock::block_on(|| async {
+            let config = FilterConfiguration {
+                buffer_pointer: FifoNumber::Fifo1,
+                mode: settings::FilterMatchMode::Both,
+                filter_bits: Id::Standard(embedded_can::StandardId::new(0x555).unwrap()),
+                mask_bits: Id::Standard(embedded_can::StandardId::new(0x700).unwrap()),
+            };
+
+            let read_back = filter_round_trip(config).await;
+            assert_eq!(read_back, Some(config));
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn disabled_filter_reads_back_as_none() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            can.configure_filter(FilterNumber::Filter7, None)
+                .await
+                .unwrap();
+
+            let read_back = can
+                .get_filter_configuration(FilterNumber::Filter7)
+                .await
+                .unwrap();
+            assert_eq!(read_back, None);
+        })
+    }
+
+    /// A chip with an empty 64 byte payload TX FIFO 1 whose next object
+    /// starts at the beginning of RAM
+    fn tx_fifo_with_room() -> MCP2518FD<MockSpi> {
+        let mut can = MCP2518FD::new(MockSpi::new());
+
+        let mut fifocon = FifoControlRegister(0);
+        fifocon.set_txen(true);
+        fifocon.set_payload_size(PayloadSize::Bytes64);
+        can.spi.set_u32(FIFO1CON, fifocon.0);
+        can.spi.set_u32(SFRAddress::C1FIFOSTA1 as u16, 1);
+
+        can
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn tx_fifo_push_writes_whole_dwords_only() {
+        crate::mock::block_on(|| async {
+            for (data_len, written) in [(3, 12), (8, 16), (12, 20), (64, 72)] {
+                let mut can = tx_fifo_with_room();
+                let data: [u8; 64] = core::array::from_fn(|i| i as u8 + 1);
+                let message = TxMessage::<64>::new_fd_std(0x123, &data[..data_len]).unwrap();
+
+                can.tx_fifo_push_message(FifoNumber::Fifo1, &message)
+                    .await
+                    .unwrap();
+
+                let ram_writes = can.spi.writes_to(0x400);
+                assert_eq!(ram_writes.len(), 1);
+                assert_eq!(ram_writes[0].len(), written, "{data_len} byte payload");
+                assert_eq!(&ram_writes[0][8..8 + data_len], &data[..data_len]);
+                assert!(ram_writes[0][8 + data_len..].iter().all(|byte| *byte == 0));
+            }
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn time_base_counter_read_and_set() {
+        crate::mock::block_on(|| async {
+            const C1TBC: u16 = SFRAddress::C1TBC as u16;
+
+            let mut can = MCP2518FD::new(MockSpi::new());
+            can.spi.set_u32(C1TBC, 0xDEADBEEF);
+
+            let tbc = can.read_time_base_counter().await.unwrap();
+
+            assert_eq!(tbc, 0xDEADBEEF);
+            assert_eq!(can.spi.reads, [C1TBC]);
+
+            can.set_time_base_counter(0x1234_5678).await.unwrap();
+            can.reset_time_base_counter().await.unwrap();
+
+            // Both are plain writes of the whole register, without a read
+            let writes = can.spi.writes_to(C1TBC);
+            assert_eq!(writes.len(), 2);
+            assert_eq!(writes[0], 0x1234_5678u32.to_le_bytes());
+            assert_eq!(writes[1], [0; 4]);
+            assert_eq!(can.spi.reads.len(), 1);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn gpio_direction_and_latch() {
+        crate::mock::block_on(|| async {
+            const IOCON: u16 = SFRAddress::IOCON as u16;
+
+            let mut can = MCP2518FD::new(MockSpi::new());
+            // The reset value, both pins are GPIO inputs
+            can.spi.set_u32(IOCON, 0x0300_0003);
+
+            can.configure_gpio(GpioPin::Gpio1, PinMode::Output)
+                .await
+                .unwrap();
+            can.set_gpio_output(GpioPin::Gpio1, true).await.unwrap();
+            can.configure_gpio(GpioPin::Gpio0, PinMode::Interrupt)
+                .await
+                .unwrap();
+            can.set_gpio_output(GpioPin::Gpio1, false).await.unwrap();
+
+            assert_eq!(
+                can.spi.words_written_to(IOCON),
+                [
+                    // TRIS1 cleared
+                    0x0300_0001,
+                    // LAT1 set
+                    0x0300_0201,
+                    // PM0 cleared
+                    0x0200_0201,
+                    // LAT1 cleared
+                    0x0200_0001,
+                ]
+            );
+
+            // GPIO1 high
+            can.spi.set_u32(IOCON, 0x0202_0001);
+            let gpio0 = can.read_gpio_input(GpioPin::Gpio0).await.unwrap();
+            let gpio1 = can.read_gpio_input(GpioPin::Gpio1).await.unwrap();
+            assert!(!gpio0);
+            assert!(gpio1);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn configure_osc_sets_clock_output_divider() {
+        crate::mock::block_on(|| async {
+            for (clock_output, clkodiv) in [
+                (ClockOutput::SysClk, 0b00),
+                (ClockOutput::DivBy2, 0b01),
+                (ClockOutput::DivBy4, 0b10),
+                (ClockOutput::DivBy10, 0b11),
+            ] {
+                let mut can = MCP2518FD::new(MockSpi::new());
+
+                // Start with every CLKODIV bit set, so each variant has to clear some
+                can.spi.set_u32(OSC, 0b11 << 5);
+
+                let oscillator = OscillatorConfiguration::new(Pll::Off, SysClkDivider::DivByOne)
+                    .with_clock_output(clock_output);
+                can.configure_osc(oscillator, &mut MockDelay::default())
+                    .await
+                    .unwrap();
+
+                let osc = crate::mock::u32_at(&can.spi.memory, OSC);
+                assert_eq!((osc >> 5) & 0b11, clkodiv, "CLKODIV {clkodiv:#04b}");
+            }
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn stats_count_transmitted_frames() {
+        crate::mock::block_on(|| async {
+            let mut can = tx_fifo_with_room();
+
+            // C1CON.TXQEN, and a TXQ with room for a message
+            can.spi.set_u32(C1CON, 1 << 20);
+            can.spi.set_u32(SFRAddress::C1TXQSTA as u16, 1);
+
+            let message = TxMessage::<8>::new_2_0_std(0x123, &[1, 2, 3]).unwrap();
+
+            for _ in 0..5 {
+                can.tx_fifo_push_message(FifoNumber::Fifo1, &message)
+                    .await
+                    .unwrap();
+            }
+
+            assert_eq!(can.stats().frames_transmitted, 5);
+
+            for _ in 0..3 {
+                can.tx_queue_push_message(&message).await.unwrap();
+            }
+
+            assert_eq!(can.stats().frames_transmitted, 8);
+            assert_eq!(can.stats().frames_received, 0);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn stats_count_overflows_and_attempt_failures() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(MockSpi::new());
+            let fifosta = SFRAddress::C1FIFOSTA1 as u16;
+
+            // RXOVIF and TXATIF of FIFO 1, TXATIF of the TXQ
+            can.spi.set_u32(fifosta, (1 << 3) | (1 << 4));
+            can.spi.set_u32(SFRAddress::C1TXQSTA as u16, 1 << 4);
+
+            can.clear_rx_overflow(FifoNumber::Fifo1).await.unwrap();
+            can.clear_tx_attempts_exhausted(Some(FifoNumber::Fifo1))
+                .await
+                .unwrap();
+            can.clear_tx_attempts_exhausted(None).await.unwrap();
+
+            assert_eq!(can.stats().rx_overflows, 1);
+            assert_eq!(can.stats().tx_attempt_failures, 2);
+
+            // Clearing flags that aren't set isn't counted
+            can.clear_rx_overflow(FifoNumber::Fifo1).await.unwrap();
+            can.clear_tx_attempts_exhausted(None).await.unwrap();
+
+            assert_eq!(can.stats().rx_overflows, 1);
+            assert_eq!(can.stats().tx_attempt_failures, 2);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn reset_stats_zeroes_every_counter() {
+        crate::mock::block_on(|| async {
+            let mut can = rx_fifo_with_message(8, false);
+            can.spi.set_u32(SFRAddress::C1FIFOSTA1 as u16, 1 | (1 << 3));
+
+            can.rx_fifo_get_next(FifoNumber::Fifo1).await.unwrap();
+            can.clear_rx_overflow(FifoNumber::Fifo1).await.unwrap();
+            assert_ne!(can.stats(), DriverStats::default());
+
+            can.reset_stats();
+
+            assert_eq!(can.stats(), DriverStats::default());
+        })
+    }
+
+    /// A chip with an enabled TXQ that has room for a message, and runs empty
+    /// (`TXQEIF`) on the `sent_on`th poll of C1TXQSTA after transmission was
+    /// requested, or never if `None`
+    fn tx_queue_sending(sent_on: Option<usize>) -> MCP2518FD<MockSpi> {
+        const C1TXQCON: u16 = SFRAddress::C1TXQCON as u16;
+        const C1TXQSTA: u16 = SFRAddress::C1TXQSTA as u16;
+
+        let mut polls = 0;
+
+        let spi = MockSpi::new().on_read(move |memory, address| {
+            let txreq = crate::mock::u32_at(memory, C1TXQCON) & (1 << 9) != 0;
+
+            if address == C1TXQSTA && txreq {
+                polls += 1;
+
+                if sent_on.is_some_and(|sent_on| polls >= sent_on) {
+                    crate::mock::set_u32(memory, C1TXQSTA, 1 | (1 << 2));
+                }
+            }
+        });
+
+        let mut can = MCP2518FD::new(spi);
+        // C1CON.TXQEN, and C1TXQSTA.TXQNIF
+        can.spi.set_u32(C1CON, 1 << 20);
+        can.spi.set_u32(C1TXQSTA, 1);
+
+        can
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn transmit_and_wait_polls_until_sent() {
+        crate::mock::block_on(|| async {
+            let mut can = tx_queue_sending(Some(3));
+            let mut delay = MockDelay::default();
+
+            let message = TxMessage::<8>::new_2_0_std(0x123, &[1, 2, 3]).unwrap();
+            can.transmit_and_wait(&message, &mut delay, 1_000)
+                .await
+                .unwrap();
+
+            assert_eq!(delay.elapsed_ns, 2 * 100_000);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn transmit_and_wait_timeout() {
+        crate::mock::block_on(|| async {
+            let mut can = tx_queue_sending(None);
+            let mut delay = MockDelay::default();
+
+            let message = TxMessage::<8>::new_2_0_std(0x123, &[1, 2, 3]).unwrap();
+            let result = can.transmit_and_wait(&message, &mut delay, 1_000).await;
+
+            assert!(matches!(result, Err(Error::TransmitTimeout)));
+            assert_eq!(delay.elapsed_ns, 1_000_000);
+        })
+    }
+
+    /// A chip that clears `C1CON.ABAT` on the `done_on`th poll of C1CON after
+    /// it was set, or never if `None`
+    fn aborting_chip(done_on: Option<usize>) -> MCP2518FD<MockSpi> {
+        let mut polls = 0;
+
+        MCP2518FD::new(MockSpi::new().on_read(move |memory, address| {
+            let c1con = crate::mock::u32_at(memory, C1CON);
+
+            if address == C1CON && c1con & (1 << 27) != 0 {
+                polls += 1;
+
+                if done_on.is_some_and(|done_on| polls >= done_on) {
+                    crate::mock::set_u32(memory, C1CON, c1con & !(1 << 27));
+                }
+            }
+        }))
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn abort_all_transmissions_sets_abat() {
+        crate::mock::block_on(|| async {
+            let mut can = aborting_chip(Some(2));
+            let mut delay = MockDelay::default();
+
+            can.abort_all_transmissions(&mut delay).await.unwrap();
+
+            let c1con_writes = can.spi.writes_to(C1CON);
+            assert_eq!(c1con_writes.len(), 1);
+            assert_ne!(c1con_writes[0][3] & (1 << 3), 0);
+            assert_eq!(delay.elapsed_ns, 500_000);
+
+            // ABAT never clears
+            let mut can = aborting_chip(None);
+            let mut delay = MockDelay::default();
+
+            let result = can.abort_all_transmissions(&mut delay).await;
+            assert!(matches!(result, Err(Error::AbortTimeout)));
+            assert_eq!(delay.elapsed_ns, 4 * 500_000);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn abort_fifo_and_tx_queue_clear_txreq_only() {
+        crate::mock::block_on(|| async {
+            let mut can = tx_fifo_with_room();
+            let txqcon = SFRAddress::C1TXQCON as u16;
+
+            // TXREQ set on top of the configuration
+            let fifocon = crate::mock::u32_at(&can.spi.memory, FIFO1CON);
+            can.spi.set_u32(FIFO1CON, fifocon | (1 << 9));
+            can.spi.set_u32(txqcon, 0x0700_0000 | (1 << 9));
+
+            can.abort_fifo_transmission(FifoNumber::Fifo1)
+                .await
+                .unwrap();
+            can.abort_tx_queue_transmission().await.unwrap();
+
+            assert_eq!(crate::mock::u32_at(&can.spi.memory, FIFO1CON), fifocon);
+            assert_eq!(crate::mock::u32_at(&can.spi.memory, txqcon), 0x0700_0000);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn reset_methods_set_freset_only() {
+        crate::mock::block_on(|| async {
+            let mut can = tx_fifo_with_room();
+            let txqcon = SFRAddress::C1TXQCON as u16;
+            let tefcon = SFRAddress::C1TEFCON as u16;
+
+            let fifocon = crate::mock::u32_at(&can.spi.memory, FIFO1CON);
+            can.spi.set_u32(txqcon, 0x0700_0000);
+            can.spi.set_u32(tefcon, 0x0300_0020);
+
+            can.reset_fifo(FifoNumber::Fifo1).await.unwrap();
+            can.reset_tx_queue().await.unwrap();
+            can.reset_tef().await.unwrap();
+
+            // FRESET is bit 10 of all three
+            let written = |address: u16| {
+                let writes = can.spi.writes_to(address);
+                assert_eq!(writes.len(), 1);
+
+                u32::from_le_bytes(writes[0].try_into().unwrap())
+            };
+
+            assert_eq!(written(FIFO1CON), fifocon | (1 << 10));
+            assert_eq!(written(txqcon), 0x0700_0000 | (1 << 10));
+            assert_eq!(written(tefcon), 0x0300_0020 | (1 << 10));
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn clear_all_fifos_resets_every_fifo() {
+        crate::mock::block_on(|| async {
+            let mut can = tx_fifo_with_room();
+
+            can.clear_all_fifos().await.unwrap();
+
+            // FRESET and unlimited retransmissions (TXAT = 0b10) in every
+            // FIFOCON, with the status and user address registers zeroed
+            for fifo in 0..31 {
+                let fifocon = FIFO1CON + fifo * 12;
+
+                assert_eq!(
+                    crate::mock::u32_at(&can.spi.memory, fifocon),
+                    (0b10 << 21) | (1 << 10)
+                );
+                assert_eq!(crate::mock::u32_at(&can.spi.memory, fifocon + 4), 0);
+            }
+
+            // The CRC instructions split the 372 byte block in two
+            let block_transactions = if cfg!(feature = "spi-crc") { 2 } else { 1 };
+            assert_eq!(can.spi.transactions, block_transactions);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn request_transmission_multi_writes_txreq_once() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            can.request_transmission_multi(&[FifoNumber::Fifo2, FifoNumber::Fifo5], false)
+                .await
+                .unwrap();
+
+            // A single write and nothing read
+            let writes = can.spi.writes_to(SFRAddress::C1TXREQ as u16);
+            assert_eq!(can.spi.transactions, 1);
+            assert_eq!(writes.len(), 1);
+            assert_eq!(
+                u32::from_le_bytes(writes[0].try_into().unwrap()),
+                (1 << 2) | (1 << 5)
+            );
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn tx_queue_push_takes_4_transactions() {
+        crate::mock::block_on(|| async {
+            let mut can = tx_queue_sending(None);
+
+            let message = TxMessage::<8>::new_2_0_std(0x123, &[1, 2, 3]).unwrap();
+            can.tx_queue_push_message(&message).await.unwrap();
+
+            // C1CON, then C1TXQCON to C1TXQUA in one burst, the object, and
+            // C1TXQCON.UINC
+            assert_eq!(can.spi.transactions, 4);
+            assert_eq!(can.spi.reads, [C1CON, SFRAddress::C1TXQCON as u16]);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn tx_queue_push_64_bytes_stays_in_object() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(MockSpi::new());
+
+            // C1CON.TXQEN, a 64 byte payload TXQ with room for a message
+            can.spi.set_u32(C1CON, 1 << 20);
+            let mut txqcon = FifoControlRegister(0);
+            txqcon.set_payload_size(PayloadSize::Bytes64);
+            can.spi.set_u32(SFRAddress::C1TXQCON as u16, txqcon.0);
+            can.spi.set_u32(SFRAddress::C1TXQSTA as u16, 1);
+
+            let message = TxMessage::<64>::new_fd_std(0x123, &[0xAA; 64]).unwrap();
+            can.tx_queue_push_message(&message).await.unwrap();
+
+            let ram_writes = can.spi.writes_to(0x400);
+            assert_eq!(ram_writes.len(), 1);
+            assert_eq!(ram_writes[0].len(), 72);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn rx_fifo_get_next_12_byte_payload() {
+        crate::mock::block_on(|| async {
+            let mut can = rx_fifo_with_message(12, true);
+
+            let message = can
+                .rx_fifo_get_next(FifoNumber::Fifo1)
+                .await
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(message.data(), &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+        })
+    }
+
+    #[test]
+    fn tx_message_payload_buffer_size() {
+        assert!(TxMessage::<8>::new_2_0_std(0x123, &[0; 8]).is_some());
+        assert!(TxMessage::<8>::new_fd_std(0x123, &[0; 12]).is_none());
+        assert!(TxMessage::<64>::new_fd_std(0x123, &[0; 64]).is_some());
+
+        let message = TxMessage::<8>::new_2_0_std(0x123, &[1, 2, 3]).unwrap();
+        let (len, bytes) = message.as_bytes();
+
+        assert_eq!(len, 11);
+        assert_eq!(&bytes[8..11], &[1, 2, 3]);
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn rtr_responder_rejects_configuration_mode() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(MockSpi::new().on_write(instant_mode_changes));
+            can.spi
+                .set_u32(C1CON, c1con_in_mode(OperationMode::Configuration));
+
+            let response = TxMessage::<8>::new_2_0_std(0x123, &[1, 2, 3, 4]).unwrap();
+
+            let result = can
+                .configure_rtr_responder(
+                    FifoNumber::Fifo1,
+                    FilterNumber::Filter0,
+                    &response,
+                    &mut MockDelay::default(),
+                )
+                .await;
+            assert!(matches!(result, Err(ConfigError::InConfigurationMode)));
+            assert!(can.spi.writes.is_empty());
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn rtr_responder_register_sequence() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(MockSpi::new().on_write(instant_mode_changes));
+            can.spi
+                .set_u32(C1CON, c1con_in_mode(OperationMode::NormalCanFD));
+            // C1FIFOSTA1.TFNRFNIF, and C1FIFOUA1 pointing at the start of RAM
+            can.spi.set_u32(SFRAddress::C1FIFOSTA1 as u16, 1);
+
+            let response = TxMessage::<8>::new_2_0_std(0x123, &[1, 2, 3, 4]).unwrap();
+
+            can.configure_rtr_responder(
+                FifoNumber::Fifo1,
+                FilterNumber::Filter0,
+                &response,
+                &mut MockDelay::default(),
+            )
+            .await
+            .unwrap();
+
+            let position = |address: u16| {
+                can.spi
+                    .writes
+                    .iter()
+                    .position(|(start, _)| *start == address)
+                    .unwrap()
+            };
+            let last_position = |address: u16| {
+                can.spi
+                    .writes
+                    .iter()
+                    .rposition(|(start, _)| *start == address)
+                    .unwrap()
+            };
+
+            // TXEN and RTREN are set, and filter 0 points at FIFO 1 and matches
+            // the response ID exactly
+            let fifocon = FifoControlRegister(u32::from_le_bytes(
+                can.spi.writes[position(FIFO1CON)].1[..4]
+                    .try_into()
+                    .unwrap(),
+            ));
+            assert!(fifocon.txen());
+            assert!(fifocon.rtren());
+
+            assert_eq!(
+                crate::mock::u32_at(&can.spi.memory, SFRAddress::C1FLTCON0 as u16) & 0xFF,
+                (1 << 7) | 1
+            );
+            assert_eq!(
+                crate::mock::u32_at(&can.spi.memory, SFRAddress::C1FLTOBJ0 as u16),
+                0x123
+            );
+            assert_eq!(
+                crate::mock::u32_at(&can.spi.memory, SFRAddress::C1MASK0 as u16) & 0x7FF,
+                0x7FF
+            );
+
+            // Configuration mode is requested first and Normal mode is restored
+            // before the response is loaded and UINC is set
+            let c1con_writes = can.spi.writes_to(C1CON);
+            let requested =
+                |data: &[u8]| (u32::from_le_bytes(data[..4].try_into().unwrap()) >> 24) & 0b111;
+            let configuration: u8 = OperationMode::Configuration.into();
+            let normal: u8 = OperationMode::NormalCanFD.into();
+
+            assert_eq!(requested(c1con_writes[0]), configuration as u32);
+            assert_eq!(requested(c1con_writes.last().unwrap()), normal as u32);
+
+            assert!(position(C1CON) < position(FIFO1CON));
+            assert!(last_position(SFRAddress::C1MASK0 as u16) < last_position(C1CON));
+            assert!(last_position(C1CON) < position(0x400));
+            assert!(position(0x400) < last_position(FIFO1CON));
+
+            assert_eq!(&can.spi.memory[0x408..0x40C], &[1, 2, 3, 4]);
+            assert_ne!(can.spi.writes[last_position(FIFO1CON)].1[1] & 1, 0);
+        })
+    }
+
+    /// Stands in for `embedded_hal_bus::spi::RefCellDevice`: the bus is only
+    /// borrowed for the duration of each transaction, so other devices can
+    /// use it in between
+    struct SharedDevice<'a>(&'a core::cell::RefCell<MockSpi>);
+
+    impl embedded_hal::spi::ErrorType for SharedDevice<'_> {
+        type Error = embedded_hal::spi::ErrorKind;
+    }
+
+    #[cfg(not(feature = "async"))]
+    impl SpiDevice<u8> for SharedDevice<'_> {
+        fn transaction(
+            &mut self,
+            operations: &mut [embedded_hal::spi::Operation<'_, u8>],
+        ) -> Result<(), Self::Error> {
+            self.0.borrow_mut().transfer(operations)
+        }
+    }
+
+    #[cfg(feature = "async")]
+    impl SpiDevice<u8> for SharedDevice<'_> {
+        async fn transaction(
+            &mut self,
+            operations: &mut [embedded_hal::spi::Operation<'_, u8>],
+        ) -> Result<(), Self::Error> {
+            self.0.borrow_mut().transfer(operations)
+        }
+    }
+
+    /// A chip that answers to its device ID and sits in Configuration mode
+    fn configurable_chip() -> MockSpi {
+        let mut spi = MockSpi::new().on_write(instant_mode_changes);
+        spi.set_u32(SFRAddress::DEVID as u16, 0x14);
+        spi.set_u32(C1CON, c1con_in_mode(OperationMode::Configuration));
+        spi
+    }
+
+    fn shared_bus_settings() -> settings::Settings {
+        settings::Settings::new(BitTimeConfiguration::new(
+            NominalBitTimeConfiguration::RATE_500_KBIT,
+            DataBitTimeConfiguration::RATE_2_MBIT,
+        ))
+        .with_verify_writes(true)
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn configure_rejects_garbled_device_id() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(configurable_chip());
+            can.spi.set_u32(SFRAddress::DEVID as u16, u32::MAX);
+
+            let result = can
+                .configure(shared_bus_settings(), &mut MockDelay::default())
+                .await;
+            assert!(matches!(result, Err(ConfigError::SpiCommunicationFailed)));
+            assert!(can.spi.writes.is_empty());
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn configure_accepts_zero_device_id() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(configurable_chip());
+            can.spi.set_u32(SFRAddress::DEVID as u16, 0);
+
+            can.configure(shared_bus_settings(), &mut MockDelay::default())
+                .await
+                .unwrap();
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn configure_sets_txbws() {
+        use crate::memory::controller::configuration::InterTransmissionDelay;
+
+        crate::mock::block_on(|| async {
+            for (delay, txbws) in [
+                (None, 0),
+                (Some(InterTransmissionDelay::NoDelay), 0),
+                (Some(InterTransmissionDelay::Delay2), 1),
+                (Some(InterTransmissionDelay::Delay16), 4),
+                (Some(InterTransmissionDelay::Delay2048), 11),
+                (Some(InterTransmissionDelay::Delay4096), 12),
+            ] {
+                let mut can = MCP2518FD::new(configurable_chip());
+                let mut settings = shared_bus_settings();
+                settings.inter_transmission_delay = delay;
+
+                can.configure(settings, &mut MockDelay::default())
+                    .await
+                    .unwrap();
+
+                let c1con = crate::mock::u32_at(&can.spi.memory, C1CON);
+                assert_eq!(c1con >> 28, txbws, "{delay:?}");
+            }
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn configure_reports_mcp2517fd() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(configurable_chip());
+            can.spi.set_u32(SFRAddress::DEVID as u16, 0);
+
+            let settings = shared_bus_settings().with_require_mcp2518fd(true);
+
+            let result = can.configure(settings, &mut MockDelay::default()).await;
+            assert!(matches!(
+                result,
+                Err(ConfigError::UnexpectedDeviceId { id: 0, rev: 0 })
+            ));
+            assert_eq!(can.spi.reads_of(SFRAddress::DEVID as u16), 1);
+
+            let mut can = MCP2518FD::new(configurable_chip());
+            let settings = shared_bus_settings().with_require_mcp2518fd(true);
+
+            can.configure(settings, &mut MockDelay::default())
+                .await
+                .unwrap();
+            assert_eq!(can.spi.reads_of(SFRAddress::DEVID as u16), 1);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn configure_clears_wake_up_filter() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(configurable_chip());
+            let c1con = c1con_in_mode(OperationMode::Configuration);
+            can.spi.set_u32(C1CON, c1con | (1 << 8));
+
+            can.configure(shared_bus_settings(), &mut MockDelay::default())
+                .await
+                .unwrap();
+
+            let c1con = can.read_register::<CanControlRegister>().await.unwrap();
+            assert!(!c1con.wakfil());
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn configure_keeps_c1con_reset_defaults() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(configurable_chip());
+
+            can.configure(shared_bus_settings(), &mut MockDelay::default())
+                .await
+                .unwrap();
+
+            let c1con = can.read_register::<CanControlRegister>().await.unwrap();
+            assert!(c1con.isocrcen());
+            assert!(c1con.pxedis());
+            assert!(!c1con.brsdis());
+            assert!(!c1con.esigm());
+            assert!(!c1con.serr2lom());
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn configure_applies_c1con_flags() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(configurable_chip());
+
+            let settings = shared_bus_settings()
+                .with_iso_crc(false)
+                .with_protocol_exception_disabled(false)
+                .with_bit_rate_switching_disabled(true)
+                .with_esi_gateway_mode(true)
+                .with_listen_only_on_system_error(true);
+
+            can.configure(settings, &mut MockDelay::default())
+                .await
+                .unwrap();
+
+            let c1con = can.read_register::<CanControlRegister>().await.unwrap();
+            assert!(!c1con.isocrcen());
+            assert!(!c1con.pxedis());
+            assert!(c1con.brsdis());
+            assert!(c1con.esigm());
+            assert!(c1con.serr2lom());
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn configure_disables_every_interrupt() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(configurable_chip());
+
+            // Left enabled by a previous configuration
+            can.spi.set_u32(SFRAddress::C1INT as u16, 0xFFFF_0000);
+
+            let settings = shared_bus_settings()
+                .with_tbc_overflow_interrupt(false)
+                .with_rx_interrupt(false)
+                .with_tx_interrupt(false)
+                .with_mode_change_interrupt(false)
+                .with_rx_overflow_interrupt(false)
+                .with_tx_attempt_interrupt(false)
+                .with_can_error_interrupts(false)
+                .with_spi_error_interrupt(false)
+                .with_ecc_error_interrupt(false)
+                .with_wake_up_interrupt(false);
+
+            can.configure(settings, &mut MockDelay::default())
+                .await
+                .unwrap();
+
+            // Bits 23:21 are unimplemented, every other bit of the upper half
+            // is an interrupt enable
+            let c1int = crate::mock::u32_at(&can.spi.memory, SFRAddress::C1INT as u16);
+            assert_eq!(c1int & 0xFF1F_0000, 0);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn configure_enables_tbc_without_its_interrupt() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(configurable_chip());
+
+            let settings = shared_bus_settings()
+                .with_time_based_counter(true)
+                .with_tbc_overflow_interrupt(false);
+
+            can.configure(settings, &mut MockDelay::default())
+                .await
+                .unwrap();
+
+            let c1tscon = crate::mock::u32_at(&can.spi.memory, SFRAddress::C1TSCON as u16);
+            let c1int = crate::mock::u32_at(&can.spi.memory, SFRAddress::C1INT as u16);
+
+            // C1TSCON.TBCEN and C1INT.TBCIE
+            assert_eq!((c1tscon >> 16) & 1, 1);
+            assert_eq!((c1int >> 18) & 1, 0);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn configure_restricts_retransmissions() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(configurable_chip());
+
+            can.configure(shared_bus_settings(), &mut MockDelay::default())
+                .await
+                .unwrap();
+
+            // Without C1CON.RTXAT the TXAT field of every FIFO is ignored
+            let c1con = can.read_register::<CanControlRegister>().await.unwrap();
+            assert!(c1con.rtxat());
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn configure_with_batched_spi_check() {
+        crate::mock::block_on(|| async {
+            let mut can = MCP2518FD::new(configurable_chip());
+            can.configure(shared_bus_settings(), &mut MockDelay::default())
+                .await
+                .unwrap();
+            let unbatched = can.spi.transactions;
+
+            let mut can = MCP2518FD::new(configurable_chip());
+            let settings = shared_bus_settings().with_batched_spi_check(true);
+            can.configure(settings, &mut MockDelay::default())
+                .await
+                .unwrap();
+            let batched = can.spi.transactions;
+
+            // One RAM write and one read instead of 32 of each
+            assert_eq!(unbatched - batched, 62);
+            assert_eq!(can.spi.reads_of(0x400), 1);
+            assert_eq!(can.spi.writes_to(0x400).len(), 1);
+        })
+    }
+
+    #[cfg_attr(not(feature = "async"), maybe_async::maybe_async)]
+    #[test]
+    fn configure_on_shared_bus() {
+        crate::mock::block_on(|| async {
+            let bus = core::cell::RefCell::new(configurable_chip());
+            let mut can = MCP2518FD::new(SharedDevice(&bus));
+
+            can.configure(shared_bus_settings(), &mut MockDelay::default())
+                .await
+                .unwrap();
+
+            // The driver doesn't hold on to the bus between calls, so another
+            // device can use it
+            bus.borrow_mut().set_u32(0x400, 0);
+
+            can.configure(shared_bus_settings(), &mut MockDelay::default())
+                .await
+                .unwrap();
+
+            // The bit timing registers are written in a single burst
+            let bus = bus.borrow();
+            let bit_timing_writes = bus.writes_to(SFRAddress::C1NBTCFG as u16);
+            assert!(bit_timing_writes.iter().all(|data| data.len() == 12));
+            assert_eq!(
+                bus.writes_to(SFRAddress::C1DBTCFG as u16).len()
+                    + bus.writes_to(SFRAddress::C1TDC as u16).len(),
+                0
+            );
+        })
+    }
 }